@@ -0,0 +1,45 @@
+/* The C interface to Caw, built with the `ffi` feature. */
+
+#ifndef CAW_H
+#define CAW_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The call succeeded. */
+#define CAW_OK 0
+/* A pointer was null, or text wasn't valid UTF-8. */
+#define CAW_INVALID_ARGUMENT -1
+/* The armor couldn't be doffed. The output buffer holds a description of the problem. */
+#define CAW_DOFF_FAILED 1
+
+/* Mappings which are owned by C code. */
+typedef struct CawDict CawDict;
+
+/* Bytes which are owned by Rust, and must be released with `caw_free`. */
+typedef struct CawBuffer {
+	uint8_t *data;
+	size_t len;
+} CawBuffer;
+
+/* Build today's mappings for the 128-bit seed made of `seed_high` and `seed_low`. */
+CawDict *caw_dict_new(uint64_t seed_high, uint64_t seed_low);
+/* Release mappings built by `caw_dict_new`. */
+void caw_dict_free(CawDict *dict);
+/* Don `len` bytes at `data`, writing the messages to `out` one per line. */
+int caw_don(const CawDict *dict, const uint8_t *data, size_t len, size_t character_limit,
+	CawBuffer *out);
+/* Doff `len` bytes of text with one message per line, writing the recovered bytes to `out`. */
+int caw_doff(const CawDict *dict, const uint8_t *messages, size_t len, CawBuffer *out);
+/* Release a buffer written by `caw_don` or `caw_doff`. */
+void caw_free(CawBuffer buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
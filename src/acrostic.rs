@@ -0,0 +1,172 @@
+//! Carrying armor in the first letters of sentences.
+//!
+//! Each message of armor is spelled out by a message of sentences. Every word of the armor is
+//! written as its index in the dictionary, in base 26 with as many digits as the dictionary's
+//! last index needs (four for English), most significant first. Each digit is the first
+//! letter of a sentence, and the letters stand for digits in an order shuffled by the
+//! mappings' key, so the same armor spells something different every day. The rest of each
+//! sentence is a few words picked at random, which carry nothing.
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use sha2::{Digest, Sha256};
+
+use crate::{doff, don_with, CawError, DictMappings, Dictionary, Options, Separator};
+
+/// The letters which sentences start with, in the order of the digits they stand for under
+/// `key`.
+fn alphabet(key: &[u8; 32]) -> Vec<char> {
+	let mut rng = Pcg64::from_seed(Sha256::digest([&b"caw acrostic "[..], key].concat()).into());
+	let mut letters: Vec<char> = ('a'..='z').collect();
+	letters.shuffle(&mut rng);
+	letters
+}
+
+/// The number of letters each word of armor is spelled with.
+fn digits<D: Dictionary>(dict: &DictMappings<D>) -> u32 {
+	let mut digits = 1;
+	while 26usize.pow(digits) < dict.dictionary.len() {
+		digits += 1;
+	}
+	digits
+}
+
+/// Don armor, and spell out each message in the first letters of sentences. The
+/// `character_limit` applies to the armor, and each of its words takes several sentences
+/// (four with English), so the messages are far longer. Separators, fillers and natural
+/// rendering don't mean anything here, so they're ignored, but the rest of `options` is used.
+///
+/// Panics if the dictionary doesn't have a word made only of ASCII letters starting with
+/// every letter from a to z.
+pub fn don_acrostic<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+) -> Vec<String> {
+	let options = Options {
+		separator: Separator::Space,
+		fillers: vec![],
+		natural: false,
+		..options.clone()
+	};
+	let alphabet = alphabet(&dict.key);
+	let digits = digits(dict);
+	let plain: Vec<&str> = (0..dict.dictionary.len() as u32)
+		.map(|v| dict.dictionary.word(v))
+		.filter(|v| !v.is_empty() && v.chars().all(|v| v.is_ascii_alphabetic()))
+		.collect();
+	let starting: Vec<Vec<&str>> = ('a'..='z')
+		.map(|letter| {
+			plain
+				.iter()
+				.copied()
+				.filter(|v| v.chars().next().unwrap().to_ascii_lowercase() == letter)
+				.collect()
+		})
+		.collect();
+	assert!(
+		starting.iter().all(|v| !v.is_empty()),
+		"the dictionary needs words starting with every letter"
+	);
+	let mut rng = rand::thread_rng();
+	don_with(data, dict, character_limit, &options)
+		.iter()
+		.map(|message| {
+			let mut sentences: Vec<String> = vec![];
+			for word in message.split(' ') {
+				let mut index = dict
+					.dictionary
+					.index_of(word)
+					.expect("armor is made of dictionary words") as usize;
+				let mut letters = vec![];
+				for _ in 0..digits {
+					letters.push(alphabet[index % 26]);
+					index /= 26;
+				}
+				for letter in letters.into_iter().rev() {
+					let first = starting[(letter as u8 - b'a') as usize]
+						.choose(&mut rng)
+						.unwrap();
+					let mut sentence = first[..1].to_ascii_uppercase() + &first[1..].to_lowercase();
+					for _ in 0..rng.gen_range(2..=5) {
+						sentence.push(' ');
+						sentence.push_str(&plain.choose(&mut rng).unwrap().to_lowercase());
+					}
+					sentence.push('.');
+					sentences.push(sentence);
+				}
+			}
+			sentences.join(" ")
+		})
+		.collect()
+}
+
+/// Doff armor spelled out by `don_acrostic`. Sentences end at periods, question marks and
+/// exclamation marks, and only their first letters are read.
+pub fn doff_acrostic<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+) -> Result<Vec<u8>, CawError> {
+	let alphabet = alphabet(&dict.key);
+	let digits = digits(dict) as usize;
+	let armor = messages
+		.iter()
+		.enumerate()
+		.map(|(message, text)| {
+			let letters: Vec<usize> = text
+				.split(['.', '?', '!'])
+				.filter_map(|v| v.chars().find(|v| v.is_ascii_alphabetic()))
+				.map(|v| {
+					let letter = v.to_ascii_lowercase();
+					alphabet.iter().position(|v| *v == letter).unwrap()
+				})
+				.collect();
+			let words = letters
+				.chunks(digits)
+				// Letters left over at the end don't make up a word
+				.filter(|v| v.len() == digits)
+				.map(|v| {
+					let index = v.iter().fold(0, |index, v| index * 26 + v);
+					if index < dict.dictionary.len() {
+						Ok(dict.dictionary.word(index as u32))
+					} else {
+						Err(CawError::UnknownWord {
+							message,
+							word: index.to_string(),
+						})
+					}
+				})
+				.collect::<Result<Vec<&str>, CawError>>()?;
+			Ok(words.join(" "))
+		})
+		.collect::<Result<Vec<String>, CawError>>()?;
+	doff(&armor, dict)
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::{doff_acrostic, don_acrostic};
+	use crate::{today, DictMappings, Options};
+
+	#[test]
+	fn acrostic() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Read between the lines".as_bytes();
+		let options = Options {
+			checksum: true,
+			..Options::default()
+		};
+		let messages = don_acrostic(test_data, &dict, 50, &options);
+		assert!(messages.len() > 1);
+		for message in &messages {
+			// Four sentences for every word of armor
+			assert_eq!(message.matches('.').count() % 4, 0);
+			assert!(message.starts_with(|v: char| v.is_ascii_uppercase()));
+		}
+		assert_eq!(doff_acrostic(&messages, &dict).unwrap(), test_data);
+		let tomorrow = DictMappings::from_seed(69, &today().succ_opt().unwrap());
+		assert!(doff_acrostic(&messages, &tomorrow).is_err());
+	}
+}
@@ -0,0 +1,144 @@
+//! Measuring how much armor stands out from ordinary English, so options can be compared.
+//!
+//! None of this needs the mappings, since automated filters don't have them either.
+
+use std::collections::HashSet;
+
+use crate::natural;
+
+/// The hundred most common words in English writing, which make up about half of it.
+const COMMON: &[&str] = &[
+	"the", "be", "to", "of", "and", "a", "in", "that", "have", "i", "it", "for", "not", "on",
+	"with", "he", "as", "you", "do", "at", "this", "but", "his", "by", "from", "they", "we", "say",
+	"her", "she", "or", "an", "will", "my", "one", "all", "would", "there", "their", "what", "so",
+	"up", "out", "if", "about", "who", "get", "which", "go", "me", "when", "make", "can", "like",
+	"time", "no", "just", "him", "know", "take", "people", "into", "year", "your", "good", "some",
+	"could", "them", "see", "other", "than", "then", "now", "look", "only", "come", "its", "over",
+	"think", "also", "back", "after", "use", "two", "how", "our", "work", "first", "well", "way",
+	"even", "new", "want", "because", "any", "these", "give", "day", "most", "us", "is", "was",
+	"are",
+];
+
+/// The fraction of English writing made up of `COMMON` words.
+const COMMON_SHARE: f64 = 0.5;
+
+/// The share of words in English writing with each length from 1 to 13 letters, and 14 or
+/// more.
+const LENGTHS: [f64; 14] = [
+	0.030, 0.171, 0.212, 0.159, 0.109, 0.084, 0.078, 0.059, 0.043, 0.027, 0.015, 0.008, 0.004,
+	0.001,
+];
+
+/// Statistics of some messages which tell armor apart from English.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detectability {
+	/// The number of words in the messages.
+	pub words: usize,
+	/// The fraction of words which are among the hundred most common in English. About half
+	/// of English writing is, and hardly any of most armor.
+	pub common_words: f64,
+	/// The Kullback–Leibler divergence, in bits, of the lengths of the words from the lengths
+	/// of English words. It's 0 if they're distributed just like English.
+	pub length_divergence: f64,
+	/// The Shannon entropy, in bits, of the lengths of the words. English's is about 3.2.
+	pub length_entropy: f64,
+	/// The fraction of pairs of neighbouring words which already appeared earlier, which
+	/// armor of repetitive data has more of than English.
+	pub repeated_pairs: f64,
+}
+
+impl Detectability {
+	/// A score from 0, for text which looks like English, to 1, for text which nobody would
+	/// mistake for it. It's the average of how far short of English's share of common words
+	/// the text falls, the length divergence up to 1 bit, and the fraction of repeated pairs.
+	pub fn score(&self) -> f64 {
+		let common = (1.0 - self.common_words / COMMON_SHARE).clamp(0.0, 1.0);
+		(common + self.length_divergence.min(1.0) + self.repeated_pairs) / 3.0
+	}
+}
+
+/// Measure how much `messages` stand out from English.
+pub fn analyze(messages: &[String]) -> Detectability {
+	let words: Vec<String> = messages
+		.iter()
+		.flat_map(|v| v.split_whitespace())
+		.map(|v| v.trim_matches(natural::DEFAULT_PUNCTUATION).to_lowercase())
+		.filter(|v| !v.is_empty())
+		.collect();
+	if words.is_empty() {
+		return Detectability {
+			words: 0,
+			common_words: 0.0,
+			length_divergence: 0.0,
+			length_entropy: 0.0,
+			repeated_pairs: 0.0,
+		};
+	}
+	let total = words.len() as f64;
+	let common = words
+		.iter()
+		.filter(|v| COMMON.contains(&v.as_str()))
+		.count();
+	let mut lengths = [0usize; LENGTHS.len()];
+	for word in &words {
+		lengths[(word.chars().count() - 1).min(LENGTHS.len() - 1)] += 1;
+	}
+	let shares = lengths.iter().map(|v| *v as f64 / total);
+	let length_divergence = shares
+		.clone()
+		.zip(LENGTHS)
+		.filter(|(share, _)| *share > 0.0)
+		.map(|(share, english)| share * (share / english).log2())
+		.sum();
+	let length_entropy = -shares
+		.filter(|v| *v > 0.0)
+		.map(|v| v * v.log2())
+		.sum::<f64>();
+	let mut seen = HashSet::new();
+	let pairs = words.len().saturating_sub(1);
+	let repeated = words
+		.windows(2)
+		.filter(|v| !seen.insert((v[0].as_str(), v[1].as_str())))
+		.count();
+	Detectability {
+		words: words.len(),
+		common_words: common as f64 / total,
+		length_divergence,
+		length_entropy,
+		repeated_pairs: match pairs {
+			0 => 0.0,
+			pairs => repeated as f64 / pairs as f64,
+		},
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::analyze;
+	use crate::{don, today, DictMappings};
+
+	#[test]
+	fn analysis() {
+		let english = [
+			"When we got back to the house, the rain had started again, so we \
+			made some tea and sat by the window for a while. Nobody said much. It had been a long \
+			day, and I think we were all glad that it was over, even if there was still work to \
+			do in the morning before the others could come."
+				.to_string(),
+		];
+		let english = analyze(&english);
+		assert!(english.common_words > 0.3);
+		assert!(english.length_divergence < 0.5);
+		let dict = DictMappings::from_seed(69, &today());
+		let armor = analyze(&don(&[7; 200], &dict, 100));
+		assert!(armor.common_words < 0.1);
+		assert!(armor.length_divergence > english.length_divergence);
+		assert!(armor.repeated_pairs > 0.5);
+		assert!(armor.score() > english.score());
+		let random: Vec<u8> = (0..=255).collect();
+		let armor = analyze(&don(&random, &dict, 100));
+		assert!(armor.repeated_pairs < 0.1);
+		assert!(armor.score() > 0.5);
+		assert_eq!(analyze(&[]).words, 0);
+	}
+}
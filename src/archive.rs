@@ -0,0 +1,223 @@
+//! Packing several files into one payload, so a whole directory can be armored at once.
+//!
+//! An archive is the number of files as a 32-bit big-endian number, then each file in turn:
+//! the length of its path in bytes as a 16-bit big-endian number, the path in UTF-8 with `/`
+//! between directories, the length of its contents as a 64-bit big-endian number, and the
+//! contents.
+
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::CawError;
+
+/// Builds an archive of files to armor as one payload.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveBuilder {
+	count: u32,
+	data: Vec<u8>,
+}
+
+impl ArchiveBuilder {
+	/// Start an empty archive.
+	pub fn new() -> ArchiveBuilder {
+		ArchiveBuilder::default()
+	}
+	/// Add a file with `contents` at `path`, which uses `/` between directories.
+	///
+	/// Panics if the path is longer than 65,535 bytes, or the archive already has
+	/// 4,294,967,295 files.
+	pub fn file(mut self, path: &str, contents: &[u8]) -> Self {
+		let path_len = u16::try_from(path.len()).expect("the path is too long");
+		self.count = self.count.checked_add(1).expect("too many files");
+		self.data.extend_from_slice(&path_len.to_be_bytes());
+		self.data.extend_from_slice(path.as_bytes());
+		self.data
+			.extend_from_slice(&(contents.len() as u64).to_be_bytes());
+		self.data.extend_from_slice(contents);
+		self
+	}
+	/// Add every file under the directory `dir`, at paths relative to it. Files are added in
+	/// order of their paths, so the same directory always makes the same archive.
+	pub fn directory(mut self, dir: impl AsRef<Path>) -> io::Result<Self> {
+		let dir = dir.as_ref();
+		let mut pending = vec![dir.to_path_buf()];
+		let mut files = vec![];
+		while let Some(next) = pending.pop() {
+			for entry in fs::read_dir(next)? {
+				let path = entry?.path();
+				if path.is_dir() {
+					pending.push(path);
+				} else {
+					files.push(path);
+				}
+			}
+		}
+		let mut files: Vec<(String, PathBuf)> = files
+			.into_iter()
+			.map(|path| {
+				let relative: Vec<String> = path
+					.strip_prefix(dir)
+					.unwrap()
+					.components()
+					.map(|v| v.as_os_str().to_string_lossy().into_owned())
+					.collect();
+				(relative.join("/"), path)
+			})
+			.collect();
+		files.sort();
+		for (name, path) in files {
+			self = self.file(&name, &fs::read(path)?);
+		}
+		Ok(self)
+	}
+	/// The archive, ready to be armored.
+	pub fn build(self) -> Vec<u8> {
+		[&self.count.to_be_bytes()[..], &self.data].concat()
+	}
+}
+
+/// A file in an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+	/// Where the file goes, with `/` between directories.
+	pub path: &'a str,
+	/// What's in the file.
+	pub contents: &'a [u8],
+}
+
+/// Reads the files out of an archive made by `ArchiveBuilder`.
+#[derive(Debug, Clone)]
+pub struct ArchiveReader<'a> {
+	entries: Vec<Entry<'a>>,
+}
+
+impl<'a> ArchiveReader<'a> {
+	/// Read an archive, checking that it's whole.
+	pub fn new(data: &'a [u8]) -> Result<ArchiveReader<'a>, CawError> {
+		let mut rest = data;
+		let mut take = |len: usize| -> Result<&'a [u8], CawError> {
+			if rest.len() < len {
+				return Err(CawError::BadArchive);
+			}
+			let (taken, after) = rest.split_at(len);
+			rest = after;
+			Ok(taken)
+		};
+		let count = u32::from_be_bytes(take(4)?.try_into().unwrap());
+		let mut entries = vec![];
+		for _ in 0..count {
+			let path_len = u16::from_be_bytes(take(2)?.try_into().unwrap());
+			let path =
+				std::str::from_utf8(take(path_len as usize)?).map_err(|_| CawError::BadArchive)?;
+			let len = u64::from_be_bytes(take(8)?.try_into().unwrap());
+			let contents = take(usize::try_from(len).map_err(|_| CawError::BadArchive)?)?;
+			entries.push(Entry { path, contents });
+		}
+		if !rest.is_empty() {
+			return Err(CawError::BadArchive);
+		}
+		Ok(ArchiveReader { entries })
+	}
+	/// The files in the archive, in the order they were added.
+	pub fn entries(&self) -> &[Entry<'a>] {
+		&self.entries
+	}
+	/// Write every file into the directory `dir`, creating directories as needed. Paths which
+	/// would leave `dir`, like absolute paths or ones with `..`, are refused before anything
+	/// is written. Files which are already there are overwritten.
+	pub fn extract(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+		let dir = dir.as_ref();
+		let paths = self
+			.entries
+			.iter()
+			.map(|entry| {
+				let relative = Path::new(entry.path);
+				let inside = !entry.path.is_empty()
+					&& relative
+						.components()
+						.all(|v| matches!(v, Component::Normal(_) | Component::CurDir));
+				if inside {
+					Ok(dir.join(relative))
+				} else {
+					Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("{:?} would be written outside of the directory", entry.path),
+					))
+				}
+			})
+			.collect::<io::Result<Vec<PathBuf>>>()?;
+		for (entry, path) in self.entries.iter().zip(paths) {
+			if let Some(parent) = path.parent() {
+				fs::create_dir_all(parent)?;
+			}
+			fs::write(path, entry.contents)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use std::fs;
+
+	use super::{ArchiveBuilder, ArchiveReader, Entry};
+	use crate::{doff, don, today, DictMappings};
+
+	#[test]
+	fn archive() {
+		let root = std::env::temp_dir().join(format!("caw-archive-{}", std::process::id()));
+		let source = root.join("source");
+		fs::create_dir_all(source.join("notes/old")).unwrap();
+		fs::write(source.join("readme.txt"), "Start here").unwrap();
+		fs::write(source.join("notes/old/first.txt"), "A long time ago").unwrap();
+		fs::write(source.join("notes/empty"), "").unwrap();
+		let archive = ArchiveBuilder::new()
+			.directory(&source)
+			.unwrap()
+			.file("extra/data.bin", &[0, 1, 2, 255])
+			.build();
+		let dict = DictMappings::from_seed(69, &today());
+		let data = doff(&don(&archive, &dict, 100), &dict).unwrap();
+		let reader = ArchiveReader::new(&data).unwrap();
+		let paths: Vec<&str> = reader.entries().iter().map(|v| v.path).collect();
+		assert_eq!(
+			paths,
+			[
+				"notes/empty",
+				"notes/old/first.txt",
+				"readme.txt",
+				"extra/data.bin"
+			]
+		);
+		let target = root.join("target");
+		reader.extract(&target).unwrap();
+		assert_eq!(
+			fs::read(target.join("notes/old/first.txt")).unwrap(),
+			b"A long time ago"
+		);
+		assert_eq!(
+			fs::read(target.join("extra/data.bin")).unwrap(),
+			[0, 1, 2, 255]
+		);
+		// Nothing is written if any path would escape
+		let escaping = ArchiveBuilder::new()
+			.file("fine.txt", b"")
+			.file("../escaped.txt", b"")
+			.build();
+		let reader = ArchiveReader::new(&escaping).unwrap();
+		assert_eq!(
+			reader.entries()[1],
+			Entry {
+				path: "../escaped.txt",
+				contents: b""
+			}
+		);
+		assert!(reader.extract(root.join("escaping")).is_err());
+		assert!(!root.join("escaping").exists());
+		assert!(ArchiveReader::new(&archive[..archive.len() - 1]).is_err());
+		assert!(ArchiveReader::new(&[archive.clone(), vec![0]].concat()).is_err());
+		fs::remove_dir_all(root).unwrap();
+	}
+}
@@ -0,0 +1,326 @@
+//! A builder which gathers options for donning and doffing in one place.
+
+use chrono::{NaiveDate, NaiveTime};
+
+use crate::{
+	don_inner, BigramModel, CawError, ContentType, DecodePolicy, DictMappings, Dictionary,
+	LimitUnit, Options, PartOfSpeech, Platform, Report, Separator, SplitStrategy, Tokenizer,
+	Tolerances,
+};
+
+/// Dons and doffs armor with one set of options, so new options don't have to be threaded
+/// through every call.
+#[derive(Debug, Clone)]
+pub struct Armorer<'a, D: Dictionary> {
+	dict: &'a DictMappings<D>,
+	character_limit: usize,
+	options: Options,
+	model: Option<&'a BigramModel>,
+	/// What doffing puts up with.
+	tolerances: Tolerances,
+	/// The age in days beyond which armor is refused while doffing.
+	max_age: Option<u32>,
+	/// The punctuation which is stripped from words while doffing.
+	punctuation: Vec<char>,
+	/// The pool which the work is done on, instead of rayon's global one.
+	#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+	pool: Option<&'a rayon::ThreadPool>,
+}
+
+impl<'a, D: Dictionary> Armorer<'a, D> {
+	/// Start building an armorer which uses `dict`. Without a character limit, all of the
+	/// armor goes in one message.
+	pub fn builder(dict: &'a DictMappings<D>) -> ArmorerBuilder<'a, D> {
+		ArmorerBuilder::new(dict)
+	}
+	/// The options the armor is donned with.
+	pub fn options(&self) -> &Options {
+		&self.options
+	}
+	/// Run `f` on the armorer's thread pool, if it has one.
+	fn run<T: Send>(&self, f: impl FnOnce() -> T + Send) -> T {
+		#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+		if let Some(pool) = self.pool {
+			return pool.install(f);
+		}
+		f()
+	}
+	/// Don armor.
+	pub fn armor(&self, data: &[u8]) -> Vec<String> {
+		self.run(|| self.armor_inner(data, &mut rand::thread_rng()))
+	}
+	/// Don armor, drawing every random choice from `rng`, like `don_with_rng`.
+	pub fn armor_with_rng(&self, data: &[u8], rng: &mut (impl rand::Rng + Send)) -> Vec<String> {
+		self.run(|| self.armor_inner(data, rng))
+	}
+	fn armor_inner(&self, data: &[u8], rng: &mut impl rand::Rng) -> Vec<String> {
+		don_inner(
+			data,
+			self.dict,
+			self.character_limit,
+			&self.options,
+			self.model,
+			rng,
+		)
+	}
+	/// Doff armor, putting up with what the armorer's decode policy allows, which is the same
+	/// as `doff` unless it's been changed.
+	pub fn unarmor(&self, messages: &[String]) -> Result<Vec<u8>, CawError> {
+		self.unarmor_with_report(messages).map(|(data, _)| data)
+	}
+	/// Doff armor, returning a report of everything which had to be worked around along
+	/// with the recovered bytes.
+	pub fn unarmor_with_report(&self, messages: &[String]) -> Result<(Vec<u8>, Report), CawError> {
+		let tokenizer = Tokenizer {
+			separator: self.options.separator.clone(),
+			punctuation: self.punctuation.clone(),
+		};
+		let (data, report) =
+			self.run(|| crate::doff_inner(messages, self.dict, &self.tolerances, &tokenizer))?;
+		if let Some(max_age) = self.max_age {
+			crate::check_age(&report, max_age)?;
+		}
+		Ok((data, report))
+	}
+}
+
+/// Builds an `Armorer`. Every option starts out the same as in `Options::default`.
+#[derive(Debug, Clone)]
+pub struct ArmorerBuilder<'a, D: Dictionary> {
+	armorer: Armorer<'a, D>,
+}
+
+impl<'a, D: Dictionary> ArmorerBuilder<'a, D> {
+	/// Start building an armorer which uses `dict`.
+	pub fn new(dict: &'a DictMappings<D>) -> ArmorerBuilder<'a, D> {
+		ArmorerBuilder {
+			armorer: Armorer {
+				dict,
+				character_limit: usize::MAX,
+				options: Options::default(),
+				model: None,
+				tolerances: Tolerances::default(),
+				max_age: None,
+				punctuation: Tokenizer::default().punctuation,
+				#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+				pool: None,
+			},
+		}
+	}
+	/// The soft limit on the length of each message.
+	pub fn character_limit(mut self, character_limit: usize) -> Self {
+		self.armorer.character_limit = character_limit;
+		self
+	}
+	/// What the character limit counts.
+	pub fn limit_unit(mut self, limit_unit: LimitUnit) -> Self {
+		self.armorer.options.limit_unit = limit_unit;
+		self
+	}
+	/// Fit the messages to `platform`, setting the character limit and what it counts.
+	pub fn platform(self, platform: Platform) -> Self {
+		self.character_limit(platform.character_limit())
+			.limit_unit(platform.limit_unit())
+	}
+	/// Replace every option at once.
+	pub fn options(mut self, options: Options) -> Self {
+		self.armorer.options = options;
+		self
+	}
+	/// See `Options::checksum`.
+	pub fn checksum(mut self, checksum: bool) -> Self {
+		self.armorer.options.checksum = checksum;
+		self
+	}
+	/// See `Options::error_correction`.
+	pub fn error_correction(mut self, parity: u16) -> Self {
+		self.armorer.options.error_correction = parity;
+		self
+	}
+	/// See `Options::compress`.
+	pub fn compression(mut self, compress: bool) -> Self {
+		self.armorer.options.compress = compress;
+		self
+	}
+	/// See `Options::encrypt`.
+	pub fn encryption(mut self, encrypt: bool) -> Self {
+		self.armorer.options.encrypt = encrypt;
+		self
+	}
+	/// See `Options::authenticate`.
+	pub fn authentication(mut self, authenticate: bool) -> Self {
+		self.armorer.options.authenticate = authenticate;
+		self
+	}
+	/// See `Options::natural`.
+	pub fn natural(mut self, natural: bool) -> Self {
+		self.armorer.options.natural = natural;
+		self
+	}
+	/// See `Options::homophones`.
+	pub fn homophones(mut self, homophones: bool) -> Self {
+		self.armorer.options.homophones = homophones;
+		self
+	}
+	/// See `Options::rhythm`.
+	pub fn rhythm(mut self, rhythm: Vec<PartOfSpeech>) -> Self {
+		self.armorer.options.rhythm = rhythm;
+		self
+	}
+	/// See `Options::content_type`.
+	pub fn content_type(mut self, content_type: ContentType) -> Self {
+		self.armorer.options.content_type = Some(content_type);
+		self
+	}
+	/// See `Options::fillers`.
+	pub fn fillers(mut self, fillers: Vec<String>) -> Self {
+		self.armorer.options.fillers = fillers;
+		self
+	}
+	/// See `Options::message_id`.
+	pub fn message_id(mut self, message_id: u16) -> Self {
+		self.armorer.options.message_id = Some(message_id);
+		self
+	}
+	/// See `Options::session_id`.
+	pub fn session_id(mut self, session_id: u32) -> Self {
+		self.armorer.options.session_id = Some(session_id);
+		self
+	}
+	/// See `Options::date`.
+	pub fn date(mut self, date: NaiveDate) -> Self {
+		self.armorer.options.date = Some(date);
+		self
+	}
+	/// See `Options::time`.
+	pub fn time(mut self, time: NaiveTime) -> Self {
+		self.armorer.options.time = Some(time);
+		self
+	}
+	/// See `Options::split`.
+	pub fn split(mut self, split: SplitStrategy) -> Self {
+		self.armorer.options.split = split;
+		self
+	}
+	/// See `Options::padding`.
+	pub fn padding(mut self, block: usize) -> Self {
+		self.armorer.options.padding = Some(block);
+		self
+	}
+	/// See `Options::chaff`.
+	pub fn chaff(mut self, percent: u8) -> Self {
+		self.armorer.options.chaff = percent;
+		self
+	}
+	/// See `Options::nonce`.
+	pub fn nonce(mut self, nonce: bool) -> Self {
+		self.armorer.options.nonce = nonce;
+		self
+	}
+	/// See `Options::keystream`.
+	pub fn keystream(mut self, keystream: bool) -> Self {
+		self.armorer.options.keystream = keystream;
+		self
+	}
+	/// See `Options::separator`. The armorer doffs with the same separator.
+	pub fn separator(mut self, separator: Separator) -> Self {
+		self.armorer.options.separator = separator;
+		self
+	}
+	/// Choose between homophones with `model`, like `don_with_model`.
+	pub fn model(mut self, model: &'a BigramModel) -> Self {
+		self.armorer.model = Some(model);
+		self
+	}
+	/// Correct mistyped words while doffing, like `doff_lenient`.
+	pub fn lenient(mut self, lenient: bool) -> Self {
+		self.armorer.tolerances.typos = lenient;
+		self
+	}
+	/// Put up with what `policy` allows while doffing, like `doff_with_policy`. This replaces
+	/// whatever `lenient` chose.
+	pub fn policy(mut self, policy: DecodePolicy) -> Self {
+		self.armorer.tolerances = policy.tolerances();
+		self
+	}
+	/// Refuse armor which was donned more than `days` days ago, or doesn't say when it was
+	/// donned, like `doff_fresh`.
+	pub fn max_age(mut self, days: u32) -> Self {
+		self.armorer.max_age = Some(days);
+		self
+	}
+	/// See `Tokenizer::punctuation`.
+	pub fn punctuation(mut self, punctuation: Vec<char>) -> Self {
+		self.armorer.punctuation = punctuation;
+		self
+	}
+	/// Do the work on `pool` instead of rayon's global pool, so it can be kept apart from the
+	/// rest of an application's work or limited to a few threads.
+	#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+	pub fn thread_pool(mut self, pool: &'a rayon::ThreadPool) -> Self {
+		self.armorer.pool = Some(pool);
+		self
+	}
+	/// Finish building the armorer.
+	pub fn build(self) -> Armorer<'a, D> {
+		self.armorer
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::Armorer;
+	use crate::{today, DictMappings, Options, Platform};
+
+	#[test]
+	fn matches_options() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Options which compose without breaking anything. ".repeat(5);
+		let test_data = test_data.as_bytes();
+		let armorer = Armorer::builder(&dict)
+			.platform(Platform::Sms)
+			.compression(true)
+			.checksum(true)
+			.message_id(7)
+			.build();
+		assert_eq!(
+			armorer.options(),
+			&Options {
+				compress: true,
+				checksum: true,
+				message_id: Some(7),
+				..Platform::Sms.options()
+			}
+		);
+		let messages = armorer.armor(test_data);
+		assert!(messages
+			.iter()
+			.all(|v| v.chars().count() <= Platform::Sms.character_limit()));
+		assert_eq!(armorer.unarmor(&messages).unwrap(), test_data);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+		// Without a limit, everything fits in one message
+		let armorer = Armorer::builder(&dict).build();
+		assert_eq!(armorer.armor(test_data).len(), 1);
+	}
+	#[test]
+	#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+	fn thread_pool() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Armored on a pool of its own. ".repeat(20);
+		let test_data = test_data.as_bytes();
+		let pool = rayon::ThreadPoolBuilder::new()
+			.num_threads(2)
+			.build()
+			.unwrap();
+		let armorer = Armorer::builder(&dict)
+			.character_limit(80)
+			.thread_pool(&pool)
+			.build();
+		let messages = armorer.armor(test_data);
+		assert!(messages.len() > 1);
+		assert_eq!(armorer.unarmor(&messages).unwrap(), test_data);
+		let mut rng = rand_pcg::Pcg64::new(1, 2);
+		let seeded = armorer.armor_with_rng(test_data, &mut rng);
+		assert_eq!(armorer.unarmor(&seeded).unwrap(), test_data);
+	}
+}
@@ -0,0 +1,211 @@
+//! Async versions of `don` and `doff` for tokio, built with the `async` feature.
+//!
+//! Mapping a whole payload takes long enough to stall a reactor, so `don_async` and
+//! `doff_async` do it on tokio's blocking pool. `don_stream` dons one message at a time as
+//! they're asked for, and `DoffSink` doffs one message at a time as they arrive, which are
+//! quick enough to do in place.
+
+use std::io::{ErrorKind, Read};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+
+use crate::{CawError, DictMappings, Dictionary, DoffReader, Options};
+
+/// Run `f` on the blocking pool, passing on its panics.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+	match tokio::task::spawn_blocking(f).await {
+		Ok(result) => result,
+		Err(e) => match e.try_into_panic() {
+			Ok(panic) => std::panic::resume_unwind(panic),
+			Err(e) => panic!("the blocking pool shut down: {}", e),
+		},
+	}
+}
+
+/// Don armor on the blocking pool, like `don_with`.
+pub async fn don_async<D: Dictionary + Send + 'static>(
+	data: Vec<u8>,
+	dict: Arc<DictMappings<D>>,
+	character_limit: usize,
+	options: Options,
+) -> Vec<String> {
+	blocking(move || crate::don_with(&data, &dict, character_limit, &options)).await
+}
+
+/// Doff armor on the blocking pool, like `doff`.
+pub async fn doff_async<D: Dictionary + Send + 'static>(
+	messages: Vec<String>,
+	dict: Arc<DictMappings<D>>,
+) -> Result<Vec<u8>, CawError> {
+	blocking(move || crate::doff(&messages, &dict)).await
+}
+
+/// Don armor lazily as a `Stream`, like `don_iter`, so each message is only armored once the
+/// one before it has been taken. Forwarding it to a rate-limited sink armors no faster than
+/// the sink accepts messages.
+pub fn don_stream<'a, D: Dictionary>(
+	data: &'a [u8],
+	dict: &'a DictMappings<D>,
+	character_limit: usize,
+) -> impl Stream<Item = String> + 'a {
+	IterStream(crate::don_iter(data, dict, character_limit))
+}
+
+/// A stream of whatever an iterator yields, which is always ready.
+struct IterStream<I>(I);
+
+impl<I: Iterator + Unpin> Stream for IterStream<I> {
+	type Item = I::Item;
+	fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<I::Item>> {
+		Poll::Ready(self.get_mut().0.next())
+	}
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.0.size_hint()
+	}
+}
+
+/// Doffs armor as messages arrive. Messages are sent into it as a `Sink`, in any order, and
+/// the recovered bytes come out of it as a `Stream` as soon as every message before them
+/// has arrived, like a `DoffReader`.
+///
+/// The stream ends once the message carrying the end marker has been read, or once the sink
+/// has been closed and there is nothing left to read.
+pub struct DoffSink<'a, D: Dictionary> {
+	reader: DoffReader<'a, D>,
+	/// Whether the sink has been closed, so no more messages are coming.
+	closed: bool,
+	/// The task waiting for bytes, which is woken when a message arrives.
+	waker: Option<Waker>,
+}
+
+impl<'a, D: Dictionary> DoffSink<'a, D> {
+	/// Create a sink with no messages.
+	pub fn new(dict: &'a DictMappings<D>) -> DoffSink<'a, D> {
+		DoffSink {
+			reader: DoffReader::new(dict),
+			closed: false,
+			waker: None,
+		}
+	}
+	/// Whether every message has arrived.
+	pub fn is_finished(&self) -> bool {
+		self.reader.is_finished()
+	}
+	fn wake(&mut self) {
+		if let Some(waker) = self.waker.take() {
+			waker.wake();
+		}
+	}
+}
+
+impl<'a, D: Dictionary> Sink<String> for DoffSink<'a, D> {
+	type Error = CawError;
+	fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), CawError>> {
+		Poll::Ready(Ok(()))
+	}
+	fn start_send(self: Pin<&mut Self>, message: String) -> Result<(), CawError> {
+		let this = self.get_mut();
+		this.reader.push(&message)?;
+		this.wake();
+		Ok(())
+	}
+	fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), CawError>> {
+		Poll::Ready(Ok(()))
+	}
+	fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), CawError>> {
+		let this = self.get_mut();
+		this.closed = true;
+		this.wake();
+		Poll::Ready(Ok(()))
+	}
+}
+
+impl<'a, D: Dictionary> Stream for DoffSink<'a, D> {
+	type Item = Vec<u8>;
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Vec<u8>>> {
+		let this = self.get_mut();
+		let mut data = vec![];
+		// Whatever was ready is kept even when the reader runs out
+		match this.reader.read_to_end(&mut data) {
+			Err(e) if e.kind() != ErrorKind::WouldBlock => unreachable!("reading only blocks"),
+			Err(_) if data.is_empty() && !this.closed => {
+				this.waker = Some(cx.waker().clone());
+				Poll::Pending
+			}
+			_ if data.is_empty() => Poll::Ready(None),
+			_ => Poll::Ready(Some(data)),
+		}
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use std::sync::Arc;
+
+	use futures::{SinkExt, StreamExt};
+
+	use super::DoffSink;
+	use crate::{today, DictMappings, Options};
+
+	#[tokio::test]
+	async fn round_trip() {
+		let dict = Arc::new(DictMappings::from_seed(69, &today()));
+		let test_data = "Armored without blocking the reactor".as_bytes();
+		let messages =
+			super::don_async(test_data.to_vec(), dict.clone(), 40, Options::default()).await;
+		assert!(messages.len() > 1);
+		let doffed = super::doff_async(messages, dict).await;
+		assert_eq!(doffed.unwrap(), test_data);
+	}
+	#[tokio::test]
+	async fn stream() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Posted one message at a time, as slowly as the channel allows. ".repeat(4);
+		let test_data = test_data.as_bytes();
+		let first: Vec<String> = super::don_stream(test_data, &dict, 60)
+			.take(1)
+			.collect()
+			.await;
+		assert_eq!(first.len(), 1);
+		let messages: Vec<String> = super::don_stream(test_data, &dict, 60).collect().await;
+		assert!(messages.len() > 2);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+		// Messages can go straight into a sink
+		let mut sink = DoffSink::new(&dict);
+		let mut stream = super::don_stream(test_data, &dict, 60).map(Ok);
+		sink.send_all(&mut stream).await.unwrap();
+		let doffed: Vec<u8> = sink.concat().await;
+		assert_eq!(doffed, test_data);
+		// The stream can be moved into a task of its own
+		let dict: &'static DictMappings =
+			Box::leak(Box::new(DictMappings::from_seed(69, &today())));
+		let test_data: &'static [u8] = b"Posted from a task of its own";
+		let messages =
+			tokio::spawn(super::don_stream(test_data, dict, 60).collect::<Vec<String>>())
+				.await
+				.unwrap();
+		assert_eq!(crate::doff(&messages, dict).unwrap(), test_data);
+	}
+	#[tokio::test]
+	async fn sink() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Bytes come out of the stream as soon as they can be read. ".repeat(8);
+		let test_data = test_data.as_bytes();
+		let messages = crate::don(test_data, &dict, 200);
+		assert!(messages.len() > 2);
+		let mut sink = DoffSink::new(&dict);
+		sink.send(messages[0].clone()).await.unwrap();
+		let first = sink.next().await.unwrap();
+		assert!(test_data.starts_with(&first));
+		for message in messages[1..].iter().rev() {
+			sink.send(message.clone()).await.unwrap();
+		}
+		assert!(sink.is_finished());
+		let rest: Vec<u8> = sink.concat().await;
+		assert_eq!([first, rest].concat(), test_data);
+	}
+}
@@ -0,0 +1,140 @@
+//! A cache of mappings, for programs which armor and doff over and over.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use chrono::{Duration, NaiveDate};
+
+use crate::{today, DictMappings, Language};
+
+/// Remembers the mappings for recent seeds and dates, so they're only built once. Building
+/// mappings shuffles the whole dictionary, which takes far longer than armoring a message.
+///
+/// The cache can be shared between threads. It holds at most `capacity` mappings, forgetting
+/// the least recently used ones first, and forgets mappings for dates more than `max_age`
+/// days before today whenever new mappings are built.
+#[derive(Debug)]
+pub struct DictCache {
+	language: Language,
+	capacity: usize,
+	max_age: u32,
+	/// The mappings, with the most recently used last.
+	entries: Mutex<VecDeque<(u128, NaiveDate, Arc<DictMappings>)>>,
+}
+
+impl DictCache {
+	/// Create a cache of English mappings which holds at most `capacity` of them, and forgets
+	/// them once they're more than a day old.
+	pub fn new(capacity: usize) -> DictCache {
+		DictCache::in_language(Language::English, capacity)
+	}
+	/// Create a cache of mappings for one of the built-in languages.
+	pub fn in_language(language: Language, capacity: usize) -> DictCache {
+		DictCache {
+			language,
+			capacity,
+			max_age: 1,
+			entries: Mutex::new(VecDeque::new()),
+		}
+	}
+	/// Keep mappings until they're more than `days` days old, such as for doffing armor which
+	/// took a while to arrive.
+	pub fn with_max_age(mut self, days: u32) -> DictCache {
+		self.max_age = days;
+		self
+	}
+	/// The mappings for `seed` on `date`, building them if they aren't in the cache.
+	pub fn get(&self, seed: u128, date: NaiveDate) -> Arc<DictMappings> {
+		if let Some(mappings) = self.lookup(seed, date) {
+			return mappings;
+		}
+		// The lock isn't held while the mappings are built, so other seeds aren't held up
+		let mappings = Arc::new(DictMappings::from_seed_in(self.language, seed, &date));
+		let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+		// Another thread may have built the same mappings meanwhile, and theirs are kept
+		if let Some(entry) = entries.iter().find(|v| v.0 == seed && v.1 == date) {
+			return entry.2.clone();
+		}
+		let oldest = today() - Duration::days(self.max_age as i64);
+		entries.retain(|v| v.1 >= oldest);
+		entries.push_back((seed, date, mappings.clone()));
+		while entries.len() > self.capacity {
+			entries.pop_front();
+		}
+		mappings
+	}
+	/// The mappings for `seed` on `date` if they're in the cache, marking them as the most
+	/// recently used.
+	fn lookup(&self, seed: u128, date: NaiveDate) -> Option<Arc<DictMappings>> {
+		// Nothing panics while the lock is held, but a poisoned cache is still usable
+		let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+		let position = entries.iter().position(|v| v.0 == seed && v.1 == date)?;
+		let entry = entries.remove(position).unwrap();
+		let mappings = entry.2.clone();
+		entries.push_back(entry);
+		Some(mappings)
+	}
+	/// Today's mappings for `seed`.
+	pub fn today(&self, seed: u128) -> Arc<DictMappings> {
+		self.get(seed, today())
+	}
+	/// The number of mappings in the cache.
+	pub fn len(&self) -> usize {
+		self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+	}
+	/// Whether the cache is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+	/// Forget every mapping.
+	pub fn clear(&self) {
+		self.entries
+			.lock()
+			.unwrap_or_else(|e| e.into_inner())
+			.clear();
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use std::sync::Arc;
+
+	use chrono::Duration;
+
+	use super::DictCache;
+	use crate::{today, DictMappings};
+
+	#[test]
+	fn cache() {
+		let cache = DictCache::new(2);
+		let first = cache.today(69);
+		assert!(Arc::ptr_eq(&first, &cache.get(69, today())));
+		assert_eq!(first.words, DictMappings::from_seed(69, &today()).words);
+		cache.today(70);
+		// 69 was used more recently than 70, so 70 is the one forgotten
+		cache.today(69);
+		cache.today(71);
+		assert_eq!(cache.len(), 2);
+		assert!(Arc::ptr_eq(&first, &cache.today(69)));
+		// Old dates are forgotten as soon as anything is built
+		let old = today() - Duration::days(5);
+		let cache = DictCache::new(4);
+		cache.get(69, old);
+		cache.today(69);
+		assert_eq!(cache.len(), 1);
+		let kept = DictCache::new(4).with_max_age(7);
+		kept.get(69, old);
+		kept.today(69);
+		assert_eq!(kept.len(), 2);
+		kept.clear();
+		assert!(kept.is_empty());
+		// Threads which build the same mappings at once all end up with the same ones
+		let cache = DictCache::new(4);
+		let built: Vec<Arc<DictMappings>> = std::thread::scope(|scope| {
+			let threads: Vec<_> = (0..4).map(|_| scope.spawn(|| cache.today(69))).collect();
+			threads.into_iter().map(|v| v.join().unwrap()).collect()
+		});
+		assert!(built.iter().all(|v| Arc::ptr_eq(v, &cache.today(69))));
+		assert_eq!(cache.len(), 1);
+	}
+}
@@ -0,0 +1,253 @@
+//! Payloads carried in values smaller than 16 bits, for dictionaries too small to have a word
+//! for every 16-bit value.
+//!
+//! The payload is read as a string of bits, most significant first, and cut into chunks of
+//! the mappings' size. Header values are still 16 bits, and are written as one chunk or two.
+
+use std::convert::TryFrom;
+
+use crate::header::TAG_COUNT;
+
+/// How many bits each word of armor carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkSize {
+	/// 256 values, so dictionaries of 335 words or more can be used, at twice the length of
+	/// 16-bit armor.
+	Eight,
+	/// 4,096 values, so dictionaries of 4,175 words or more can be used, at a third more than
+	/// the length of 16-bit armor.
+	Twelve,
+	/// 65,536 values, which needs a dictionary of 65,615 words or more.
+	#[default]
+	Sixteen,
+}
+
+impl ChunkSize {
+	/// The number of bits in a chunk.
+	pub const fn bits(self) -> u32 {
+		match self {
+			ChunkSize::Eight => 8,
+			ChunkSize::Twelve => 12,
+			ChunkSize::Sixteen => 16,
+		}
+	}
+	/// The number of different chunks.
+	pub const fn values(self) -> usize {
+		1 << self.bits()
+	}
+	/// The fewest words a dictionary can have to be used with this chunk size: fifteen
+	/// markers, a word for every chunk and the header tags.
+	pub const fn min_len(self) -> usize {
+		15 + self.values() + TAG_COUNT
+	}
+	/// The largest chunk size which a dictionary of `len` words can be used with.
+	pub fn fitting(len: usize) -> Option<ChunkSize> {
+		[ChunkSize::Sixteen, ChunkSize::Twelve, ChunkSize::Eight]
+			.iter()
+			.copied()
+			.find(|v| len >= v.min_len())
+	}
+	/// The chunk size with a word for each of `values` chunks.
+	pub(crate) fn from_values(values: usize) -> Option<ChunkSize> {
+		[ChunkSize::Sixteen, ChunkSize::Twelve, ChunkSize::Eight]
+			.iter()
+			.copied()
+			.find(|v| v.values() == values)
+	}
+	/// How many chunks a 16-bit header value is written as.
+	pub(crate) fn chunks_per_value(self) -> usize {
+		16usize.div_ceil(self.bits() as usize)
+	}
+	/// The smallest number of bytes which is a whole number of chunks. Padded payloads are
+	/// padded to a multiple of it.
+	pub(crate) fn group(self) -> usize {
+		match self {
+			ChunkSize::Eight => 1,
+			ChunkSize::Twelve => 3,
+			ChunkSize::Sixteen => 2,
+		}
+	}
+	/// The chunks of a 16-bit value, most significant first.
+	pub(crate) fn split_value(self, value: u16) -> impl Iterator<Item = u16> {
+		let bits = self.bits();
+		(0..self.chunks_per_value() as u32)
+			.rev()
+			.map(move |v| ((value as u32 >> (v * bits)) & ((1 << bits) - 1)) as u16)
+	}
+	/// The 16-bit value made of `chunks`, if it fits in 16 bits.
+	pub(crate) fn join_value(self, chunks: &[u16]) -> Option<u16> {
+		let value = chunks
+			.iter()
+			.fold(0u32, |value, v| (value << self.bits()) | *v as u32);
+		u16::try_from(value).ok()
+	}
+	/// The number of chunks it takes to carry `bytes` bytes.
+	pub(crate) fn chunks_for(self, bytes: usize) -> usize {
+		(bytes * 8).div_ceil(self.bits() as usize)
+	}
+}
+
+/// Cuts bytes into chunks as they arrive.
+#[derive(Debug, Clone)]
+pub(crate) struct Chunker {
+	size: ChunkSize,
+	/// The bits which haven't made up a whole chunk yet.
+	bits: u32,
+	/// How many bits are in `bits`.
+	count: u32,
+}
+
+impl Chunker {
+	pub(crate) fn new(size: ChunkSize) -> Chunker {
+		Chunker {
+			size,
+			bits: 0,
+			count: 0,
+		}
+	}
+	/// Add a byte, returning the chunk it completed. Chunks are at least a byte long, so no
+	/// byte completes more than one.
+	pub(crate) fn push(&mut self, byte: u8) -> Option<u16> {
+		self.bits = (self.bits << 8) | byte as u32;
+		self.count += 8;
+		let size = self.size.bits();
+		if self.count < size {
+			return None;
+		}
+		self.count -= size;
+		let chunk = (self.bits >> self.count) as u16;
+		self.bits &= (1 << self.count) - 1;
+		Some(chunk)
+	}
+	/// The bits which haven't made up a whole chunk yet, and how many there are.
+	pub(crate) fn leftover(&self) -> (u32, u32) {
+		(self.bits, self.count)
+	}
+	/// Carry on from bits left over by another chunker of the same size.
+	pub(crate) fn with_leftover(size: ChunkSize, (bits, count): (u32, u32)) -> Chunker {
+		Chunker { size, bits, count }
+	}
+	/// The last chunk, filled out with zeroes, if any bits are left over.
+	pub(crate) fn finish(self) -> Option<u16> {
+		if self.count == 0 {
+			return None;
+		}
+		Some((self.bits << (self.size.bits() - self.count)) as u16)
+	}
+}
+
+/// Joins chunks back into bytes as they arrive.
+#[derive(Debug, Clone)]
+pub(crate) struct Joiner {
+	size: ChunkSize,
+	/// The bits which haven't made up a whole byte yet.
+	bits: u32,
+	/// How many bits are in `bits`.
+	count: u32,
+}
+
+impl Joiner {
+	pub(crate) fn new(size: ChunkSize) -> Joiner {
+		Joiner {
+			size,
+			bits: 0,
+			count: 0,
+		}
+	}
+	/// Add a chunk, passing each byte it completes to `out`. Bits left over at the end are
+	/// the filling after the last chunk, and are dropped.
+	pub(crate) fn push(&mut self, chunk: u16, out: &mut impl Extend<u8>) {
+		self.bits = (self.bits << self.size.bits()) | chunk as u32;
+		self.count += self.size.bits();
+		while self.count >= 8 {
+			self.count -= 8;
+			out.extend([(self.bits >> self.count) as u8]);
+		}
+		self.bits &= (1 << self.count) - 1;
+	}
+}
+
+/// Cut `bytes` into chunks, filling out the last one with zeroes.
+pub(crate) fn split(bytes: &[u8], size: ChunkSize) -> Vec<u16> {
+	if size == ChunkSize::Sixteen {
+		return crate::simd::pack_pairs(bytes);
+	}
+	let mut chunker = Chunker::new(size);
+	let mut chunks: Vec<u16> = bytes.iter().filter_map(|v| chunker.push(*v)).collect();
+	chunks.extend(chunker.finish());
+	chunks
+}
+
+/// Join chunks back into bytes, dropping any bits left over.
+pub(crate) fn join<'c>(chunks: impl IntoIterator<Item = &'c u16>, size: ChunkSize) -> Vec<u8> {
+	let mut joiner = Joiner::new(size);
+	let mut bytes = vec![];
+	for chunk in chunks {
+		joiner.push(*chunk, &mut bytes);
+	}
+	bytes
+}
+
+/// Join chunks, some of which are missing, back into 16-bit words. A word is missing if any
+/// of its bits are.
+pub(crate) fn join_words(chunks: &[Option<u16>], size: ChunkSize) -> Vec<Option<u16>> {
+	if size == ChunkSize::Sixteen {
+		return chunks.to_vec();
+	}
+	let bits = size.bits() as usize;
+	let mut words = vec![Some(0u16); (chunks.len() * bits) / 16];
+	for (position, chunk) in chunks.iter().enumerate() {
+		for bit in 0..bits {
+			let at = position * bits + bit;
+			let Some(word) = words.get_mut(at / 16) else {
+				break;
+			};
+			match chunk {
+				Some(chunk) => {
+					if let Some(word) = word {
+						*word |= ((chunk >> (bits - 1 - bit)) & 1) << (15 - at % 16);
+					}
+				}
+				None => *word = None,
+			}
+		}
+	}
+	words
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ChunkSize;
+
+	#[test]
+	fn round_trip() {
+		let bytes: Vec<u8> = (0..=255).collect();
+		for size in [ChunkSize::Eight, ChunkSize::Twelve, ChunkSize::Sixteen] {
+			let chunks = super::split(&bytes, size);
+			assert_eq!(chunks.len(), size.chunks_for(bytes.len()));
+			assert!(chunks.iter().all(|v| (*v as usize) < size.values()));
+			assert_eq!(super::join(&chunks, size), bytes);
+			for value in [0, 1, 0xabc, 0x1234, u16::MAX] {
+				let chunks: Vec<u16> = size.split_value(value).collect();
+				assert_eq!(chunks.len(), size.chunks_per_value());
+				assert_eq!(size.join_value(&chunks), Some(value));
+			}
+		}
+		assert_eq!(ChunkSize::Twelve.join_value(&[0x10, 0]), None);
+		assert_eq!(
+			super::split(&[0xab, 0xcd], ChunkSize::Twelve),
+			[0xabc, 0xd00]
+		);
+	}
+	#[test]
+	fn missing_words() {
+		let chunks = [Some(0xabc), None, Some(0x123), Some(0x456)];
+		assert_eq!(
+			super::join_words(&chunks, ChunkSize::Twelve),
+			[None, None, Some(0x3456)]
+		);
+		assert_eq!(ChunkSize::fitting(4096 + 79), Some(ChunkSize::Twelve));
+		assert_eq!(ChunkSize::fitting(4096), Some(ChunkSize::Eight));
+		assert_eq!(ChunkSize::fitting(300), None);
+	}
+}
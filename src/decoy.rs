@@ -0,0 +1,68 @@
+//! Messages which look like armor but carry nothing, for keeping up constant chatter.
+
+use rand::Rng;
+
+use crate::{don_inner, DictMappings, Dictionary, Options};
+
+/// A message which looks just like a message of real armor donned with the default options,
+/// but carries random data, so a channel can carry a steady stream of them and real armor
+/// doesn't stand out when it's sent. It's one of the messages of armor for a random payload
+/// of up to a few messages, picked at random, so its marker, sequence number and length are
+/// distributed like real ones.
+pub fn generate_decoy<D: Dictionary>(
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	rng: &mut impl Rng,
+) -> String {
+	generate_decoy_with(dict, character_limit, &Options::default(), rng)
+}
+
+/// A decoy which looks like a message of armor donned with `options`, like `generate_decoy`.
+/// Real armor and decoys should be donned with the same options, or they can be told apart.
+pub fn generate_decoy_with<D: Dictionary>(
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	rng: &mut impl Rng,
+) -> String {
+	// A word carries at least a byte, and none is much shorter than a couple of characters
+	let len = rng.gen_range(0..=character_limit.min(1 << 16) * 2);
+	let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+	let mut messages = don_inner(&data, dict, character_limit, options, None, rng);
+	let index = rng.gen_range(0..messages.len());
+	messages.swap_remove(index)
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use rand::SeedableRng;
+
+	use super::generate_decoy;
+	use crate::{detect, doff_diagnose, don, DictMappings, MessageKind};
+
+	#[test]
+	fn decoys() {
+		let date = chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+		let dict = DictMappings::from_seed(69, &date);
+		let mut rng = rand_pcg::Pcg64::seed_from_u64(1);
+		let decoys: Vec<String> = (0..20)
+			.map(|_| generate_decoy(&dict, 100, &mut rng))
+			.collect();
+		assert!(decoys.iter().all(|v| v.len() <= 100));
+		assert!(decoys.iter().all(|v| detect(v, &dict).is_likely()));
+		let diagnoses = doff_diagnose(&decoys, &dict);
+		assert!(diagnoses.iter().all(|v| v.error.is_none()));
+		// Like real armor, most messages are fragments
+		let fragments = diagnoses
+			.iter()
+			.filter(|v| v.kind == MessageKind::Fragment)
+			.count();
+		assert!(fragments > 10 && fragments < 20);
+		// A decoy is scored just like the real armor it's mixed in with
+		let real = don(b"The real message", &dict, 100);
+		assert_eq!(
+			detect(&real[0], &dict).score(),
+			detect(&decoys[0], &dict).score()
+		);
+	}
+}
@@ -0,0 +1,116 @@
+//! Picking armor out of ordinary chatter without doffing it.
+
+use crate::header::Tag;
+use crate::{natural, DictMappings, Dictionary, MessageKind, Tokenizer};
+
+/// How much a message looks like armor under some mappings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Confidence {
+	/// The marker the message starts with.
+	pub kind: MessageKind,
+	/// Whether the marker is followed by what it should be, after any flags: a header tag
+	/// after `begin`, or a word with a meaning or the sequence tag after `fragment`. Ordinary text which happens
+	/// to start with a marker hardly ever is.
+	pub structured: bool,
+	/// The fraction of tokens which are in the dictionary.
+	pub known: f64,
+	/// The fraction of tokens which mean something under the mappings, as a chunk, marker,
+	/// tag or flag. With homophones, most of a big dictionary means something, so this is weak
+	/// evidence on its own.
+	pub meaningful: f64,
+}
+
+impl Confidence {
+	/// A score from 0 to 1. A marker followed by what it should be counts for 0.6, or 0.3 if
+	/// it isn't, and the fractions of known and meaningful tokens for 0.2 each. Armor scores
+	/// 1, or a little less with fillers, and text without a marker never scores more than 0.4.
+	pub fn score(&self) -> f64 {
+		let marker = match (self.kind, self.structured) {
+			(MessageKind::Unmarked, _) => 0.0,
+			(_, true) => 0.6,
+			(_, false) => 0.3,
+		};
+		marker + 0.2 * self.known + 0.2 * self.meaningful
+	}
+	/// Whether the message is probably armor, which is when it scores at least 0.8.
+	pub fn is_likely(&self) -> bool {
+		self.score() >= 0.8
+	}
+}
+
+/// Work out how much `text` looks like a message of armor under `dict`, from its marker and
+/// words alone. It's much quicker than doffing, and works on one message at a time, so bots
+/// can pick armor out of a channel as it arrives.
+pub fn detect<D: Dictionary>(text: &str, dict: &DictMappings<D>) -> Confidence {
+	let tokenizer = Tokenizer::default();
+	let indices: Vec<Option<u32>> = natural::tokens(text, tokenizer.separator.as_str())
+		.map(|v| natural::lookup(dict, v, &tokenizer.punctuation))
+		.collect();
+	let meaningful = |index: &u32| {
+		dict.reverse_lookup(*index).is_some()
+			|| dict.begin.contains(index)
+			|| dict.end.contains(index)
+			|| dict.fragment.contains(index)
+			|| dict.tags.contains(index)
+			|| dict.flags.contains(index)
+			|| dict.nulls.contains(index)
+	};
+	// Flags can come between the marker and what follows it
+	let after_flags = |rest: &[Option<u32>]| {
+		rest.iter()
+			.find(|v| !matches!(v, Some(v) if dict.flags.contains(v)))
+			.copied()
+	};
+	let (kind, structured) = match indices.as_slice() {
+		[Some(first), rest @ ..] if dict.begin.contains(first) => (
+			MessageKind::First,
+			matches!(after_flags(rest), Some(Some(v)) if dict.tags.contains(&v)),
+		),
+		[Some(first), rest @ ..] if dict.fragment.contains(first) => (
+			MessageKind::Fragment,
+			matches!(
+				after_flags(rest),
+				Some(Some(v)) if dict.reverse_lookup(v).is_some()
+					|| v == dict.tags[Tag::Sequence as usize]
+			),
+		),
+		_ => (MessageKind::Unmarked, false),
+	};
+	let fraction = |count: usize| match indices.len() {
+		0 => 0.0,
+		len => count as f64 / len as f64,
+	};
+	Confidence {
+		kind,
+		structured,
+		known: fraction(indices.iter().flatten().count()),
+		meaningful: fraction(indices.iter().flatten().filter(|v| meaningful(v)).count()),
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::detect;
+	use crate::{don, today, DictMappings, MessageKind};
+
+	#[test]
+	fn detects_armor() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data: Vec<u8> = (0..=255).collect();
+		let messages = don(&test_data, &dict, 100);
+		let first = detect(&messages[0], &dict);
+		assert_eq!(first.kind, MessageKind::First);
+		assert_eq!(first.score(), 1.0);
+		let second = detect(&messages[1], &dict);
+		assert_eq!(second.kind, MessageKind::Fragment);
+		assert!(second.is_likely());
+		for chatter in ["anyone up for lunch at noon? I'm thinking tacos", "", "ok"] {
+			let confidence = detect(chatter, &dict);
+			assert!(confidence.score() <= 0.4, "{:?}", confidence);
+			assert!(!confidence.is_likely());
+		}
+		// Without its marker, armor is only as suspicious as its words
+		let unmarked = messages[1].split_once(' ').unwrap().1;
+		assert!(!detect(unmarked, &dict).is_likely());
+	}
+}
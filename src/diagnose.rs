@@ -0,0 +1,105 @@
+//! Working out why armor won't doff.
+
+use crate::{doff_message, natural, CawError, DictMappings, Dictionary, Tokenizer, Tolerances};
+
+/// The marker which a message starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+	/// The message starts with `begin`, so it's the first and carries the header.
+	First,
+	/// The message starts with `fragment`.
+	Fragment,
+	/// The message doesn't start with either marker.
+	Unmarked,
+}
+
+/// What was found while reading a single message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+	/// The number of tokens in the message.
+	pub tokens: usize,
+	/// The positions of the tokens which aren't in the dictionary, counting from 0.
+	pub unknown: Vec<usize>,
+	/// The marker the message starts with, ignoring unknown tokens.
+	pub kind: MessageKind,
+	/// The message's position in the order of the armor, if it could be read.
+	pub sequence: Option<u32>,
+	/// The problem which stops the message being read, or which would if its armor doesn't
+	/// have fillers.
+	pub error: Option<CawError>,
+}
+
+/// Read every message on its own and report what was found, for working out why armor won't
+/// doff. Nothing is corrected or assembled, and the messages don't have to belong to the same
+/// armor.
+pub fn doff_diagnose<D: Dictionary>(messages: &[String], dict: &DictMappings<D>) -> Vec<Diagnosis> {
+	let tokenizer = Tokenizer::default();
+	messages
+		.iter()
+		.enumerate()
+		.map(|(message, text)| {
+			let indices: Vec<Option<u32>> = natural::tokens(text, tokenizer.separator.as_str())
+				.map(|v| natural::lookup(dict, v, &tokenizer.punctuation))
+				.collect();
+			let kind = match indices.iter().flatten().next() {
+				Some(v) if dict.begin.contains(v) => MessageKind::First,
+				Some(v) if dict.fragment.contains(v) => MessageKind::Fragment,
+				_ => MessageKind::Unmarked,
+			};
+			let (sequence, error) =
+				match doff_message(message, text, dict, &Tolerances::default(), &tokenizer) {
+					Ok(decoded) => (Some(decoded.sequence), decoded.skipped),
+					Err(e) => (None, Some(e)),
+				};
+			Diagnosis {
+				tokens: indices.len(),
+				unknown: (0..indices.len())
+					.filter(|v| indices[*v].is_none())
+					.collect(),
+				kind,
+				sequence,
+				error,
+			}
+		})
+		.collect()
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::{doff_diagnose, MessageKind};
+	use crate::{don, today, CawError, DictMappings};
+
+	#[test]
+	fn diagnose() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data: Vec<u8> = (0..=255).collect();
+		let mut messages = don(&test_data, &dict, 100);
+		let mut words: Vec<String> = messages[1].split(' ').map(String::from).collect();
+		words[5] = "xyzzy".to_string();
+		messages[1] = words.join(" ");
+		messages[2] = messages[2].split_once(' ').unwrap().1.to_string();
+		let diagnoses = doff_diagnose(&messages, &dict);
+		assert_eq!(diagnoses[0].kind, MessageKind::First);
+		assert_eq!(diagnoses[0].sequence, Some(0));
+		assert!(diagnoses[0].unknown.is_empty());
+		assert_eq!(diagnoses[0].error, None);
+		assert_eq!(diagnoses[1].kind, MessageKind::Fragment);
+		assert_eq!(diagnoses[1].sequence, Some(1));
+		assert_eq!(diagnoses[1].unknown, [5]);
+		assert_eq!(diagnoses[1].tokens, words.len());
+		assert_eq!(
+			diagnoses[1].error,
+			Some(CawError::UnknownWord {
+				message: 1,
+				word: "xyzzy".to_string()
+			})
+		);
+		assert_eq!(diagnoses[2].kind, MessageKind::Unmarked);
+		assert_eq!(diagnoses[2].sequence, None);
+		assert_eq!(
+			diagnoses[2].error,
+			Some(CawError::MissingMarker { message: 2 })
+		);
+		assert_eq!(diagnoses[3].sequence, Some(3));
+	}
+}
@@ -0,0 +1,428 @@
+//! Wordlists which can be used to build mappings.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::ChunkSize;
+
+// rust-analyzer doesn't like this but it works
+#[cfg(not(feature = "no-embedded-dict"))]
+const ENGLISH: &[&str] = &include!("../words");
+#[cfg(feature = "no-embedded-dict")]
+const ENGLISH: &[&str] = &[];
+#[cfg(feature = "lang-es")]
+const SPANISH: &[&str] = &include!("../words-es");
+#[cfg(feature = "lang-fr")]
+const FRENCH: &[&str] = &include!("../words-fr");
+#[cfg(feature = "lang-de")]
+const GERMAN: &[&str] = &include!("../words-de");
+#[cfg(feature = "emoji")]
+const EMOJI: &[&str] = &include!("../words-emoji");
+
+/// The fewest words a dictionary can have, which is enough for 8-bit chunks. Dictionaries
+/// need 4,175 words for 12-bit chunks and 65,615 for 16-bit ones.
+pub const MIN_LEN: usize = ChunkSize::Eight.min_len();
+
+/// A list of words which armor is made of.
+///
+/// Both parties must use exactly the same dictionary, in the same order.
+pub trait Dictionary: Sync {
+	/// The number of words in the dictionary.
+	fn len(&self) -> usize;
+	/// Whether the dictionary has no words at all.
+	fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+	/// The word at `index`. Panics if `index` is out of bounds.
+	fn word(&self, index: u32) -> &str;
+	/// The index of `word`, if it's in the dictionary.
+	fn index_of(&self, word: &str) -> Option<u32>;
+	/// The index of `word`, if it's in the dictionary in any case. An exact match wins, and
+	/// otherwise any word which is the same once both are lowercased will do. The default
+	/// only finds words which are entirely lowercase in the dictionary.
+	fn index_of_any_case(&self, word: &str) -> Option<u32> {
+		self.index_of(word)
+			.or_else(|| self.index_of(&word.to_lowercase()))
+	}
+	/// Check for words which would make armor unreadable, and whether there are enough words
+	/// to build mappings at all.
+	fn validate(&self) -> Validation {
+		let mut validation = Validation {
+			len: self.len(),
+			..Validation::default()
+		};
+		for index in 0..self.len() as u32 {
+			let word = self.word(index);
+			// Only the first copy of a word can be looked up
+			if self.index_of(word) != Some(index) {
+				validation.duplicates.push(index);
+			}
+			if word.chars().any(char::is_whitespace) {
+				validation.whitespace.push(index);
+			}
+			if word.chars().count() < 2 {
+				validation.short.push(index);
+			}
+		}
+		validation
+	}
+}
+
+/// The problems `Dictionary::validate` found with a dictionary. Each list holds the indices
+/// of the offending words, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validation {
+	/// The number of words in the dictionary, which must be at least `MIN_LEN`.
+	pub len: usize,
+	/// Words which are the same as an earlier word, so they'd be read back as that word.
+	pub duplicates: Vec<u32>,
+	/// Words containing whitespace, which would be read back as more than one word.
+	pub whitespace: Vec<u32>,
+	/// Words shorter than two characters, which are easily lost as stray letters or typos.
+	/// They can still be read back, so they don't make the dictionary invalid, and English
+	/// has a few.
+	pub short: Vec<u32>,
+}
+
+impl Validation {
+	/// Whether the dictionary has too few words to build mappings.
+	pub fn too_small(&self) -> bool {
+		self.len < MIN_LEN
+	}
+	/// The largest chunk size mappings built from the dictionary can have.
+	pub fn chunk_size(&self) -> Option<ChunkSize> {
+		ChunkSize::fitting(self.len)
+	}
+	/// Whether mappings can be built from the dictionary, and every word can be read back.
+	pub fn is_valid(&self) -> bool {
+		!self.too_small() && self.duplicates.is_empty() && self.whitespace.is_empty()
+	}
+}
+
+impl<D: Dictionary + ?Sized> Dictionary for &D {
+	fn len(&self) -> usize {
+		(**self).len()
+	}
+	fn word(&self, index: u32) -> &str {
+		(**self).word(index)
+	}
+	fn index_of(&self, word: &str) -> Option<u32> {
+		(**self).index_of(word)
+	}
+	fn index_of_any_case(&self, word: &str) -> Option<u32> {
+		(**self).index_of_any_case(word)
+	}
+}
+
+/// The wordlists which are built into the library. Languages other than English are only
+/// available with their `lang-*` feature enabled, and emoji with the `emoji` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Language {
+	/// The English wordlist at `/words`. With the `no-embedded-dict` feature it's left out
+	/// of the build and has no words, so mappings can't be built from it.
+	#[default]
+	English,
+	/// The Spanish wordlist at `/words-es`.
+	#[cfg(feature = "lang-es")]
+	Spanish,
+	/// The French wordlist at `/words-fr`.
+	#[cfg(feature = "lang-fr")]
+	French,
+	/// The German wordlist at `/words-de`.
+	#[cfg(feature = "lang-de")]
+	German,
+	/// The emoji at `/words-emoji`, for channels which only allow emoji. Every word is a
+	/// single emoji, so armor can leave out separators with `Separator::Nothing`. There are
+	/// only enough of them for 8-bit chunks.
+	#[cfg(feature = "emoji")]
+	Emoji,
+}
+
+impl Language {
+	fn words(self) -> &'static [&'static str] {
+		match self {
+			Language::English => ENGLISH,
+			#[cfg(feature = "lang-es")]
+			Language::Spanish => SPANISH,
+			#[cfg(feature = "lang-fr")]
+			Language::French => FRENCH,
+			#[cfg(feature = "lang-de")]
+			Language::German => GERMAN,
+			#[cfg(feature = "emoji")]
+			Language::Emoji => EMOJI,
+		}
+	}
+	/// The index of every word, which is built the first time it's needed.
+	fn indices(self) -> &'static HashMap<&'static str, u32> {
+		static ENGLISH_INDICES: OnceLock<HashMap<&str, u32>> = OnceLock::new();
+		#[cfg(feature = "lang-es")]
+		static SPANISH_INDICES: OnceLock<HashMap<&str, u32>> = OnceLock::new();
+		#[cfg(feature = "lang-fr")]
+		static FRENCH_INDICES: OnceLock<HashMap<&str, u32>> = OnceLock::new();
+		#[cfg(feature = "lang-de")]
+		static GERMAN_INDICES: OnceLock<HashMap<&str, u32>> = OnceLock::new();
+		#[cfg(feature = "emoji")]
+		static EMOJI_INDICES: OnceLock<HashMap<&str, u32>> = OnceLock::new();
+		let indices = match self {
+			Language::English => &ENGLISH_INDICES,
+			#[cfg(feature = "lang-es")]
+			Language::Spanish => &SPANISH_INDICES,
+			#[cfg(feature = "lang-fr")]
+			Language::French => &FRENCH_INDICES,
+			#[cfg(feature = "lang-de")]
+			Language::German => &GERMAN_INDICES,
+			#[cfg(feature = "emoji")]
+			Language::Emoji => &EMOJI_INDICES,
+		};
+		indices.get_or_init(|| {
+			let mut indices = HashMap::with_capacity(self.words().len());
+			for (index, word) in self.words().iter().enumerate() {
+				indices.entry(*word).or_insert(index as u32);
+			}
+			indices
+		})
+	}
+}
+
+impl Dictionary for Language {
+	fn len(&self) -> usize {
+		self.words().len()
+	}
+	fn word(&self, index: u32) -> &str {
+		self.words()[index as usize]
+	}
+	fn index_of(&self, word: &str) -> Option<u32> {
+		self.indices().get(word).copied()
+	}
+}
+
+/// A wordlist loaded at runtime.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+	feature = "serde",
+	derive(serde::Serialize, serde::Deserialize),
+	serde(from = "Vec<String>", into = "Vec<String>")
+)]
+pub struct WordList {
+	words: Vec<String>,
+	indices: HashMap<String, u32>,
+	/// The index of the first word with each lowercased spelling.
+	lowercase: HashMap<String, u32>,
+}
+
+impl WordList {
+	/// Build a wordlist from a list of words. If a word appears more than once, only its
+	/// first position can be looked up.
+	pub fn new(words: Vec<String>) -> WordList {
+		let mut indices = HashMap::with_capacity(words.len());
+		let mut lowercase = HashMap::new();
+		for (index, word) in words.iter().enumerate() {
+			indices.entry(word.clone()).or_insert(index as u32);
+			lowercase.entry(word.to_lowercase()).or_insert(index as u32);
+		}
+		WordList {
+			words,
+			indices,
+			lowercase,
+		}
+	}
+	/// Read a newline-separated wordlist, skipping blank lines.
+	pub fn read(reader: impl BufRead) -> io::Result<WordList> {
+		let words = reader
+			.lines()
+			.filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+			.map(|line| line.map(|line| line.trim().to_string()))
+			.collect::<io::Result<_>>()?;
+		Ok(WordList::new(words))
+	}
+	/// Read a newline-separated wordlist from a file, like `read`. The file is streamed, so
+	/// only the words are held in memory, and the wordlist can be changed without rebuilding
+	/// anything.
+	pub fn from_file(path: impl AsRef<Path>) -> io::Result<WordList> {
+		WordList::read(BufReader::new(File::open(path)?))
+	}
+}
+
+impl From<Vec<String>> for WordList {
+	fn from(words: Vec<String>) -> WordList {
+		WordList::new(words)
+	}
+}
+
+impl From<WordList> for Vec<String> {
+	fn from(words: WordList) -> Vec<String> {
+		words.words
+	}
+}
+
+impl Dictionary for WordList {
+	fn len(&self) -> usize {
+		self.words.len()
+	}
+	fn word(&self, index: u32) -> &str {
+		&self.words[index as usize]
+	}
+	fn index_of(&self, word: &str) -> Option<u32> {
+		self.indices.get(word).copied()
+	}
+	fn index_of_any_case(&self, word: &str) -> Option<u32> {
+		self.index_of(word)
+			.or_else(|| self.lowercase.get(&word.to_lowercase()).copied())
+	}
+}
+
+/// Read a word frequency list, for `DictMappings::with_frequencies`: lines of a word and how
+/// many times it turned up, separated by whitespace, like the lists made from large samples
+/// of real text. Blank lines are skipped, and so are lines whose count isn't a number.
+pub fn read_frequencies(reader: impl BufRead) -> io::Result<Vec<(String, u32)>> {
+	let mut counts = vec![];
+	for line in reader.lines() {
+		let line = line?;
+		let mut fields = line.split_whitespace();
+		if let (Some(word), Some(Ok(count))) = (fields.next(), fields.next().map(str::parse)) {
+			counts.push((word.to_string(), count));
+		}
+	}
+	Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Dictionary, WordList};
+	use crate::{today, DictMappings};
+
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn builtin_lookup() {
+		use super::Language;
+		for index in [0, 1234, 65550, 370000] {
+			let word = Language::English.word(index);
+			assert_eq!(Language::English.index_of(word), Some(index));
+		}
+		assert_eq!(Language::English.index_of("xyzzyplugh"), None);
+	}
+	#[test]
+	fn custom_wordlist() {
+		let text: String = (0..70000).map(|n| format!("word{}\n\n", n)).collect();
+		let words = WordList::read(text.as_bytes()).unwrap();
+		assert_eq!(words.len(), 70000);
+		assert_eq!(words.index_of("word1234"), Some(1234));
+		let dict = DictMappings::from_dictionary(words, 69, &today());
+		let test_data = "Armored with a homemade dictionary".as_bytes();
+		let messages = crate::don(test_data, &dict, 50);
+		assert!(messages[0].starts_with("word"));
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	fn from_file() {
+		let path = std::env::temp_dir().join(format!("caw-words-{}", std::process::id()));
+		let text: String = (0..5000).map(|n| format!("word{}\n", n)).collect();
+		std::fs::write(&path, text).unwrap();
+		let words = WordList::from_file(&path);
+		std::fs::remove_file(&path).unwrap();
+		let words = words.unwrap();
+		assert_eq!(words.len(), 5000);
+		assert_eq!(words.word(4321), "word4321");
+		assert!(WordList::from_file(&path).is_err());
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn frequencies() {
+		let dict = DictMappings::from_seed(69, &today());
+		let favourite = dict
+			.dictionary
+			.word(dict.homophones[2 * 65536 + 0x6361])
+			.to_string();
+		let list = format!("{} 100000\n\nthe 2000\nnonsense line\n", favourite);
+		let counts = super::read_frequencies(list.as_bytes()).unwrap();
+		assert_eq!(counts.len(), 2);
+		let dict = dict.with_frequencies(counts);
+		let options = crate::Options {
+			homophones: true,
+			..crate::Options::default()
+		};
+		let test_data = "cacacacacacacacacaca".as_bytes();
+		let messages = crate::don_with(test_data, &dict, 1000, &options);
+		let words = messages.iter().flat_map(|v| v.split(' '));
+		assert!(words.filter(|v| *v == favourite).count() > 7);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn validation() {
+		use super::Language;
+		let english = Language::English.validate();
+		assert!(english.is_valid());
+		assert_eq!(english.short.len(), 26);
+		let mut words: Vec<String> = (0..70000).map(|n| format!("word{}", n)).collect();
+		words[3] = "word1".to_string();
+		words[4] = "two words".to_string();
+		words[5] = "x".to_string();
+		let validation = WordList::new(words.clone()).validate();
+		assert!(!validation.is_valid());
+		assert!(!validation.too_small());
+		assert_eq!(validation.chunk_size(), Some(crate::ChunkSize::Sixteen));
+		assert_eq!(validation.duplicates, [3]);
+		assert_eq!(validation.whitespace, [4]);
+		assert_eq!(validation.short, [5]);
+		words.truncate(super::MIN_LEN - 1);
+		assert!(WordList::new(words).validate().too_small());
+	}
+	#[cfg(feature = "lang-es")]
+	#[test]
+	fn spanish() {
+		use super::Language;
+		let dict = DictMappings::from_seed_in(Language::Spanish, 69, &today());
+		let test_data = "Hola, ¿qué tal estás?".as_bytes();
+		let messages = crate::don(test_data, &dict, 50);
+		let first = messages[0].split(' ').next().unwrap();
+		assert!(Language::Spanish.index_of(first).is_some());
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[cfg(feature = "emoji")]
+	#[test]
+	fn emoji() {
+		use super::Language;
+		use crate::{ChunkSize, Options, Separator};
+		use unicode_segmentation::UnicodeSegmentation;
+		assert!(Language::Emoji.validate().is_valid());
+		let dict = DictMappings::from_seed_in(Language::Emoji, 69, &today());
+		assert_eq!(dict.chunk_size(), ChunkSize::Eight);
+		let test_data = "Only emoji allowed".as_bytes();
+		let options = Options {
+			separator: Separator::Nothing,
+			..Options::default()
+		};
+		let messages = crate::don_with(test_data, &dict, 100, &options);
+		for message in &messages {
+			assert!(message
+				.graphemes(true)
+				.all(|v| Language::Emoji.index_of(v).is_some()));
+		}
+		let separator = Separator::Nothing;
+		assert_eq!(
+			crate::doff_with_separator(&messages, &dict, &separator).unwrap(),
+			test_data
+		);
+		// Keyboards sometimes add a variation selector, asking for emoji to be drawn in color
+		let messages: Vec<String> = messages
+			.iter()
+			.map(|v| {
+				v.graphemes(true)
+					.map(|v| format!("{}\u{fe0f}", v))
+					.collect()
+			})
+			.collect();
+		assert_eq!(
+			crate::doff_with_separator(&messages, &dict, &separator).unwrap(),
+			test_data
+		);
+		// Separators don't hurt
+		let messages = crate::don(test_data, &dict, 100);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+	}
+}
@@ -0,0 +1,214 @@
+use std::{fmt, io};
+
+use chrono::NaiveDate;
+
+/// Everything that can go wrong while donning or doffing armor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CawError {
+	/// A token in a message isn't in the dictionary at all.
+	UnknownWord {
+		/// The position of the offending message in the input.
+		message: usize,
+		/// The token which couldn't be found.
+		word: String,
+	},
+	/// A message doesn't start with a `begin` or `fragment` marker.
+	MissingMarker {
+		/// The position of the offending message in the input.
+		message: usize,
+	},
+	/// A `fragment` marker isn't followed by its sequence number.
+	MissingSequence {
+		/// The position of the offending message in the input.
+		message: usize,
+	},
+	/// A dictionary word has no 16-bit meaning under the current mappings.
+	UnmappedWord {
+		/// The position of the offending message in the input.
+		message: usize,
+		/// The word which couldn't be reverse looked up.
+		word: String,
+	},
+	/// A reserved header word which this version of the library doesn't understand.
+	UnknownTag {
+		/// The position of the offending message in the input.
+		message: usize,
+		/// The unrecognized tag word.
+		word: String,
+	},
+	/// The armor was written in a later version of the format than this version of the
+	/// library can read.
+	UnsupportedVersion {
+		/// The position of the offending message in the input.
+		message: usize,
+		/// The version of the armor's format.
+		version: u16,
+	},
+	/// The armor was donned with mappings whose words carry a different number of bits.
+	WrongChunkSize {
+		/// The position of the offending message in the input.
+		message: usize,
+		/// The number of bits each word of the armor carries.
+		bits: u16,
+	},
+	/// A header field is missing some of its values.
+	TruncatedHeader {
+		/// The position of the offending message in the input.
+		message: usize,
+	},
+	/// Some of the messages are missing, so the data would be incomplete.
+	MissingFragments {
+		/// The sequence numbers of the missing messages, in order. If the armor doesn't say
+		/// how many messages there are and the last one is missing, only the first sequence
+		/// number after the messages which arrived is listed.
+		missing: Vec<u32>,
+	},
+	/// The messages belong to more than one armor, which `doff_all` can tell apart by their
+	/// message IDs.
+	MixedMessages,
+	/// The recovered data doesn't match its checksum.
+	ChecksumMismatch,
+	/// The armor is too damaged for error correction to repair.
+	Uncorrectable,
+	/// The word which says how much padding the data ends with is missing or impossible.
+	BadPadding,
+	/// The compressed data couldn't be decompressed.
+	BadCompression,
+	/// Encrypted data couldn't be decrypted, because it has been tampered with or was
+	/// sealed under a different key.
+	DecryptionFailed,
+	/// The data doesn't match its authentication code, so it wasn't armored by someone who
+	/// knows the seed, or has been changed since.
+	AuthenticationFailed,
+	/// The text doesn't have enough synonyms for all of the data hidden in it, or a synonym
+	/// which can't carry any, so it isn't the text data was hidden in.
+	TruncatedCover,
+	/// The header says the data starts with a file's name and timestamp, but it doesn't.
+	BadFileInfo,
+	/// The data isn't an archive made by `ArchiveBuilder`, or it's been cut short.
+	BadArchive,
+	/// The job was cancelled before it finished.
+	Cancelled,
+	/// The armor is older than the doffer allows, or doesn't say when it was donned.
+	Stale {
+		/// The date of the mappings the armor says it was donned with, if it says.
+		date: Option<NaiveDate>,
+	},
+	/// The same messages have already been doffed, so they're being sent again.
+	Replayed,
+	/// A message has the same sequence number as an earlier one, which the decode policy
+	/// doesn't allow.
+	DuplicateMessage {
+		/// The position of the later message in the input.
+		message: usize,
+	},
+	/// None of the messages carries the end marker, which the decode policy doesn't allow.
+	MissingEnd,
+	/// The armor takes more messages than were allowed.
+	TooManyMessages {
+		/// The number of messages the armor takes.
+		needed: usize,
+		/// The number of messages which were allowed.
+		allowed: usize,
+	},
+}
+
+impl fmt::Display for CawError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CawError::UnknownWord { message, word } => {
+				write!(f, "message {} contains unknown word {:?}", message, word)
+			}
+			CawError::MissingMarker { message } => {
+				write!(
+					f,
+					"message {} doesn't start with a begin or fragment marker",
+					message
+				)
+			}
+			CawError::MissingSequence { message } => {
+				write!(
+					f,
+					"message {} is missing its fragment sequence number",
+					message
+				)
+			}
+			CawError::UnmappedWord { message, word } => {
+				write!(
+					f,
+					"message {} contains word {:?} which has no meaning",
+					message, word
+				)
+			}
+			CawError::UnknownTag { message, word } => {
+				write!(
+					f,
+					"message {} contains unknown header tag {:?}",
+					message, word
+				)
+			}
+			CawError::UnsupportedVersion { message, version } => {
+				write!(
+					f,
+					"message {} is in version {} of the format, which isn't supported",
+					message, version
+				)
+			}
+			CawError::WrongChunkSize { message, bits } => {
+				write!(
+					f,
+					"message {} was donned with {}-bit chunks, which the mappings don't use",
+					message, bits
+				)
+			}
+			CawError::TruncatedHeader { message } => {
+				write!(f, "message {} has a truncated header", message)
+			}
+			CawError::MissingFragments { missing } => {
+				let missing: Vec<String> = missing.iter().map(|v| v.to_string()).collect();
+				write!(f, "the armor is missing fragments {}", missing.join(", "))
+			}
+			CawError::MixedMessages => write!(f, "the messages belong to more than one armor"),
+			CawError::ChecksumMismatch => write!(f, "the data doesn't match its checksum"),
+			CawError::Uncorrectable => write!(f, "the armor is too damaged to repair"),
+			CawError::BadPadding => write!(f, "the data's padding is malformed"),
+			CawError::BadCompression => write!(f, "the compressed data is corrupt"),
+			CawError::DecryptionFailed => write!(f, "the encrypted data couldn't be decrypted"),
+			CawError::AuthenticationFailed => write!(f, "the data isn't authentic"),
+			CawError::TruncatedCover => write!(f, "the text doesn't carry all of the hidden data"),
+			CawError::BadFileInfo => write!(f, "the file's name and timestamp are malformed"),
+			CawError::BadArchive => write!(f, "the archive is malformed"),
+			CawError::Cancelled => write!(f, "the job was cancelled"),
+			CawError::Stale { date: Some(date) } => {
+				write!(f, "the armor was donned on {}, which is too long ago", date)
+			}
+			CawError::Stale { date: None } => write!(f, "the armor doesn't say when it was donned"),
+			CawError::Replayed => write!(f, "the messages have already been doffed"),
+			CawError::DuplicateMessage { message } => {
+				write!(f, "message {} repeats an earlier message", message)
+			}
+			CawError::MissingEnd => write!(f, "the armor is missing its end marker"),
+			CawError::TooManyMessages { needed, allowed } => {
+				write!(
+					f,
+					"the armor takes {} messages, but only {} are allowed",
+					needed, allowed
+				)
+			}
+		}
+	}
+}
+
+impl std::error::Error for CawError {}
+
+/// Armor which can't be doffed is invalid data, as far as readers and writers are concerned.
+/// Cancelled jobs aren't `Interrupted`, since that would make `write_all` and `io::copy` carry
+/// on.
+impl From<CawError> for io::Error {
+	fn from(e: CawError) -> io::Error {
+		match e {
+			CawError::Cancelled => io::Error::other(e),
+			e => io::Error::new(io::ErrorKind::InvalidData, e),
+		}
+	}
+}
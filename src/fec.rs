@@ -0,0 +1,264 @@
+//! Reed–Solomon error correction over the 16-bit words of a payload.
+//!
+//! The payload is cut into blocks of up to `65535 - parity` words, and each block is followed
+//! by `parity` parity words. A block can recover from any combination of `e` wrong words and
+//! `m` missing words as long as `2e + m <= parity`.
+
+use std::sync::OnceLock;
+
+/// The number of non-zero elements in GF(2^16).
+const ORDER: usize = 65535;
+/// x^16 + x^12 + x^3 + x + 1, which generates GF(2^16).
+const POLYNOMIAL: u32 = 0x1100B;
+
+struct Field {
+	exp: Vec<u16>,
+	log: Vec<u16>,
+}
+
+fn field() -> &'static Field {
+	static FIELD: OnceLock<Field> = OnceLock::new();
+	FIELD.get_or_init(|| {
+		let mut exp = vec![0; ORDER * 2];
+		let mut log = vec![0; ORDER + 1];
+		let mut x: u32 = 1;
+		for (i, slot) in exp.iter_mut().take(ORDER).enumerate() {
+			*slot = x as u16;
+			log[x as usize] = i as u16;
+			x <<= 1;
+			if x & 0x10000 != 0 {
+				x ^= POLYNOMIAL;
+			}
+		}
+		for i in ORDER..ORDER * 2 {
+			exp[i] = exp[i - ORDER];
+		}
+		Field { exp, log }
+	})
+}
+
+fn mul(a: u16, b: u16) -> u16 {
+	if a == 0 || b == 0 {
+		return 0;
+	}
+	let field = field();
+	field.exp[field.log[a as usize] as usize + field.log[b as usize] as usize]
+}
+
+fn inverse(a: u16) -> u16 {
+	let field = field();
+	field.exp[ORDER - field.log[a as usize] as usize]
+}
+
+fn div(a: u16, b: u16) -> u16 {
+	mul(a, inverse(b))
+}
+
+/// The generator raised to `power`.
+fn alpha(power: usize) -> u16 {
+	field().exp[power % ORDER]
+}
+
+// Polynomials are stored with their highest-degree coefficient first.
+
+fn poly_mul(a: &[u16], b: &[u16]) -> Vec<u16> {
+	let mut result = vec![0; a.len() + b.len() - 1];
+	for (i, x) in a.iter().enumerate() {
+		for (j, y) in b.iter().enumerate() {
+			result[i + j] ^= mul(*x, *y);
+		}
+	}
+	result
+}
+
+fn poly_add(a: &[u16], b: &[u16]) -> Vec<u16> {
+	let len = a.len().max(b.len());
+	let mut result = vec![0; len];
+	for (i, x) in a.iter().enumerate() {
+		result[i + len - a.len()] = *x;
+	}
+	for (i, x) in b.iter().enumerate() {
+		result[i + len - b.len()] ^= *x;
+	}
+	result
+}
+
+fn poly_scale(a: &[u16], x: u16) -> Vec<u16> {
+	a.iter().map(|v| mul(*v, x)).collect()
+}
+
+fn poly_eval(a: &[u16], x: u16) -> u16 {
+	a.iter().fold(0, |acc, v| mul(acc, x) ^ *v)
+}
+
+fn generator(parity: usize) -> Vec<u16> {
+	(0..parity).fold(vec![1], |g, i| poly_mul(&g, &[1, alpha(i)]))
+}
+
+/// The largest number of data words in a single block.
+fn block_len(parity: usize) -> usize {
+	ORDER - parity
+}
+
+/// The number of words in the encoded form of `data_len` data words.
+pub(crate) fn encoded_len(data_len: usize, parity: usize) -> usize {
+	data_len + data_len.div_ceil(block_len(parity)) * parity
+}
+
+/// Append parity words to every block of `data`.
+pub(crate) fn encode(data: &[u16], parity: usize) -> Vec<u16> {
+	let generator = generator(parity);
+	let mut result = Vec::with_capacity(encoded_len(data.len(), parity));
+	for block in data.chunks(block_len(parity)) {
+		let mut remainder = vec![0; parity];
+		for word in block {
+			let coefficient = word ^ remainder[0];
+			remainder.rotate_left(1);
+			remainder[parity - 1] = 0;
+			for (slot, g) in remainder.iter_mut().zip(&generator[1..]) {
+				*slot ^= mul(*g, coefficient);
+			}
+		}
+		result.extend_from_slice(block);
+		result.extend(remainder);
+	}
+	result
+}
+
+/// Correct every block of `coded`, where missing words are `None`, and return the data words.
+pub(crate) fn decode(coded: &[Option<u16>], data_len: usize, parity: usize) -> Option<Vec<u16>> {
+	if coded.len() != encoded_len(data_len, parity) {
+		return None;
+	}
+	let mut result = Vec::with_capacity(data_len);
+	for block in coded.chunks(block_len(parity) + parity) {
+		let erasures: Vec<usize> = (0..block.len()).filter(|i| block[*i].is_none()).collect();
+		let mut block: Vec<u16> = block.iter().map(|v| v.unwrap_or(0)).collect();
+		correct(&mut block, parity, &erasures)?;
+		result.extend_from_slice(&block[..block.len() - parity]);
+	}
+	Some(result)
+}
+
+fn syndromes(block: &[u16], parity: usize) -> Vec<u16> {
+	(0..parity).map(|i| poly_eval(block, alpha(i))).collect()
+}
+
+/// Correct a single block in place, given the positions of the words known to be missing.
+fn correct(block: &mut [u16], parity: usize, erasures: &[usize]) -> Option<()> {
+	if erasures.len() > parity {
+		return None;
+	}
+	let syndromes = syndromes(block, parity);
+	if syndromes.iter().all(|v| *v == 0) {
+		return Some(());
+	}
+	let n = block.len();
+	// Remove the erasures' influence from the syndromes to find the remaining errors.
+	let mut forney = syndromes.clone();
+	for position in erasures {
+		let x = alpha(n - 1 - position);
+		for j in 0..forney.len() - 1 {
+			forney[j] = mul(forney[j], x) ^ forney[j + 1];
+		}
+	}
+	let locator = error_locator(&forney, parity, erasures.len())?;
+	let mut locator_reversed = locator.clone();
+	locator_reversed.reverse();
+	let errors: Vec<usize> = (0..n)
+		.filter(|i| poly_eval(&locator_reversed, alpha(*i)) == 0)
+		.map(|i| n - 1 - i)
+		.collect();
+	if errors.len() != locator.len() - 1 {
+		return None;
+	}
+	let positions: Vec<usize> = erasures.iter().chain(&errors).copied().collect();
+	correct_errata(block, &syndromes, &positions);
+	if syndromes_are_zero(block, parity) {
+		Some(())
+	} else {
+		None
+	}
+}
+
+fn syndromes_are_zero(block: &[u16], parity: usize) -> bool {
+	syndromes(block, parity).iter().all(|v| *v == 0)
+}
+
+/// Berlekamp–Massey, which finds the polynomial whose roots locate the errors.
+fn error_locator(syndromes: &[u16], parity: usize, erasure_count: usize) -> Option<Vec<u16>> {
+	let mut locator = vec![1];
+	let mut old = vec![1];
+	// The Forney syndromes only have this many useful values
+	for k in 0..parity - erasure_count {
+		let mut delta = syndromes[k];
+		for j in 1..locator.len().min(k + 1) {
+			delta ^= mul(locator[locator.len() - 1 - j], syndromes[k - j]);
+		}
+		old.push(0);
+		if delta != 0 {
+			if old.len() > locator.len() {
+				let new = poly_scale(&old, delta);
+				old = poly_scale(&locator, inverse(delta));
+				locator = new;
+			}
+			locator = poly_add(&locator, &poly_scale(&old, delta));
+		}
+	}
+	let leading = locator.iter().take_while(|v| **v == 0).count();
+	locator.drain(..leading);
+	let errors = locator.len() - 1;
+	if errors * 2 + erasure_count > parity {
+		return None;
+	}
+	Some(locator)
+}
+
+/// Forney's algorithm, which fixes the words at `positions`.
+fn correct_errata(block: &mut [u16], syndromes: &[u16], positions: &[usize]) {
+	let n = block.len();
+	let coefficients: Vec<usize> = positions.iter().map(|p| n - 1 - p).collect();
+	let locator = coefficients
+		.iter()
+		.fold(vec![1], |l, c| poly_mul(&l, &[alpha(*c), 1]));
+	// The evaluator is the syndrome polynomial times the locator, mod x^(errata + 1).
+	let mut padded = vec![0];
+	padded.extend_from_slice(syndromes);
+	padded.reverse();
+	let product = poly_mul(&padded, &locator);
+	let evaluator = &product[product.len() - locator.len()..];
+	let xs: Vec<u16> = coefficients.iter().map(|c| alpha(*c)).collect();
+	for (i, x) in xs.iter().enumerate() {
+		let x_inverse = inverse(*x);
+		let derivative = xs
+			.iter()
+			.enumerate()
+			.filter(|(j, _)| *j != i)
+			.fold(1, |acc, (_, other)| mul(acc, 1 ^ mul(x_inverse, *other)));
+		let y = mul(*x, poly_eval(evaluator, x_inverse));
+		block[positions[i]] ^= div(y, derivative);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{decode, encode};
+
+	#[test]
+	fn corrects_errors_and_erasures() {
+		let data: Vec<u16> = (0..500).map(|v| (v * 7919 % 65536) as u16).collect();
+		let coded = encode(&data, 16);
+		assert_eq!(coded.len(), 516);
+		let mut damaged: Vec<Option<u16>> = coded.iter().copied().map(Some).collect();
+		// Four wrong words and eight missing ones use up all 16 parity words
+		for i in [3, 100, 250, 510] {
+			damaged[i] = Some(damaged[i].unwrap() ^ 0x5555);
+		}
+		for slot in &mut damaged[40..48] {
+			*slot = None;
+		}
+		assert_eq!(decode(&damaged, 500, 16), Some(data.clone()));
+		damaged[300] = Some(damaged[300].unwrap() ^ 1);
+		assert_ne!(decode(&damaged, 500, 16), Some(data));
+	}
+}
@@ -0,0 +1,184 @@
+//! A C interface, built with the `ffi` feature. The declarations are in `include/caw.h`.
+//!
+//! Messages cross the boundary as UTF-8 text with one message per line. That only works
+//! because `caw_don` always uses the default options, whose messages never contain newlines.
+//! Rust callers can pick `Separator::Newline`, which puts a newline between words, but
+//! armor like that can't be passed to `caw_doff` as it is.
+
+use std::os::raw::c_int;
+use std::{ptr, slice};
+
+use crate::{today, DictMappings};
+
+/// The call succeeded.
+pub const CAW_OK: c_int = 0;
+/// A pointer was null, or text wasn't valid UTF-8.
+pub const CAW_INVALID_ARGUMENT: c_int = -1;
+/// The armor couldn't be doffed. The output buffer holds a description of the problem.
+pub const CAW_DOFF_FAILED: c_int = 1;
+
+/// Mappings which are owned by C code.
+pub struct CawDict(DictMappings);
+
+/// Bytes which are owned by Rust, and must be released with `caw_free`.
+#[repr(C)]
+pub struct CawBuffer {
+	/// The first byte.
+	pub data: *mut u8,
+	/// The number of bytes.
+	pub len: usize,
+}
+
+impl From<Vec<u8>> for CawBuffer {
+	fn from(bytes: Vec<u8>) -> CawBuffer {
+		let len = bytes.len();
+		CawBuffer {
+			data: Box::into_raw(bytes.into_boxed_slice()) as *mut u8,
+			len,
+		}
+	}
+}
+
+/// Build today's mappings for the 128-bit seed made of `seed_high` and `seed_low`.
+/// The mappings must be released with `caw_dict_free`.
+#[no_mangle]
+pub extern "C" fn caw_dict_new(seed_high: u64, seed_low: u64) -> *mut CawDict {
+	let seed = ((seed_high as u128) << 64) + seed_low as u128;
+	Box::into_raw(Box::new(CawDict(DictMappings::from_seed(seed, &today()))))
+}
+
+/// Release mappings built by `caw_dict_new`.
+///
+/// # Safety
+///
+/// `dict` must be null or come from `caw_dict_new`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn caw_dict_free(dict: *mut CawDict) {
+	if !dict.is_null() {
+		drop(Box::from_raw(dict));
+	}
+}
+
+/// Don `len` bytes at `data`, writing the messages to `out` one per line.
+///
+/// # Safety
+///
+/// `dict` must come from `caw_dict_new`, `data` must point to `len` readable bytes, and
+/// `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn caw_don(
+	dict: *const CawDict,
+	data: *const u8,
+	len: usize,
+	character_limit: usize,
+	out: *mut CawBuffer,
+) -> c_int {
+	if dict.is_null() || (data.is_null() && len != 0) || out.is_null() {
+		return CAW_INVALID_ARGUMENT;
+	}
+	let data = if len == 0 {
+		&[]
+	} else {
+		slice::from_raw_parts(data, len)
+	};
+	let messages = crate::don(data, &(*dict).0, character_limit);
+	out.write(messages.join("\n").into_bytes().into());
+	CAW_OK
+}
+
+/// Doff the `len` bytes of text at `messages`, which has one message per line, writing the
+/// recovered bytes to `out`. Blank lines are skipped.
+///
+/// # Safety
+///
+/// `dict` must come from `caw_dict_new`, `messages` must point to `len` readable bytes, and
+/// `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn caw_doff(
+	dict: *const CawDict,
+	messages: *const u8,
+	len: usize,
+	out: *mut CawBuffer,
+) -> c_int {
+	if dict.is_null() || (messages.is_null() && len != 0) || out.is_null() {
+		return CAW_INVALID_ARGUMENT;
+	}
+	let messages = if len == 0 {
+		&[]
+	} else {
+		slice::from_raw_parts(messages, len)
+	};
+	let messages: Vec<String> = match std::str::from_utf8(messages) {
+		Ok(text) => text
+			.lines()
+			.filter(|line| !line.trim().is_empty())
+			.map(|line| line.to_string())
+			.collect(),
+		Err(_) => return CAW_INVALID_ARGUMENT,
+	};
+	match crate::doff(&messages, &(*dict).0) {
+		Ok(data) => {
+			out.write(data.into());
+			CAW_OK
+		}
+		Err(e) => {
+			out.write(e.to_string().into_bytes().into());
+			CAW_DOFF_FAILED
+		}
+	}
+}
+
+/// Release a buffer written by `caw_don` or `caw_doff`.
+///
+/// # Safety
+///
+/// `buffer` must have been written by this library, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn caw_free(buffer: CawBuffer) {
+	if !buffer.data.is_null() {
+		drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+			buffer.data,
+			buffer.len,
+		)));
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use std::{ptr, slice};
+
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let test_data = "Armored from C".as_bytes();
+		unsafe {
+			let dict = caw_dict_new(0, 69);
+			let mut messages = CawBuffer {
+				data: ptr::null_mut(),
+				len: 0,
+			};
+			let status = caw_don(dict, test_data.as_ptr(), test_data.len(), 30, &mut messages);
+			assert_eq!(status, CAW_OK);
+			let mut doffed = CawBuffer {
+				data: ptr::null_mut(),
+				len: 0,
+			};
+			let status = caw_doff(dict, messages.data, messages.len, &mut doffed);
+			assert_eq!(status, CAW_OK);
+			assert_eq!(slice::from_raw_parts(doffed.data, doffed.len), test_data);
+			caw_free(doffed);
+			let mut error = CawBuffer {
+				data: ptr::null_mut(),
+				len: 0,
+			};
+			let nonsense = "xyzzyplugh".as_bytes();
+			let status = caw_doff(dict, nonsense.as_ptr(), nonsense.len(), &mut error);
+			assert_eq!(status, CAW_DOFF_FAILED);
+			assert!(slice::from_raw_parts(error.data, error.len).starts_with(b"message 0"));
+			caw_free(error);
+			caw_free(messages);
+			caw_dict_free(dict);
+		}
+	}
+}
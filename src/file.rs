@@ -0,0 +1,125 @@
+//! Keeping a file's name and timestamp with its contents.
+//!
+//! The record goes in front of the data before anything else is done to it, so it's
+//! compressed, encrypted and authenticated along with the data. It's the length of the name
+//! in bytes as a 16-bit big-endian number, the name in UTF-8, then the length of the file and
+//! the time it was last modified in seconds since the Unix epoch, as 64-bit big-endian
+//! numbers. A time of 0 means it isn't known.
+
+use std::convert::{TryFrom, TryInto};
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::CawError;
+
+/// What's kept of a file when it's armored along with its contents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileInfo {
+	/// The name of the file, without any directories.
+	pub name: String,
+	/// The length of the file in bytes. `don` fills it in from the data, but `DonWriter` uses
+	/// it as given.
+	pub len: u64,
+	/// When the file was last modified, in seconds since the Unix epoch, if that's known.
+	pub modified: Option<u64>,
+}
+
+impl FileInfo {
+	/// Read the name, length and modification time of the file at `path`.
+	pub fn from_path(path: impl AsRef<Path>) -> io::Result<FileInfo> {
+		let path = path.as_ref();
+		let metadata = path.metadata()?;
+		Ok(FileInfo {
+			name: path
+				.file_name()
+				.map(|v| v.to_string_lossy().into_owned())
+				.unwrap_or_default(),
+			len: metadata.len(),
+			modified: metadata
+				.modified()
+				.ok()
+				.and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+				.map(|v| v.as_secs())
+				.filter(|v| *v != 0),
+		})
+	}
+	/// The modification time as a `SystemTime`, for setting it on the file once it's written.
+	pub fn modified_time(&self) -> Option<SystemTime> {
+		self.modified
+			.map(|v| UNIX_EPOCH + std::time::Duration::from_secs(v))
+	}
+	/// The record which goes in front of the data.
+	///
+	/// Panics if the name is longer than 65,535 bytes.
+	pub(crate) fn encode(&self) -> Vec<u8> {
+		let name_len = u16::try_from(self.name.len()).expect("the file name is too long");
+		let mut record = name_len.to_be_bytes().to_vec();
+		record.extend_from_slice(self.name.as_bytes());
+		record.extend_from_slice(&self.len.to_be_bytes());
+		record.extend_from_slice(&self.modified.unwrap_or(0).to_be_bytes());
+		record
+	}
+	/// Split the record off the front of `data`.
+	pub(crate) fn split(mut data: Vec<u8>) -> Result<(FileInfo, Vec<u8>), CawError> {
+		let name_len = match data.get(..2) {
+			Some(v) => u16::from_be_bytes([v[0], v[1]]) as usize,
+			None => return Err(CawError::BadFileInfo),
+		};
+		let record_len = 2 + name_len + 16;
+		if data.len() < record_len {
+			return Err(CawError::BadFileInfo);
+		}
+		let name = std::str::from_utf8(&data[2..2 + name_len])
+			.map_err(|_| CawError::BadFileInfo)?
+			.to_string();
+		let number = |at: usize| u64::from_be_bytes(data[at..at + 8].try_into().unwrap());
+		let info = FileInfo {
+			name,
+			len: number(2 + name_len),
+			modified: Some(number(2 + name_len + 8)).filter(|v| *v != 0),
+		};
+		Ok((info, data.split_off(record_len)))
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::FileInfo;
+	use crate::{don_with, today, DictMappings, Options};
+
+	#[test]
+	fn file_info() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "The contents of notes.txt".as_bytes();
+		let options = Options {
+			file: Some(FileInfo {
+				name: "notes.txt".to_string(),
+				len: 0,
+				modified: Some(1_700_000_000),
+			}),
+			compress: true,
+			..Options::default()
+		};
+		let messages = don_with(test_data, &dict, 60, &options);
+		let (data, report) = crate::doff_with_report(&messages, &dict).unwrap();
+		assert_eq!(data, test_data);
+		let file = report.file.unwrap();
+		assert_eq!(file.name, "notes.txt");
+		assert_eq!(file.len, test_data.len() as u64);
+		assert_eq!(file.modified, Some(1_700_000_000));
+		// Streamed armor carries the record too
+		let mut messages = vec![];
+		crate::don_read(test_data, &dict, 60, &options, |v| {
+			messages.push(v);
+			Ok(())
+		})
+		.unwrap();
+		let (data, report) = crate::doff_with_report(&messages, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.file.unwrap().name, "notes.txt");
+		let plain = crate::don(test_data, &dict, 60);
+		assert_eq!(crate::doff_with_report(&plain, &dict).unwrap().1.file, None);
+		assert!(FileInfo::split(vec![0, 9, b'x']).is_err());
+	}
+}
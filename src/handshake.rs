@@ -0,0 +1,128 @@
+//! Agreeing on a seed over a channel anyone can read, with an x25519 key exchange.
+//!
+//! Each party starts a `Handshake` and sends the other its public key, as 24 BIP39 words or
+//! as bytes, then finishes the handshake with the key it got back. Both end up with the same
+//! seed, which nobody watching the channel can work out. Nothing checks who sent a key, so
+//! the parties should compare `DictMappings::fingerprint` once they have mappings, to be sure
+//! nobody swapped in a key of their own along the way.
+
+use std::convert::TryInto;
+use std::fmt;
+
+use bip39::Mnemonic;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+use crate::Seed;
+
+/// One side of a key exchange. The secret half is only used once, by `finish`.
+pub struct Handshake {
+	secret: EphemeralSecret,
+	public: PublicKey,
+}
+
+impl Handshake {
+	/// Start a handshake with a new random key pair.
+	pub fn new() -> Handshake {
+		let secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+		let public = PublicKey::from(&secret);
+		Handshake { secret, public }
+	}
+	/// The public key to send to the other party.
+	pub fn public_key(&self) -> [u8; 32] {
+		self.public.to_bytes()
+	}
+	/// The public key as a 24-word English BIP39 mnemonic, which can be sent anywhere words
+	/// can.
+	pub fn public_words(&self) -> String {
+		Mnemonic::from_entropy(self.public.as_bytes())
+			.expect("32 bytes is a valid entropy length")
+			.to_string()
+	}
+	/// Finish the handshake with the other party's public key, returning the shared seed.
+	/// Keys which would make the seed predictable, like all zeroes, are refused.
+	pub fn finish(self, theirs: &[u8; 32]) -> Result<Seed, InvalidPublicKey> {
+		let theirs = PublicKey::from(*theirs);
+		let shared = self.secret.diffie_hellman(&theirs);
+		if !shared.was_contributory() {
+			return Err(InvalidPublicKey);
+		}
+		// Both parties hash the keys in the same order, whichever of them is which
+		let (low, high) = if self.public.as_bytes() < theirs.as_bytes() {
+			(self.public, theirs)
+		} else {
+			(theirs, self.public)
+		};
+		let digest = Sha256::new()
+			.chain_update(b"caw handshake seed")
+			.chain_update(shared.as_bytes())
+			.chain_update(low.as_bytes())
+			.chain_update(high.as_bytes())
+			.finalize();
+		Ok(Seed(u128::from_be_bytes(digest[..16].try_into().unwrap())))
+	}
+}
+
+impl Default for Handshake {
+	fn default() -> Handshake {
+		Handshake::new()
+	}
+}
+
+impl fmt::Debug for Handshake {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Handshake")
+			.field("public", &self.public)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Read a public key from the 24 words `Handshake::public_words` made. Case and extra
+/// whitespace are ignored, but the checksum word has to be right.
+pub fn public_key_from_words(words: &str) -> Result<[u8; 32], InvalidPublicKey> {
+	let words: Vec<String> = words
+		.split_whitespace()
+		.map(|word| word.to_lowercase())
+		.collect();
+	Mnemonic::parse_in_normalized(bip39::Language::English, &words.join(" "))
+		.map_err(|_| InvalidPublicKey)?
+		.to_entropy()
+		.try_into()
+		.map_err(|_| InvalidPublicKey)
+}
+
+/// A public key which isn't 24 words from the BIP39 English wordlist with a valid checksum,
+/// or which would make the shared seed predictable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPublicKey;
+
+impl fmt::Display for InvalidPublicKey {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "the public key isn't valid")
+	}
+}
+
+impl std::error::Error for InvalidPublicKey {}
+
+#[cfg(test)]
+mod tests {
+	use super::{public_key_from_words, Handshake, InvalidPublicKey};
+
+	#[test]
+	fn handshake() {
+		let alice = Handshake::new();
+		let bob = Handshake::new();
+		let alice_words = alice.public_words();
+		assert_eq!(alice_words.split(' ').count(), 24);
+		let alice_key = public_key_from_words(&alice_words.to_uppercase()).unwrap();
+		assert_eq!(alice_key, alice.public_key());
+		let bob_key = bob.public_key();
+		let eve = Handshake::new();
+		let alice_seed = alice.finish(&bob_key).unwrap();
+		let bob_seed = bob.finish(&alice_key).unwrap();
+		assert_eq!(alice_seed, bob_seed);
+		assert_ne!(eve.finish(&bob_key).unwrap(), bob_seed);
+		assert_eq!(Handshake::new().finish(&[0; 32]), Err(InvalidPublicKey));
+		assert!(public_key_from_words("abandon abandon about").is_err());
+	}
+}
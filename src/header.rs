@@ -0,0 +1,924 @@
+//! The optional fields which follow the `begin` marker.
+//!
+//! Each field starts with one of the reserved tag words in `DictMappings::tags`, followed by
+//! a fixed number of 16-bit values, each of which is written as one chunk or two. Data starts at the first word which isn't a tag, so
+//! armor without any fields is read exactly like armor from before fields existed.
+
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+use chrono::{NaiveDate, NaiveTime, Timelike};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_pcg::Pcg64;
+use sha2::{Digest, Sha256};
+
+use crate::{
+	chunk, fec, CawError, ChunkSize, Decoded, DictMappings, Dictionary, FileInfo, Options,
+};
+
+/// How many dictionary words are reserved for tags, including ones which aren't used yet.
+pub(crate) const TAG_COUNT: usize = 64;
+
+/// The version of the format which `don` writes. Armor without a version field is version 0,
+/// which is read the same way, since version 1 only added the field.
+pub(crate) const VERSION: u16 = 1;
+
+/// How many dictionary words are reserved for metadata flags, including ones which aren't
+/// used yet.
+pub(crate) const FLAG_COUNT: usize = 16;
+
+/// Metadata which can be written as single words straight after a message's marker, numbered
+/// by their position in `DictMappings::flags`. Unlike header fields, a flag which isn't
+/// understood is skipped, so new ones can be added without breaking older readers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Flag {
+	/// The data was compressed before it was armored.
+	Compressed = 0,
+	/// The data was encrypted before it was armored.
+	Encrypted = 1,
+	/// The data is UTF-8 text, so it can be printed rather than saved.
+	Text = 2,
+	/// The message is the last one of the armor.
+	Final = 3,
+	/// The data is itself armor under the same mappings, with one message to a line, so
+	/// doffing it takes two layers.
+	Nested = 4,
+}
+
+impl Flag {
+	pub(crate) fn from_position(position: usize) -> Option<Flag> {
+		match position {
+			0 => Some(Flag::Compressed),
+			1 => Some(Flag::Encrypted),
+			2 => Some(Flag::Text),
+			3 => Some(Flag::Final),
+			4 => Some(Flag::Nested),
+			_ => None,
+		}
+	}
+	/// The flags which describe a payload packed with `header` from `data`, leaving out
+	/// `Final`, which belongs to a message rather than the payload.
+	pub(crate) fn for_payload(header: &Header, data: &[u8]) -> Vec<Flag> {
+		let mut flags = vec![];
+		if header.compressed {
+			flags.push(Flag::Compressed);
+		}
+		if header.encrypted {
+			flags.push(Flag::Encrypted);
+		}
+		if std::str::from_utf8(data).is_ok() {
+			flags.push(Flag::Text);
+		}
+		flags
+	}
+	/// Read the flags from the start of `words`, returning the ones which are understood
+	/// along with the rest of the words.
+	pub(crate) fn parse<'w, D: Dictionary>(
+		dict: &DictMappings<D>,
+		mut words: &'w [u32],
+	) -> (Vec<Flag>, &'w [u32]) {
+		let mut flags = vec![];
+		while let Some(position) = words
+			.first()
+			.and_then(|first| dict.flags.iter().position(|v| v == first))
+		{
+			flags.extend(Flag::from_position(position));
+			words = &words[1..];
+		}
+		(flags, words)
+	}
+}
+
+/// The kinds of header field, numbered by their position in `DictMappings::tags`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Tag {
+	/// The payload ends with a big-endian CRC32 of the data before it.
+	Checksum = 0,
+	/// The payload is protected by Reed–Solomon parity words. The values are the number of
+	/// parity words per block, then the high and low halves of the payload's length in bytes.
+	ErrorCorrection = 1,
+	/// The payload ends with two bytes holding the number of padding bytes before them,
+	/// which is fewer than the number of bytes in the smallest whole number of chunks: 2
+	/// for 16-bit chunks, 3 for 12-bit chunks and 1 for 8-bit chunks.
+	Padding = 2,
+	/// The data was compressed with DEFLATE before anything else was added to it.
+	Compressed = 3,
+	/// The data was encrypted with ChaCha20-Poly1305 after being compressed. It starts with
+	/// the 12-byte nonce, and ends with the 16-byte authentication tag.
+	Encrypted = 4,
+	/// The data ends with an HMAC-SHA256 of the data before it, which was added before
+	/// anything else.
+	Authenticated = 5,
+	/// The messages may contain tokens which aren't in the dictionary, which are skipped.
+	Fillers = 6,
+	/// The values are the high and low halves of the sequence number of the last fragment,
+	/// which is one less than the number of messages. It's always the last field, since it
+	/// isn't known until the rest of the armor has been split.
+	Fragments = 7,
+	/// Not a header field. It follows the `fragment` marker of messages whose sequence
+	/// number doesn't fit in 16 bits, and the values are its high and low halves.
+	Sequence = 8,
+	/// The value tells this armor apart from others sent through the same channel. Every
+	/// fragment repeats it after its sequence number.
+	MessageId = 9,
+	/// The values are the high and low halves of the session the armor belongs to. Every
+	/// fragment repeats it after its sequence number and message ID.
+	SessionId = 10,
+	/// The value is the version of the format, which decides how the rest of the armor is
+	/// read. It's always the first field.
+	Version = 11,
+	/// The value is the date of the mappings the armor was donned with, as the number of
+	/// days since the Unix epoch, wrapped to 16 bits. It's written as the word at that
+	/// position in the unshuffled dictionary, so it can be read without knowing the mappings.
+	/// It always comes straight after the version.
+	Date = 12,
+	/// The value is the number of bits each word carries, which is left out when it's 16.
+	/// Armor whose chunk size doesn't match the mappings' can't be read.
+	ChunkSize = 13,
+	/// The value says what kind of content the data is, as a `ContentType`.
+	ContentType = 14,
+	/// The data starts with a record of the name, length and modification time of the file
+	/// it came from, which was added before anything else.
+	File = 15,
+	/// The value is the time of day the armor was donned, in minutes since midnight UTC.
+	Time = 16,
+	/// The value is a random nonce which every chunk of the payload was XORed with, so the
+	/// same payload is written with different words every time it's donned.
+	Nonce = 17,
+	/// Every chunk of the payload was XORed with a keystream drawn from the mappings' key and
+	/// the nonce, after the nonce itself.
+	Keystream = 18,
+}
+
+impl Tag {
+	fn from_position(position: usize) -> Option<Tag> {
+		match position {
+			0 => Some(Tag::Checksum),
+			1 => Some(Tag::ErrorCorrection),
+			2 => Some(Tag::Padding),
+			3 => Some(Tag::Compressed),
+			4 => Some(Tag::Encrypted),
+			5 => Some(Tag::Authenticated),
+			6 => Some(Tag::Fillers),
+			7 => Some(Tag::Fragments),
+			8 => Some(Tag::Sequence),
+			9 => Some(Tag::MessageId),
+			10 => Some(Tag::SessionId),
+			11 => Some(Tag::Version),
+			12 => Some(Tag::Date),
+			13 => Some(Tag::ChunkSize),
+			14 => Some(Tag::ContentType),
+			15 => Some(Tag::File),
+			16 => Some(Tag::Time),
+			17 => Some(Tag::Nonce),
+			18 => Some(Tag::Keystream),
+			_ => None,
+		}
+	}
+	/// How many values follow the tag.
+	fn arity(self) -> usize {
+		match self {
+			Tag::Checksum => 0,
+			Tag::ErrorCorrection => 3,
+			Tag::Padding => 0,
+			Tag::Compressed => 0,
+			Tag::Encrypted => 0,
+			Tag::Authenticated => 0,
+			Tag::Fillers => 0,
+			Tag::Fragments => 2,
+			Tag::Sequence => 2,
+			Tag::MessageId => 1,
+			Tag::SessionId => 2,
+			Tag::Version => 1,
+			Tag::Date => 1,
+			Tag::ChunkSize => 1,
+			Tag::ContentType => 1,
+			Tag::File => 0,
+			Tag::Time => 1,
+			Tag::Nonce => 1,
+			Tag::Keystream => 0,
+		}
+	}
+}
+
+/// What kind of content armored data is, so whoever doffs it knows whether to print it, save
+/// it or decompress it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentType {
+	/// UTF-8 text.
+	Text = 0,
+	/// Anything which isn't text.
+	Binary = 1,
+	/// A gzip file.
+	Gzip = 2,
+	/// A JSON document.
+	Json = 3,
+}
+
+impl ContentType {
+	fn from_value(value: u16) -> Option<ContentType> {
+		match value {
+			0 => Some(ContentType::Text),
+			1 => Some(ContentType::Binary),
+			2 => Some(ContentType::Gzip),
+			3 => Some(ContentType::Json),
+			_ => None,
+		}
+	}
+}
+
+/// The parameters of a payload's error correction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ErrorCorrection {
+	/// The number of parity words after each block.
+	pub(crate) parity: u16,
+	/// The length of the payload before parity was added, in bytes.
+	pub(crate) len: u32,
+}
+
+impl ErrorCorrection {
+	fn data_words(&self) -> usize {
+		(self.len as usize).div_ceil(2)
+	}
+	/// The number of bytes the payload takes up once parity is added.
+	fn encoded_len(&self) -> usize {
+		fec::encoded_len(self.data_words(), self.parity as usize) * 2
+	}
+}
+
+/// The fields in a message's header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Header {
+	/// The version of the format, which is 0 if the armor doesn't say.
+	pub(crate) version: u16,
+	pub(crate) checksum: bool,
+	pub(crate) error_correction: Option<ErrorCorrection>,
+	pub(crate) padded: bool,
+	pub(crate) compressed: bool,
+	pub(crate) encrypted: bool,
+	pub(crate) authenticated: bool,
+	pub(crate) fillers: bool,
+	/// The date of the mappings, as `date_value` has it.
+	pub(crate) date: Option<u16>,
+	/// The time of day, in minutes since midnight.
+	pub(crate) time: Option<u16>,
+	/// The nonce the payload's chunks were XORed with, if any.
+	pub(crate) nonce: Option<u16>,
+	/// Whether the payload's chunks were XORed with a keystream.
+	pub(crate) keystream: bool,
+	/// The kind of content, which is `None` if the armor doesn't say or says something this
+	/// version of the library doesn't know.
+	pub(crate) content_type: Option<ContentType>,
+	/// Whether the data starts with a `FileInfo` record.
+	pub(crate) file: bool,
+	pub(crate) ids: Ids,
+	/// The sequence number of the last fragment, if the armor says how many there are.
+	pub(crate) fragments: Option<u32>,
+}
+
+impl Header {
+	/// The header for `options`, leaving out anything which depends on the data.
+	pub(crate) fn new(options: &Options) -> Header {
+		Header {
+			version: VERSION,
+			checksum: options.checksum,
+			error_correction: None,
+			padded: false,
+			compressed: false,
+			encrypted: options.encrypt,
+			authenticated: options.authenticate,
+			fillers: !options.fillers.is_empty(),
+			date: options.date.map(date_value),
+			time: options.time.map(time_value),
+			nonce: None,
+			keystream: options.keystream,
+			content_type: options.content_type,
+			file: options.file.is_some(),
+			ids: Ids::new(options),
+			fragments: None,
+		}
+	}
+	/// Whether the whole payload has to be known before the header can be written.
+	pub(crate) fn needs_whole_payload(options: &Options) -> bool {
+		options.error_correction != 0 || options.compress || options.encrypt || options.authenticate
+	}
+	/// Whether the whole payload has to arrive before any of it can be unpacked.
+	pub(crate) fn is_whole_payload(&self) -> bool {
+		self.error_correction.is_some() || self.compressed || self.encrypted || self.authenticated
+	}
+	/// The dictionary indices which make up the header.
+	pub(crate) fn encode<D: Dictionary>(&self, dict: &DictMappings<D>) -> Vec<u32> {
+		let mut fields: Vec<(Tag, Vec<u16>)> = vec![];
+		if self.version != 0 {
+			fields.push((Tag::Version, vec![self.version]));
+		}
+		if let Some(date) = self.date {
+			fields.push((Tag::Date, vec![date]));
+		}
+		if let Some(time) = self.time {
+			fields.push((Tag::Time, vec![time]));
+		}
+		if let Some(nonce) = self.nonce {
+			fields.push((Tag::Nonce, vec![nonce]));
+		}
+		if self.keystream {
+			fields.push((Tag::Keystream, vec![]));
+		}
+		if dict.chunk_size() != ChunkSize::Sixteen {
+			fields.push((Tag::ChunkSize, vec![dict.chunk_size().bits() as u16]));
+		}
+		if self.checksum {
+			fields.push((Tag::Checksum, vec![]));
+		}
+		if let Some(error_correction) = self.error_correction {
+			fields.push((
+				Tag::ErrorCorrection,
+				vec![
+					error_correction.parity,
+					(error_correction.len >> 16) as u16,
+					error_correction.len as u16,
+				],
+			));
+		}
+		if self.padded {
+			fields.push((Tag::Padding, vec![]));
+		}
+		if self.compressed {
+			fields.push((Tag::Compressed, vec![]));
+		}
+		if self.encrypted {
+			fields.push((Tag::Encrypted, vec![]));
+		}
+		if self.authenticated {
+			fields.push((Tag::Authenticated, vec![]));
+		}
+		if self.fillers {
+			fields.push((Tag::Fillers, vec![]));
+		}
+		if let Some(content_type) = self.content_type {
+			fields.push((Tag::ContentType, vec![content_type as u16]));
+		}
+		if self.file {
+			fields.push((Tag::File, vec![]));
+		}
+		fields.extend(self.ids.fields());
+		if let Some(fragments) = self.fragments {
+			fields.push((
+				Tag::Fragments,
+				vec![(fragments >> 16) as u16, fragments as u16],
+			));
+		}
+		encode_fields(dict, fields)
+	}
+	/// Read the header from the start of `words`, returning it along with the rest of the words.
+	pub(crate) fn parse<'w, D: Dictionary>(
+		dict: &DictMappings<D>,
+		message: usize,
+		mut words: &'w [u32],
+	) -> Result<(Header, &'w [u32]), CawError> {
+		let mut header = Header::default();
+		let chunk_size = dict.chunk_size();
+		let per_value = chunk_size.chunks_per_value();
+		while let Some(position) = words
+			.first()
+			.and_then(|first| dict.tags.iter().position(|v| v == first))
+		{
+			let tag = Tag::from_position(position)
+				.filter(|v| *v != Tag::Sequence)
+				.ok_or_else(|| CawError::UnknownTag {
+					message,
+					word: dict.dictionary.word(words[0]).to_string(),
+				})?;
+			let len = tag.arity() * per_value;
+			let chunks = words
+				.get(1..1 + len)
+				.ok_or(CawError::TruncatedHeader { message })?
+				.iter()
+				.map(|v| {
+					if tag == Tag::Date {
+						u16::try_from(*v)
+							.ok()
+							.filter(|v| (*v as usize) < chunk_size.values())
+					} else {
+						dict.reverse_lookup(*v)
+					}
+					.ok_or(CawError::TruncatedHeader { message })
+				})
+				.collect::<Result<Vec<u16>, CawError>>()?;
+			let values = chunks
+				.chunks(per_value)
+				.map(|v| {
+					chunk_size
+						.join_value(v)
+						.ok_or(CawError::TruncatedHeader { message })
+				})
+				.collect::<Result<Vec<u16>, CawError>>()?;
+			match (tag, values.as_slice()) {
+				// Every version so far is read the same way, and a later one might not be
+				(Tag::Version, [version]) => match version {
+					0..=VERSION => header.version = *version,
+					_ => {
+						return Err(CawError::UnsupportedVersion {
+							message,
+							version: *version,
+						})
+					}
+				},
+				(Tag::Date, [date]) => header.date = Some(*date),
+				(Tag::Time, [time]) => header.time = Some(*time),
+				(Tag::Nonce, [nonce]) => header.nonce = Some(*nonce),
+				(Tag::ChunkSize, [bits]) => {
+					if *bits as u32 != chunk_size.bits() {
+						return Err(CawError::WrongChunkSize {
+							message,
+							bits: *bits,
+						});
+					}
+				}
+				(Tag::Checksum, []) => header.checksum = true,
+				(Tag::Padding, []) => header.padded = true,
+				(Tag::Compressed, []) => header.compressed = true,
+				(Tag::Encrypted, []) => header.encrypted = true,
+				(Tag::Authenticated, []) => header.authenticated = true,
+				(Tag::Fillers, []) => header.fillers = true,
+				(Tag::File, []) => header.file = true,
+				(Tag::Keystream, []) => header.keystream = true,
+				// Kinds of content which are added later are only a hint, so they're ignored
+				(Tag::ContentType, [value]) => {
+					header.content_type = ContentType::from_value(*value)
+				}
+				(Tag::MessageId, [id]) => header.ids.message_id = Some(*id),
+				(Tag::SessionId, [high, low]) => {
+					header.ids.session_id = Some(((*high as u32) << 16) + *low as u32)
+				}
+				(Tag::Fragments, [high, low]) => {
+					header.fragments = Some(((*high as u32) << 16) + *low as u32)
+				}
+				(Tag::ErrorCorrection, [parity, high, low]) => {
+					header.error_correction = Some(ErrorCorrection {
+						parity: *parity,
+						len: ((*high as u32) << 16) + *low as u32,
+					})
+				}
+				_ => unreachable!("values always match the tag's arity"),
+			}
+			words = &words[1 + len..];
+		}
+		Ok((header, words))
+	}
+	/// Add everything `options` asks for to the data, producing the header and the payload
+	/// which is armored.
+	pub(crate) fn pack<D: Dictionary>(
+		dict: &DictMappings<D>,
+		options: &Options,
+		data: &[u8],
+		rng: &mut impl Rng,
+	) -> (Header, Vec<u8>) {
+		let mut header = Header::new(options);
+		if options.nonce || options.keystream {
+			header.nonce = Some(rng.gen());
+		}
+		let mut payload = match &options.file {
+			Some(file) => {
+				let file = FileInfo {
+					len: data.len() as u64,
+					..file.clone()
+				};
+				[file.encode(), data.to_vec()].concat()
+			}
+			None => data.to_vec(),
+		};
+		if header.authenticated {
+			payload.extend(authentication_code(&dict.mac_key, &payload));
+		}
+		if options.compress {
+			let mut encoder = DeflateEncoder::new(vec![], flate2::Compression::best());
+			encoder.write_all(&payload).unwrap();
+			let compressed = encoder.finish().unwrap();
+			if compressed.len() < payload.len() {
+				header.compressed = true;
+				payload = compressed;
+			}
+		}
+		if header.encrypted {
+			let nonce: [u8; 12] = rng.gen();
+			let sealed = ChaCha20Poly1305::new((&dict.key).into())
+				.encrypt(Nonce::from_slice(&nonce), &payload[..])
+				.expect("payloads are small enough to encrypt");
+			payload = nonce.to_vec();
+			payload.extend(sealed);
+		}
+		if header.checksum {
+			payload.extend_from_slice(&crc32fast::hash(&payload).to_be_bytes());
+		}
+		// Error correction records the exact length, so only plain payloads need padding
+		let group = dict.chunk_size().group();
+		if options.error_correction == 0 && !payload.len().is_multiple_of(group) {
+			header.padded = true;
+			payload.extend(padding(payload.len(), group));
+		}
+		if options.error_correction != 0 {
+			// A block needs room for at least one word of data
+			let parity = options.error_correction.min(u16::MAX - 1);
+			header.error_correction = Some(ErrorCorrection {
+				parity,
+				len: payload.len() as u32,
+			});
+			let words: Vec<u16> = payload
+				.chunks(2)
+				.map(|pair| ((pair[0] as u16) << 8) + *pair.get(1).unwrap_or(&0) as u16)
+				.collect();
+			payload = fec::encode(&words, parity as usize)
+				.into_iter()
+				.flat_map(|v| v.to_be_bytes())
+				.collect();
+		}
+		(header, payload)
+	}
+	/// The sequence numbers of the messages which are missing from `decoded`, which is sorted
+	/// by sequence number. Without a fragment count or the message carrying the end marker,
+	/// nobody knows how many messages come after the last one, so only the next one is listed.
+	pub(crate) fn missing(&self, decoded: &[Decoded]) -> Vec<u32> {
+		let last = self
+			.fragments
+			.or_else(|| decoded.iter().find(|v| v.last).map(|v| v.sequence))
+			.unwrap_or_else(|| decoded.last().map_or(0, |v| v.sequence.saturating_add(1)));
+		let mut present = decoded.iter().map(|v| v.sequence).peekable();
+		(0..=last)
+			.filter(|sequence| {
+				while present.next_if(|v| v < sequence).is_some() {}
+				present.next_if_eq(sequence).is_none()
+			})
+			.collect()
+	}
+	/// Join the chunks of messages, sorted by sequence number, back into the payload.
+	///
+	/// Without error correction, any missing message is an error. With it, missing messages
+	/// can be made up for if they're all in one run.
+	pub(crate) fn reassemble<D: Dictionary>(
+		&self,
+		dict: &DictMappings<D>,
+		decoded: Vec<Decoded>,
+	) -> Result<Vec<u8>, CawError> {
+		let chunk_size = dict.chunk_size();
+		let mut mask = self.mask(dict);
+		let error_correction = match self.error_correction {
+			Some(error_correction) => error_correction,
+			None => {
+				let missing = self.missing(&decoded);
+				if !missing.is_empty() {
+					return Err(CawError::MissingFragments { missing });
+				}
+				let chunks: Vec<u16> = decoded
+					.iter()
+					.flat_map(|v| &v.data)
+					.map(|v| v ^ mask.next())
+					.collect();
+				return Ok(chunk::join(&chunks, chunk_size));
+			}
+		};
+		let present: usize = decoded.iter().map(|v| v.data.len()).sum();
+		let missing = chunk_size
+			.chunks_for(error_correction.encoded_len())
+			.checked_sub(present)
+			.ok_or(CawError::Uncorrectable)?;
+		// Find where the run of missing messages starts, if there is one
+		let mut gap = None;
+		let mut expected = 0;
+		for (position, v) in decoded.iter().enumerate() {
+			if v.sequence as usize != expected {
+				if gap.is_some() {
+					return Err(CawError::Uncorrectable);
+				}
+				gap = Some(position);
+			}
+			expected = v.sequence as usize + 1;
+		}
+		if !decoded.last().is_some_and(|v| v.last) && missing != 0 {
+			if gap.is_some() {
+				return Err(CawError::Uncorrectable);
+			}
+			gap = Some(decoded.len());
+		}
+		let mut chunks: Vec<Option<u16>> = vec![];
+		for (position, v) in decoded.iter().enumerate() {
+			if gap == Some(position) {
+				chunks.extend(std::iter::repeat_n(None, missing));
+			}
+			chunks.extend(v.data.iter().map(|v| Some(*v)));
+		}
+		if gap == Some(decoded.len()) {
+			chunks.extend(std::iter::repeat_n(None, missing));
+		}
+		// Missing chunks still take up their place in the keystream
+		for chunk in &mut chunks {
+			let mask = mask.next();
+			if let Some(chunk) = chunk {
+				*chunk ^= mask;
+			}
+		}
+		self.join(chunk_size, &chunks)
+	}
+	/// Join the payload's chunks, in order, back into bytes, correcting it if it has error
+	/// correction. Missing chunks are only allowed with error correction.
+	pub(crate) fn join(
+		&self,
+		chunk_size: ChunkSize,
+		chunks: &[Option<u16>],
+	) -> Result<Vec<u8>, CawError> {
+		let Some(error_correction) = self.error_correction else {
+			return Ok(chunk::join(chunks.iter().flatten(), chunk_size));
+		};
+		let mut words = chunk::join_words(chunks, chunk_size);
+		words.truncate(error_correction.encoded_len() / 2);
+		self.correct(&words)
+	}
+	/// Apply error correction to the payload's words, returning the corrected bytes.
+	pub(crate) fn correct(&self, words: &[Option<u16>]) -> Result<Vec<u8>, CawError> {
+		let error_correction = match self.error_correction {
+			Some(error_correction) => error_correction,
+			None => {
+				return Ok(words
+					.iter()
+					.flatten()
+					.flat_map(|v| v.to_be_bytes())
+					.collect())
+			}
+		};
+		let mut payload: Vec<u8> = fec::decode(
+			words,
+			error_correction.data_words(),
+			error_correction.parity as usize,
+		)
+		.ok_or(CawError::Uncorrectable)?
+		.into_iter()
+		.flat_map(|v| v.to_be_bytes())
+		.collect();
+		payload.truncate(error_correction.len as usize);
+		Ok(payload)
+	}
+	/// What the payload's chunks were XORed with, from the first chunk on.
+	pub(crate) fn mask<D: Dictionary>(&self, dict: &DictMappings<D>) -> Mask {
+		let bits = (dict.chunk_size().values() - 1) as u16;
+		let keystream = self.keystream.then(|| {
+			let nonce = self.nonce.unwrap_or(0).to_be_bytes();
+			Pcg64::from_seed(
+				Sha256::digest([&b"caw keystream "[..], &dict.key, &nonce].concat()).into(),
+			)
+		});
+		Mask {
+			nonce: self.nonce,
+			keystream,
+			bits,
+		}
+	}
+	/// Check and remove everything the header promised, recovering the original data.
+	pub(crate) fn unpack<D: Dictionary>(
+		&self,
+		dict: &DictMappings<D>,
+		mut payload: Vec<u8>,
+	) -> Result<Vec<u8>, CawError> {
+		if self.padded {
+			let len = unpadded_len(&payload, dict.chunk_size().group())?;
+			payload.truncate(len);
+		}
+		if self.checksum {
+			let split = payload
+				.len()
+				.checked_sub(4)
+				.ok_or(CawError::ChecksumMismatch)?;
+			let expected = payload.split_off(split);
+			if crc32fast::hash(&payload).to_be_bytes() != expected[..] {
+				return Err(CawError::ChecksumMismatch);
+			}
+		}
+		if self.encrypted {
+			if payload.len() < 12 {
+				return Err(CawError::DecryptionFailed);
+			}
+			let (nonce, sealed) = payload.split_at(12);
+			payload = ChaCha20Poly1305::new((&dict.key).into())
+				.decrypt(Nonce::from_slice(nonce), sealed)
+				.map_err(|_| CawError::DecryptionFailed)?;
+		}
+		if self.compressed {
+			let mut data = vec![];
+			DeflateDecoder::new(&payload[..])
+				.read_to_end(&mut data)
+				.map_err(|_| CawError::BadCompression)?;
+			payload = data;
+		}
+		if self.authenticated {
+			let split = payload
+				.len()
+				.checked_sub(32)
+				.ok_or(CawError::AuthenticationFailed)?;
+			let code = payload.split_off(split);
+			let mut mac = hmac(&dict.mac_key);
+			mac.update(&payload);
+			mac.verify_slice(&code)
+				.map_err(|_| CawError::AuthenticationFailed)?;
+		}
+		Ok(payload)
+	}
+}
+
+/// The fields which every fragment repeats after its sequence number, so that it can be told
+/// apart from fragments of other armor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Ids {
+	pub(crate) message_id: Option<u16>,
+	pub(crate) session_id: Option<u32>,
+}
+
+impl Ids {
+	pub(crate) fn new(options: &Options) -> Ids {
+		Ids {
+			message_id: options.message_id,
+			session_id: options.session_id,
+		}
+	}
+	fn fields(self) -> Vec<(Tag, Vec<u16>)> {
+		let mut fields = vec![];
+		if let Some(message_id) = self.message_id {
+			fields.push((Tag::MessageId, vec![message_id]));
+		}
+		if let Some(session_id) = self.session_id {
+			fields.push((
+				Tag::SessionId,
+				vec![(session_id >> 16) as u16, session_id as u16],
+			));
+		}
+		fields
+	}
+	/// The dictionary indices which follow a fragment's sequence number.
+	pub(crate) fn encode<D: Dictionary>(self, dict: &DictMappings<D>) -> Vec<u32> {
+		encode_fields(dict, self.fields())
+	}
+	/// Read the IDs which follow a fragment's sequence number, returning them along with the
+	/// rest of the words.
+	pub(crate) fn parse<'w, D: Dictionary>(
+		dict: &DictMappings<D>,
+		message: usize,
+		mut words: &'w [u32],
+	) -> Result<(Ids, &'w [u32]), CawError> {
+		let mut ids = Ids::default();
+		for tag in [Tag::MessageId, Tag::SessionId] {
+			let rest = match words.split_first() {
+				Some((first, rest)) if *first == dict.tags[tag as usize] => rest,
+				_ => continue,
+			};
+			let chunk_size = dict.chunk_size();
+			let len = tag.arity() * chunk_size.chunks_per_value();
+			let chunks = rest
+				.get(..len)
+				.ok_or(CawError::TruncatedHeader { message })?
+				.iter()
+				.map(|v| {
+					dict.reverse_lookup(*v)
+						.ok_or(CawError::TruncatedHeader { message })
+				})
+				.collect::<Result<Vec<u16>, CawError>>()?;
+			let values = chunks
+				.chunks(chunk_size.chunks_per_value())
+				.map(|v| {
+					chunk_size
+						.join_value(v)
+						.ok_or(CawError::TruncatedHeader { message })
+				})
+				.collect::<Result<Vec<u16>, CawError>>()?;
+			match (tag, values.as_slice()) {
+				(Tag::MessageId, [id]) => ids.message_id = Some(*id),
+				(Tag::SessionId, [high, low]) => {
+					ids.session_id = Some(((*high as u32) << 16) + *low as u32)
+				}
+				_ => unreachable!("values always match the tag's arity"),
+			}
+			words = &rest[len..];
+		}
+		Ok((ids, words))
+	}
+}
+
+/// The dictionary indices for each tag followed by its values.
+fn encode_fields<D: Dictionary>(dict: &DictMappings<D>, fields: Vec<(Tag, Vec<u16>)>) -> Vec<u32> {
+	let chunk_size = dict.chunk_size();
+	fields
+		.into_iter()
+		.flat_map(|(tag, values)| {
+			let chunks = values
+				.into_iter()
+				.flat_map(move |v| chunk_size.split_value(v));
+			std::iter::once(dict.tags[tag as usize]).chain(chunks.map(move |v| {
+				if tag == Tag::Date {
+					v as u32
+				} else {
+					dict.words[v as usize]
+				}
+			}))
+		})
+		.collect()
+}
+
+/// The number of days between the Unix epoch and `date`, wrapped to 16 bits.
+pub(crate) fn date_value(date: NaiveDate) -> u16 {
+	let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+	(date - epoch).num_days().rem_euclid(1 << 16) as u16
+}
+
+/// The values which chunks of a payload are XORed with, one after another, which both hides
+/// and recovers them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Mask {
+	nonce: Option<u16>,
+	keystream: Option<Pcg64>,
+	/// The bits of a chunk.
+	bits: u16,
+}
+
+impl Mask {
+	/// What the next chunk is XORed with.
+	pub(crate) fn next(&mut self) -> u16 {
+		let nonce = self.nonce.unwrap_or(0) & self.bits;
+		match &mut self.keystream {
+			Some(keystream) => nonce ^ (keystream.next_u32() as u16 & self.bits),
+			None => nonce,
+		}
+	}
+	/// Skip the values for `chunks` chunks.
+	pub(crate) fn skip(&mut self, chunks: usize) {
+		if let Some(keystream) = &mut self.keystream {
+			keystream.advance(chunks as u128);
+		}
+	}
+	/// The header's nonce, which is all that's needed to start the mask over.
+	pub(crate) fn nonce(&self) -> Option<u16> {
+		self.nonce
+	}
+}
+
+/// The number of whole minutes between midnight and `time`.
+pub(crate) fn time_value(time: NaiveTime) -> u16 {
+	(time.num_seconds_from_midnight() / 60) as u16
+}
+
+/// The time of day `value` minutes after midnight, if there is one.
+pub(crate) fn time_from_value(value: u16) -> Option<NaiveTime> {
+	NaiveTime::from_num_seconds_from_midnight_opt(value as u32 * 60, 0)
+}
+
+/// The latest date on or before `today` with the date value `value`.
+pub(crate) fn date_from_value(value: u16, today: NaiveDate) -> NaiveDate {
+	let behind = (date_value(today) as i64 - value as i64).rem_euclid(1 << 16);
+	today - chrono::Duration::days(behind)
+}
+
+/// The date value in the header of a message, given as dictionary indices, if the message
+/// is the first one of armor donned with `dict` and has a date. Any flags between the begin
+/// marker and the header are skipped.
+pub(crate) fn first_message_date<D: Dictionary>(
+	dict: &DictMappings<D>,
+	words: &[u32],
+) -> Option<u16> {
+	let (first, words) = words.split_first()?;
+	if !dict.begin.contains(first) {
+		return None;
+	}
+	let (_, words) = Flag::parse(dict, words);
+	Header::parse(dict, 0, words).ok()?.0.date
+}
+
+fn hmac(key: &[u8; 32]) -> Hmac<Sha256> {
+	<Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+/// The HMAC-SHA256 of `data`.
+fn authentication_code(key: &[u8; 32], data: &[u8]) -> [u8; 32] {
+	let mut mac = hmac(key);
+	mac.update(data);
+	mac.finalize().into_bytes().into()
+}
+
+/// The padding which makes a payload of `len` bytes a multiple of `group` bytes long: as
+/// many zeroes as it takes, then their count in two bytes.
+pub(crate) fn padding(len: usize, group: usize) -> Vec<u8> {
+	let count = (group - (len + 2) % group) % group;
+	let mut padding = vec![0; count + 1];
+	padding.push(count as u8);
+	padding
+}
+
+/// The length of a payload padded to a multiple of `group` bytes once its padding is removed.
+pub(crate) fn unpadded_len(payload: &[u8], group: usize) -> Result<usize, CawError> {
+	let split = payload.len().checked_sub(2).ok_or(CawError::BadPadding)?;
+	let count = match payload[split..] {
+		[0, count] if (count as usize) < group => count as usize,
+		_ => return Err(CawError::BadPadding),
+	};
+	split.checked_sub(count).ok_or(CawError::BadPadding)
+}
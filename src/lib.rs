@@ -3,18 +3,126 @@
 //! Caw is a library which can armor data using a simple time-sensitive substitution
 //! cipher, intended to prevent automated identification of non-English data.
 
-use chrono::{Date, Datelike, Utc};
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Timelike, Utc};
 use rand::prelude::SliceRandom;
+use rand::{RngCore, SeedableRng};
 use rand_pcg::Pcg64;
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
 use rayon::prelude::*;
+#[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+use sequential::ParallelSlice;
+
+mod acrostic;
+pub mod analysis;
+mod archive;
+mod armorer;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+mod cache;
+mod chunk;
+mod decoy;
+mod detect;
+mod diagnose;
+pub mod dictionary;
+mod error;
+mod fec;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod file;
+pub mod handshake;
+mod header;
+mod markov;
+mod natural;
+mod padding;
+mod partial;
+mod pos;
+#[cfg(feature = "python")]
+pub mod python;
+mod ratchet;
+mod reassembler;
+mod replay;
+mod seed;
+mod simd;
+mod stream;
+mod synonym;
+mod templates;
+mod typo;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod zero_width;
+pub use acrostic::{doff_acrostic, don_acrostic};
+pub use archive::{ArchiveBuilder, ArchiveReader, Entry};
+pub use armorer::{Armorer, ArmorerBuilder};
+#[cfg(feature = "async")]
+pub use asynchronous::{doff_async, don_async, don_stream, DoffSink};
+pub use cache::DictCache;
+pub use chunk::ChunkSize;
+pub use decoy::{generate_decoy, generate_decoy_with};
+pub use detect::{detect, Confidence};
+pub use diagnose::{doff_diagnose, Diagnosis, MessageKind};
+pub use dictionary::{Dictionary, Language};
+pub use error::CawError;
+pub use file::FileInfo;
+pub use header::{ContentType, Flag};
+use header::{Header, Ids, Tag, FLAG_COUNT, TAG_COUNT};
+pub use markov::BigramModel;
+use padding::Padder;
+pub use partial::{doff_partial, Gap, Recovered};
+pub use pos::{PartOfSpeech, TaggedWordList};
+pub use ratchet::Ratchet;
+pub use reassembler::{PushResult, Reassembler};
+pub use replay::ReplayGuard;
+pub use seed::{InvalidMnemonic, PassphraseParams, Seed};
+pub use stream::{
+	doff_into, doff_into_cancellable, doff_into_with_progress, don_iter, don_read,
+	don_read_cancellable, don_read_with_progress, Checkpoint, DoffReader, DonWriter, Progress,
+	ProgressSink, CANCEL_CHUNK,
+};
+pub use synonym::{doff_cover, don_cover, Synonyms};
+pub use templates::{doff_templated, don_templated, Templates};
+pub use typo::Correction;
+pub use zero_width::{doff_zero_width, don_zero_width, strip_zero_width};
+
+/// Without the `parallel` feature, and in browsers, which don't have threads, the parallel
+/// iterators are swapped for ordinary ones.
+#[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+mod sequential {
+	pub(crate) trait ParallelSlice<T> {
+		fn par_iter(&self) -> std::slice::Iter<'_, T>;
+		fn par_iter_mut(&mut self) -> std::slice::IterMut<'_, T>;
+	}
 
-// rust-analyzer doesn't like this but it works
-const DICTIONARY: &[&str] = &include!("../words");
+	impl<T> ParallelSlice<T> for [T] {
+		fn par_iter(&self) -> std::slice::Iter<'_, T> {
+			self.iter()
+		}
+		fn par_iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+			self.iter_mut()
+		}
+	}
+}
 
-/// The mappings between 16-bit words and English words.
+/// The mappings between 16-bit words and the words of a dictionary.
+///
+/// With the `serde` feature, mappings can be serialized and shipped to someone who doesn't
+/// know the seed.
 #[derive(Debug)]
-pub struct DictMappings {
-	/// The corresponding indices for 16-bit words.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+	feature = "serde",
+	serde(
+		try_from = "StoredMappings<D>",
+		bound(deserialize = "D: serde::Deserialize<'de>")
+	)
+)]
+pub struct DictMappings<D: Dictionary = Language> {
+	/// The dictionary which the indices point into.
+	pub dictionary: D,
+	/// The corresponding indices for every chunk, so there are 256, 4,096 or 65,536 of
+	/// them, depending on the chunk size.
 	pub words: Vec<u32>,
 	/// The indices for the beginning of a message.
 	pub begin: Vec<u32>,
@@ -22,174 +130,3463 @@ pub struct DictMappings {
 	pub end: Vec<u32>,
 	/// The indices for the start of a message fragment.
 	pub fragment: Vec<u32>,
+	/// The indices which introduce fields in a message's header.
+	pub tags: Vec<u32>,
+	/// The indices which stand for metadata flags after a message's marker. Dictionaries
+	/// which don't have room for them after the tags have none.
+	pub flags: Vec<u32>,
+	/// The indices which carry nothing at all, and are skipped wherever they appear after a
+	/// message's header, so messages can be padded with them. Dictionaries which don't have
+	/// room for them after the flags have fewer or none, and so do mappings made by
+	/// `DictMappings::from_tagged`.
+	pub nulls: Vec<u32>,
+	/// Extra indices for chunks, in sets as big as `words`. With 16-bit chunks, the word at
+	/// `homophones[n * 65536 + v]` means the same as `words[v]`. Dictionaries which aren't big
+	/// enough have none.
+	pub homophones: Vec<u32>,
+	/// How often each word of the dictionary turns up in real text, by index, as relative
+	/// weights which `Options::homophones` picks between homophones by, so common words are
+	/// common in armor too. It doesn't change what any word means, so it can be left empty,
+	/// which gives every homophone the same chance, but it's kept with serialized mappings so
+	/// both parties write alike.
+	pub frequencies: Vec<u32>,
+	/// The key which encrypted payloads are sealed with, derived from the seed and date.
+	pub key: [u8; 32],
+	/// The key which authenticated payloads are signed with, derived from the seed and date.
+	pub mac_key: [u8; 32],
+	/// The chunk for each index in the dictionary, if it has one.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	inverse: Vec<Option<u16>>,
+}
+
+/// Serialized mappings, which are checked before they're turned into `DictMappings`.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct StoredMappings<D> {
+	dictionary: D,
+	words: Vec<u32>,
+	begin: Vec<u32>,
+	end: Vec<u32>,
+	fragment: Vec<u32>,
+	tags: Vec<u32>,
+	#[serde(default)]
+	flags: Vec<u32>,
+	#[serde(default)]
+	nulls: Vec<u32>,
+	#[serde(default)]
+	homophones: Vec<u32>,
+	#[serde(default)]
+	frequencies: Vec<u32>,
+	key: [u8; 32],
+	mac_key: [u8; 32],
+}
+
+#[cfg(feature = "serde")]
+impl<D: Dictionary> TryFrom<StoredMappings<D>> for DictMappings<D> {
+	type Error = String;
+	fn try_from(stored: StoredMappings<D>) -> Result<DictMappings<D>, String> {
+		let len = stored.dictionary.len();
+		let lists = [
+			&stored.words,
+			&stored.begin,
+			&stored.end,
+			&stored.fragment,
+			&stored.tags,
+			&stored.homophones,
+			&stored.flags,
+			&stored.nulls,
+		];
+		if lists
+			.iter()
+			.flat_map(|v| v.iter())
+			.any(|v| *v as usize >= len)
+		{
+			return Err("mappings point outside of the dictionary".to_string());
+		}
+		if ChunkSize::from_values(stored.words.len()).is_none()
+			|| !stored.homophones.len().is_multiple_of(stored.words.len())
+		{
+			return Err("mappings must have a word for every chunk".to_string());
+		}
+		if lists[1..5].iter().any(|v| v.is_empty()) || stored.tags.len() != TAG_COUNT {
+			return Err("mappings are missing reserved words".to_string());
+		}
+		if !stored.frequencies.is_empty() && stored.frequencies.len() != len {
+			return Err("mappings must have a frequency for every word or none".to_string());
+		}
+		Ok(DictMappings {
+			inverse: inverse(len, &stored.words, &stored.homophones),
+			dictionary: stored.dictionary,
+			words: stored.words,
+			begin: stored.begin,
+			end: stored.end,
+			fragment: stored.fragment,
+			tags: stored.tags,
+			flags: stored.flags,
+			nulls: stored.nulls,
+			homophones: stored.homophones,
+			frequencies: stored.frequencies,
+			key: stored.key,
+			mac_key: stored.mac_key,
+		})
+	}
+}
+
+/// The chunk for each index in a dictionary of `len` words.
+fn inverse(len: usize, words: &[u32], homophones: &[u32]) -> Vec<Option<u16>> {
+	let mut inverse = vec![None; len];
+	for (n, index) in words.iter().chain(homophones).enumerate() {
+		inverse[*index as usize] = Some((n % words.len()) as u16);
+	}
+	inverse
+}
+
+/// How many entries are set aside between the tags and the homophones, so that more reserved
+/// words can be added later without moving the homophones. The flags are the first of them,
+/// and the nulls come straight after the flags.
+const SPARE_COUNT: usize = 1024;
+
+/// How many of the spare entries are nulls.
+const NULL_COUNT: usize = 64;
+
+/// Today's date in UTC, which is the date the mappings are normally built for.
+pub fn today() -> NaiveDate {
+	Utc::now().date_naive()
+}
+
+/// The date in UTC at the instant `at`, which might not be the date in `at`'s own time zone.
+pub fn utc_date<Tz: TimeZone>(at: &DateTime<Tz>) -> NaiveDate {
+	at.with_timezone(&Utc).date_naive()
+}
+
+/// How often the mappings change. Both parties must use the same period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationPeriod {
+	/// New mappings every hour, for channels which see a lot of traffic.
+	Hourly,
+	/// New mappings every day at midnight UTC.
+	#[default]
+	Daily,
+	/// New mappings every ISO week, starting on Monday.
+	Weekly,
+	/// The same mappings forever, for archives which are read long after they were written.
+	Static,
+}
+
+impl RotationPeriod {
+	/// The string which the RNG is seeded with at the time `at`.
+	pub fn seed_string(self, seed: u128, at: &DateTime<Utc>) -> String {
+		match self {
+			RotationPeriod::Hourly => format!(
+				"{}{}{}{}T{}",
+				seed,
+				at.year(),
+				at.month(),
+				at.day(),
+				at.hour()
+			),
+			RotationPeriod::Daily => format!("{}{}{}{}", seed, at.year(), at.month(), at.day()),
+			RotationPeriod::Weekly => {
+				let week = at.iso_week();
+				format!("{}{}W{}", seed, week.year(), week.week())
+			}
+			RotationPeriod::Static => seed.to_string(),
+		}
+	}
+}
+
+/// How the dictionary is shuffled to build mappings. Both parties must use the same
+/// derivation, and it isn't recorded in the armor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Derivation {
+	/// Shuffle the whole dictionary, which every version of the library does.
+	#[default]
+	Full,
+	/// Shuffle only as many entries as the mappings assign, stopping the shuffle early. Each
+	/// entry is as likely to end up anywhere as with a full shuffle, but the time saved
+	/// depends on how much of the dictionary is left unassigned. English assigns most of its
+	/// entries to homophones, so it only saves about a tenth.
+	Partial,
+}
+
+/// How many words of the dictionary mark the beginning of armor, its end and the start of
+/// each fragment. More markers make armor harder to pick out by its first word, and fewer
+/// leave more of a small dictionary for chunks. Both parties must use the same counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MarkerCounts {
+	/// The number of words which begin armor.
+	pub begin: u8,
+	/// The number of words which end armor.
+	pub end: u8,
+	/// The number of words which start a fragment.
+	pub fragment: u8,
+}
+
+impl Default for MarkerCounts {
+	fn default() -> MarkerCounts {
+		MarkerCounts {
+			begin: 5,
+			end: 5,
+			fragment: 5,
+		}
+	}
+}
+
+impl MarkerCounts {
+	/// The number of marker words altogether.
+	pub fn total(&self) -> usize {
+		self.begin as usize + self.end as usize + self.fragment as usize
+	}
+	/// The number of words a dictionary needs for mappings with these markers and
+	/// `chunk_size`.
+	pub fn min_len(&self, chunk_size: ChunkSize) -> usize {
+		chunk_size.min_len() - MarkerCounts::default().total() + self.total()
+	}
+	/// The mapping version which is added to the seed string, so mappings with different
+	/// counts have nothing in common, not even their keys. The default counts have none, so
+	/// they build the same mappings as always.
+	fn version(&self) -> String {
+		if *self == MarkerCounts::default() {
+			String::new()
+		} else {
+			format!("M{}.{}.{}", self.begin, self.end, self.fragment)
+		}
+	}
+}
+
+/// Turn a seed string into the seed for a generator of type `R`, by hashing it with
+/// `rand_seeder`'s SipHash-based `Seeder` and reading as many bytes as `R` needs. Every
+/// generator is seeded this way, so any two implementations which agree on the generator
+/// agree on the mappings.
+pub fn derive_seed<R: SeedableRng>(seed: &str) -> R::Seed {
+	rand_seeder::Seeder::from(seed).make_seed()
 }
 
 impl DictMappings {
-	/// Build the dictionary mappings from a shared seed and the current date.
-	pub fn from_seed(seed: u128, date: &Date<Utc>) -> DictMappings {
-		use rand_seeder::Seeder;
-		let mut rng: Pcg64 = Seeder::from(format!(
-			"{}{}{}{}",
+	/// Build the dictionary mappings for the English dictionary from a shared seed and the
+	/// current date.
+	pub fn from_seed(seed: u128, date: &impl Datelike) -> DictMappings {
+		DictMappings::from_seed_in(Language::English, seed, date)
+	}
+	/// Build the dictionary mappings for one of the built-in languages from a shared seed and
+	/// the current date.
+	pub fn from_seed_in(language: Language, seed: u128, date: &impl Datelike) -> DictMappings {
+		DictMappings::from_dictionary(language, seed, date)
+	}
+	/// Build the dictionary mappings for the English dictionary from a shared seed, for the
+	/// rotation period containing `at`.
+	pub fn from_seed_rotating(
+		seed: u128,
+		period: RotationPeriod,
+		at: &DateTime<Utc>,
+	) -> DictMappings {
+		DictMappings::from_dictionary_rotating(Language::English, seed, period, at)
+	}
+}
+
+impl<D: Dictionary> DictMappings<D> {
+	/// Build the dictionary mappings for any dictionary from a shared seed and the current date.
+	///
+	/// The chunk size is the largest the dictionary is big enough for. Panics if the
+	/// dictionary has fewer than 335 words. `Dictionary::validate` checks for this and other
+	/// problems beforehand.
+	pub fn from_dictionary(dictionary: D, seed: u128, date: &impl Datelike) -> DictMappings<D> {
+		DictMappings::from_seed_string(
+			dictionary,
+			&format!("{}{}{}{}", seed, date.year(), date.month(), date.day()),
+		)
+	}
+	/// Build the dictionary mappings for any dictionary from a shared seed, for the rotation
+	/// period containing `at`.
+	///
+	/// Panics if the dictionary has fewer than 335 words.
+	pub fn from_dictionary_rotating(
+		dictionary: D,
+		seed: u128,
+		period: RotationPeriod,
+		at: &DateTime<Utc>,
+	) -> DictMappings<D> {
+		DictMappings::from_seed_string(dictionary, &period.seed_string(seed, at))
+	}
+	/// Build the dictionary mappings for any dictionary from a seed string, such as one from
+	/// `RotationPeriod::seed_string`.
+	///
+	/// Panics if the dictionary has fewer than 335 words.
+	pub fn from_seed_string(dictionary: D, seed: &str) -> DictMappings<D> {
+		DictMappings::from_seed_string_with::<Pcg64>(dictionary, seed)
+	}
+	/// Build the dictionary mappings for any dictionary from a seed string, shuffling the
+	/// dictionary with a generator of type `R` instead of `Pcg64`. The generator is seeded by
+	/// `derive_seed`, and the keys don't depend on the generator. Both parties must use the
+	/// same generator.
+	///
+	/// Panics if the dictionary has fewer than 335 words.
+	pub fn from_seed_string_with<R: SeedableRng + RngCore>(
+		dictionary: D,
+		seed: &str,
+	) -> DictMappings<D> {
+		let chunk_size = ChunkSize::fitting(dictionary.len()).expect("dictionary is too small");
+		DictMappings::from_seed_string_sized::<R>(dictionary, seed, chunk_size)
+	}
+	/// Build the dictionary mappings for any dictionary from a seed string, like
+	/// `from_seed_string_with`, with a chunk size of its own instead of the largest one the
+	/// dictionary is big enough for. Smaller chunks make for longer armor. Both parties must
+	/// use the same chunk size.
+	///
+	/// Panics if the dictionary has fewer than `chunk_size.min_len()` words.
+	pub fn from_seed_string_sized<R: SeedableRng + RngCore>(
+		dictionary: D,
+		seed: &str,
+		chunk_size: ChunkSize,
+	) -> DictMappings<D> {
+		DictMappings::from_seed_string_derived::<R>(
+			dictionary,
 			seed,
-			date.year(),
-			date.month(),
-			date.day()
-		))
-		.make_rng();
-		let mut indices: Vec<u32> = (0..DICTIONARY.len() as u32).collect();
-		indices.shuffle(&mut rng);
+			chunk_size,
+			Derivation::default(),
+		)
+	}
+	/// Build the dictionary mappings for any dictionary from a seed string, like
+	/// `from_seed_string_sized`, choosing how the dictionary is shuffled. Both parties must
+	/// use the same derivation.
+	///
+	/// Panics if the dictionary has fewer than `chunk_size.min_len()` words.
+	pub fn from_seed_string_derived<R: SeedableRng + RngCore>(
+		dictionary: D,
+		seed: &str,
+		chunk_size: ChunkSize,
+		derivation: Derivation,
+	) -> DictMappings<D> {
+		DictMappings::from_seed_string_markers::<R>(
+			dictionary,
+			seed,
+			chunk_size,
+			derivation,
+			MarkerCounts::default(),
+		)
+	}
+	/// Build the dictionary mappings for any dictionary from a seed string, like
+	/// `from_seed_string_derived`, with a number of markers of its own. Counts other than
+	/// the default add a mapping version to the seed string, so both parties must use the
+	/// same counts.
+	///
+	/// Panics if any count is zero, or the dictionary has fewer than `markers.min_len()`
+	/// words.
+	pub fn from_seed_string_markers<R: SeedableRng + RngCore>(
+		dictionary: D,
+		seed: &str,
+		chunk_size: ChunkSize,
+		derivation: Derivation,
+		markers: MarkerCounts,
+	) -> DictMappings<D> {
+		use sha2::{Digest, Sha256};
+		assert!(
+			markers.begin > 0 && markers.end > 0 && markers.fragment > 0,
+			"every marker needs at least one word"
+		);
+		assert!(
+			dictionary.len() >= markers.min_len(chunk_size),
+			"dictionary is too small"
+		);
+		let seed = format!("{}{}", seed, markers.version());
+		let seed = seed.as_str();
+		let mut rng = R::from_seed(derive_seed::<R>(seed));
+		let mut indices: Vec<u32> = (0..dictionary.len() as u32).collect();
+		let chunks = chunk_size.values();
+		let reserved = markers.total();
+		let used = reserved + chunks + TAG_COUNT + SPARE_COUNT;
+		let sets = dictionary.len().saturating_sub(used) / chunks;
+		let (list, homophones): (&[u32], Vec<u32>) = match derivation {
+			Derivation::Full => {
+				indices.shuffle(&mut rng);
+				// Homophones are taken from the far end of the list, so nothing before them
+				// moves
+				let homophones = indices.iter().rev().take(sets * chunks).copied().collect();
+				(&indices, homophones)
+			}
+			Derivation::Partial => {
+				let assigned = used.min(dictionary.len()) + sets * chunks;
+				let (list, _) = indices.partial_shuffle(&mut rng, assigned);
+				let homophones = list.get(used..).unwrap_or_default().to_vec();
+				(list, homophones)
+			}
+		};
+		let words = list[reserved..reserved + chunks].to_vec();
+		// Everything after the chunks' words was unused before tags existed, so assigning
+		// them doesn't disturb the rest of the mappings.
+		let tags = list[reserved + chunks..][..TAG_COUNT].to_vec();
+		let (begin, rest) = list.split_at(markers.begin as usize);
+		let (end, rest) = rest.split_at(markers.end as usize);
+		let fragment = &rest[..markers.fragment as usize];
+		// The flags are the first of the spare entries, if the dictionary reaches that far
+		let flags = list
+			.get(reserved + chunks + TAG_COUNT..)
+			.and_then(|v| v.get(..FLAG_COUNT))
+			.unwrap_or_default()
+			.to_vec();
+		let nulls = list
+			.get(reserved + chunks + TAG_COUNT + FLAG_COUNT..)
+			.map(|v| &v[..v.len().min(NULL_COUNT)])
+			.unwrap_or_default()
+			.to_vec();
 		DictMappings {
-			begin: indices[0..5].to_vec(),
-			end: indices[5..10].to_vec(),
-			fragment: indices[10..15].to_vec(),
-			words: indices[15..16 + (u16::MAX as usize)].to_vec(),
+			inverse: inverse(dictionary.len(), &words, &homophones),
+			dictionary,
+			begin: begin.to_vec(),
+			end: end.to_vec(),
+			fragment: fragment.to_vec(),
+			words,
+			tags,
+			flags,
+			nulls,
+			homophones,
+			frequencies: vec![],
+			key: Sha256::digest(format!("caw encryption key {}", seed)).into(),
+			mac_key: Sha256::digest(format!("caw authentication key {}", seed)).into(),
+		}
+	}
+	/// One of the dictionary indices which mean the chunk `value`, picked at random, weighted
+	/// by `frequencies` if there are any.
+	pub(crate) fn random_word_for(&self, value: u16, rng: &mut impl rand::Rng) -> u32 {
+		let total: u64 = match self.frequencies.is_empty() {
+			true => 0,
+			false => self
+				.words_for(value)
+				.map(|v| self.frequencies[v as usize] as u64)
+				.sum(),
+		};
+		if total != 0 {
+			let mut point = rng.gen_range(0..total);
+			for index in self.words_for(value) {
+				let weight = self.frequencies[index as usize] as u64;
+				if point < weight {
+					return index;
+				}
+				point -= weight;
+			}
+		}
+		let count = 1 + self.homophones.len() / self.words.len();
+		match rng.gen_range(0..count) {
+			0 => self.words[value as usize],
+			set => self.homophones[(set - 1) * self.words.len() + value as usize],
+		}
+	}
+	/// The dictionary index which means the chunk `value` in the `set`th set of words, where
+	/// 0 is `words` and the rest are sets of homophones. Sets the mappings don't have fall
+	/// back to `words`.
+	pub(crate) fn word_in_set(&self, value: u16, set: usize) -> u32 {
+		match set {
+			0 => self.words[value as usize],
+			set => self
+				.homophones
+				.get((set - 1) * self.words.len() + value as usize)
+				.copied()
+				.unwrap_or(self.words[value as usize]),
+		}
+	}
+	/// Weigh the choice between homophones by how often each word turns up in real text, as
+	/// counts like the ones `dictionary::read_frequencies` reads. Words are matched in any
+	/// case, and ones which aren't counted are taken to have turned up once.
+	pub fn with_frequencies<S: AsRef<str>>(
+		mut self,
+		counts: impl IntoIterator<Item = (S, u32)>,
+	) -> DictMappings<D> {
+		let mut frequencies = vec![1; self.dictionary.len()];
+		for (word, count) in counts {
+			if let Some(index) = self.dictionary.index_of_any_case(word.as_ref()) {
+				frequencies[index as usize] = count.saturating_add(1);
+			}
 		}
+		self.frequencies = frequencies;
+		self
 	}
-	/// Look up a 16-bit word given its index in the dictionary.
+	/// Every dictionary index which means the chunk `value`, starting with the one in `words`.
+	pub fn words_for(&self, value: u16) -> impl Iterator<Item = u32> + '_ {
+		std::iter::once(self.words[value as usize]).chain(
+			self.homophones
+				.iter()
+				.skip(value as usize)
+				.step_by(self.words.len())
+				.copied(),
+		)
+	}
+	/// Look up a chunk given its index in the dictionary.
 	pub fn reverse_lookup(&self, index: u32) -> Option<u16> {
-		self.words
-			.iter()
-			.enumerate()
-			.filter(|(_, v)| **v == index)
-			.map(|(n, _)| n as u16)
-			.next()
+		self.inverse.get(index as usize).copied().flatten()
+	}
+	/// How many bits each word of armor carries.
+	pub fn chunk_size(&self) -> ChunkSize {
+		ChunkSize::from_values(self.words.len()).expect("mappings have a word for every chunk")
+	}
+	/// A short phrase of three words which depends on everything that makes up the mappings:
+	/// the seed, the date, the wordlist and how it was shuffled. Two parties whose phrases
+	/// match have the same mappings, so they can compare them out loud before sending
+	/// anything. The words are picked by position in the unshuffled wordlist, so the phrase
+	/// gives away nothing about the mappings.
+	pub fn fingerprint(&self) -> String {
+		use sha2::{Digest, Sha256};
+		let mut hasher = Sha256::new();
+		hasher.update(b"caw fingerprint");
+		hasher.update(self.key);
+		hasher.update(self.mac_key);
+		for list in [
+			&self.begin,
+			&self.end,
+			&self.fragment,
+			&self.words,
+			&self.tags,
+			&self.flags,
+			&self.homophones,
+		] {
+			hasher.update((list.len() as u64).to_be_bytes());
+			for index in list {
+				let word = self.dictionary.word(*index);
+				hasher.update((word.len() as u64).to_be_bytes());
+				hasher.update(word.as_bytes());
+			}
+		}
+		let digest = hasher.finalize();
+		digest
+			.chunks(8)
+			.take(3)
+			.map(|v| {
+				let value = u64::from_be_bytes(v.try_into().unwrap());
+				self.dictionary
+					.word((value % self.dictionary.len() as u64) as u32)
+			})
+			.collect::<Vec<&str>>()
+			.join(" ")
+	}
+	/// The dictionary indices which a 16-bit header value is written as.
+	pub(crate) fn value_words(&self, value: u16) -> impl Iterator<Item = u32> + '_ {
+		self.chunk_size()
+			.split_value(value)
+			.map(move |v| self.words[v as usize])
+	}
+}
+
+/// Options which change how armor is donned. `doff` works out which options were used
+/// from the armor itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+	/// Append a CRC32 of the data, which `doff` verifies.
+	pub checksum: bool,
+	/// The number of Reed–Solomon parity words added to every block of up to 65,535 words.
+	/// `doff` can fix up to half this many wrong words per block, or this many missing words,
+	/// which lets it recover from a run of missing messages. Zero disables error correction,
+	/// and more than 65,534 is treated as 65,534, since every block needs a word of data.
+	pub error_correction: u16,
+	/// Compress the data with DEFLATE before armoring it. The data is left alone if
+	/// compressing it doesn't make it any smaller, but it's still worth turning this off for
+	/// data which is already compressed, since nothing can be sent until it's all been tried.
+	pub compress: bool,
+	/// Encrypt the data with ChaCha20-Poly1305 under `DictMappings::key`, so reading it takes
+	/// more than working out the mappings. `doff` refuses data which has been tampered with.
+	pub encrypt: bool,
+	/// Append an HMAC-SHA256 of the data under `DictMappings::mac_key`, so `doff` can tell
+	/// that the messages weren't forged, reordered or cut short by someone who only knows the
+	/// format.
+	pub authenticate: bool,
+	/// Render each message as sentences, with capital letters, commas and periods. `doff`
+	/// ignores them either way.
+	pub natural: bool,
+	/// Write each chunk as its word or one of its homophones, picked at random, so a chunk
+	/// which is repeated isn't the same word every time. The pick is weighted by the mappings'
+	/// `frequencies`, if they have any. Dictionaries without homophones are armored as usual,
+	/// and `don_with_model` picks homophones its own way.
+	pub homophones: bool,
+	/// The parts of speech the chunks are written in, over and over, for mappings made by
+	/// `DictMappings::from_tagged`, so the armor has a rhythm like noun, verb, noun. With
+	/// other mappings, each part of speech picks a set of homophones the same way. Empty
+	/// leaves the choice to `homophones`, and anything else overrides it and the model.
+	pub rhythm: Vec<PartOfSpeech>,
+	/// Write metadata flags after the markers: whether the data was compressed or encrypted
+	/// and whether it's text after the `begin` marker, and `Flag::Final` after the marker of
+	/// the last message. `doff_with_report` lists the flags it finds. Mappings without room
+	/// for flags, and `DonWriter`, leave them out.
+	pub metadata: bool,
+	/// What kind of content the data is, which is recorded in the header for `doff_with_report`
+	/// to pass on.
+	pub content_type: Option<ContentType>,
+	/// The name, length and modification time of the file which the data came from, which
+	/// are kept in front of the data for `doff_with_report` to pass on. `doff_into` leaves
+	/// the record out, and `DoffReader` passes it on with `DoffReader::file`.
+	pub file: Option<FileInfo>,
+	/// What the character limit counts.
+	pub limit_unit: LimitUnit,
+	/// Tokens which are sprinkled between the words at random, like `lol` or emoji, so the
+	/// messages look more like chat. `doff` skips them. Fillers which are in the dictionary
+	/// or contain whitespace can't be told apart from the armor, so they're never used.
+	pub fillers: Vec<String>,
+	/// A number which every message repeats, so armor can be told apart from other armor
+	/// sent through the same channel at the same time. `doff_all` separates messages by it.
+	pub message_id: Option<u16>,
+	/// A number which every message repeats, naming the session or conversation the armor
+	/// belongs to. `filter_session` picks out the messages of one session.
+	pub session_id: Option<u32>,
+	/// What goes between the words of a message. Armor whose separator isn't whitespace has
+	/// to be doffed with `doff_with_separator`.
+	pub separator: Separator,
+	/// The date of the mappings the armor is donned with, which is recorded in the header so
+	/// `doff_dated` can pick the right mappings however long ago the armor was sent. Anyone
+	/// can read the date, even without the mappings.
+	pub date: Option<NaiveDate>,
+	/// The time of day the armor was donned, in UTC, which is recorded in the header to the
+	/// minute so whoever doffs it can put sets of armor in order and notice ones which took
+	/// a long time to arrive. Like the date, it can be read by anyone who can read the
+	/// header.
+	pub time: Option<NaiveTime>,
+	/// How the armor is split into messages.
+	pub split: SplitStrategy,
+	/// Pad every message with nulls until it's as close to the character limit as the
+	/// nulls' lengths allow, and add messages of nothing but nulls until the number of
+	/// messages is a multiple of this, so neither the messages' lengths nor their number
+	/// give away how much data there is. `doff` skips the nulls. Armor without a character
+	/// limit, mappings without nulls, and `DonWriter` aren't padded.
+	pub padding: Option<usize>,
+	/// The percentage of the words carrying data which are followed by a null picked at
+	/// random, so the positions of words in a message say less about what they mean. `doff`
+	/// throws the nulls away. Mappings without nulls get no chaff.
+	pub chaff: u8,
+	/// Put a random nonce in the header and XOR every chunk of the payload with it, so the
+	/// same data donned twice with the same mappings doesn't come out as the same words.
+	/// Anyone with the mappings can still read the armor.
+	pub nonce: bool,
+	/// XOR every chunk of the payload with a keystream drawn from the mappings' key, which
+	/// depends on the seed and date, and a nonce, which is added to the header as if `nonce`
+	/// were set, so runs of the same bytes, like the zeroes in binaries, don't come out as
+	/// runs of the same word. `doff_partial` can't recover such armor past a missing message.
+	pub keystream: bool,
+}
+
+/// How armor is split into messages. Whatever the strategy, no message is longer than the
+/// character limit unless a single word doesn't fit in a message of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitStrategy {
+	/// Fill every message up to the limit before starting the next, which usually leaves the
+	/// last message short.
+	#[default]
+	Greedy,
+	/// Use as few messages as `Greedy` does, but make them as close to the same length as
+	/// possible. `DonWriter` can't know the lengths before the data has all been written, so
+	/// it splits greedily.
+	Balanced,
+	/// Put at most this many words in each message, not counting the fragment marker,
+	/// sequence number and IDs which start it. The first message always holds the whole
+	/// header.
+	Words(usize),
+	/// Give each message a limit of its own, picked at random between this length and the
+	/// character limit, so the lengths of the messages don't give away that they're all as
+	/// long as they can be.
+	Random(usize),
+}
+
+/// What goes between the words of a message. It mustn't appear in any word of the
+/// dictionary, or the armor can't be split back into words.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Separator {
+	/// A single space.
+	#[default]
+	Space,
+	/// A line break, for channels which keep lines but collapse spaces.
+	Newline,
+	/// A hyphen, for channels which don't allow whitespace at all.
+	Hyphen,
+	/// Any other non-empty string.
+	Custom(String),
+	/// Nothing at all, for dictionaries whose every word is a single grapheme cluster, like
+	/// emoji. Doffing splits the armor at every grapheme cluster, so it can't be used with
+	/// `Options::natural`, whose punctuation would be read as words.
+	Nothing,
+}
+
+impl Separator {
+	/// The text which goes between words.
+	pub fn as_str(&self) -> &str {
+		match self {
+			Separator::Space => " ",
+			Separator::Newline => "\n",
+			Separator::Hyphen => "-",
+			Separator::Custom(separator) => separator,
+			Separator::Nothing => "",
+		}
+	}
+}
+
+/// How messages are split into words while doffing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tokenizer {
+	/// What goes between the words, besides whitespace, which always splits words.
+	pub separator: Separator,
+	/// Characters which are stripped from either end of a token which isn't in the dictionary
+	/// as written, like the quotes, periods and smart apostrophes which chat clients and
+	/// people add. The default set includes the periods and commas which natural armor has.
+	pub punctuation: Vec<char>,
+}
+
+impl Default for Tokenizer {
+	fn default() -> Tokenizer {
+		Tokenizer {
+			separator: Separator::Space,
+			punctuation: natural::DEFAULT_PUNCTUATION.to_vec(),
+		}
+	}
+}
+
+/// How forgiving doffing is of armor which isn't exactly as it was donned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodePolicy {
+	/// Refuse anything which isn't exactly as `don` wrote it, other than the separator: no
+	/// corrections, no changes of case, no punctuation, no repeated messages and no missing
+	/// end marker. Natural armor can't be read strictly, since it's punctuated.
+	Strict,
+	/// Put up with everything that can be put up with, including mistyped words, like
+	/// `doff_lenient`.
+	Lenient,
+	/// Put up with exactly the things which are allowed.
+	Custom(Tolerances),
+}
+
+impl DecodePolicy {
+	/// What the policy puts up with.
+	pub fn tolerances(&self) -> Tolerances {
+		match self {
+			DecodePolicy::Strict => Tolerances {
+				typos: false,
+				case: false,
+				punctuation: false,
+				duplicates: false,
+				missing_end: false,
+			},
+			DecodePolicy::Lenient => Tolerances {
+				typos: true,
+				..Tolerances::default()
+			},
+			DecodePolicy::Custom(tolerances) => tolerances.clone(),
+		}
+	}
+}
+
+/// The things doffing can put up with. The default is what `doff` puts up with, which is
+/// everything but mistyped words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tolerances {
+	/// Take a token which isn't in the dictionary to be the closest word with a meaning, if
+	/// one is a single character away.
+	pub typos: bool,
+	/// Read words whose case has changed, like the capitals which natural armor has.
+	pub case: bool,
+	/// Strip the tokenizer's punctuation from either end of a token.
+	pub punctuation: bool,
+	/// Leave out messages whose sequence number has already been seen, such as a message which
+	/// was pasted twice, instead of refusing the armor.
+	pub duplicates: bool,
+	/// Read armor without an end marker, as long as the header says how many messages there
+	/// are or error correction makes up for the last of them.
+	pub missing_end: bool,
+}
+
+impl Default for Tolerances {
+	fn default() -> Tolerances {
+		Tolerances {
+			typos: false,
+			case: true,
+			punctuation: true,
+			duplicates: true,
+			missing_end: true,
+		}
+	}
+}
+
+/// The ways platforms count the length of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitUnit {
+	/// UTF-8 bytes, like IRC.
+	#[default]
+	Bytes,
+	/// Unicode scalar values.
+	Chars,
+	/// Extended grapheme clusters, which is what people see as characters.
+	Graphemes,
+	/// UTF-16 code units, like JavaScript and Twitter.
+	Utf16,
+}
+
+impl LimitUnit {
+	/// The length of `text` in this unit.
+	pub fn measure(self, text: &str) -> usize {
+		use unicode_segmentation::UnicodeSegmentation;
+		match self {
+			LimitUnit::Bytes => text.len(),
+			LimitUnit::Chars => text.chars().count(),
+			LimitUnit::Graphemes => text.graphemes(true).count(),
+			LimitUnit::Utf16 => text.encode_utf16().count(),
+		}
+	}
+}
+
+/// Places armor is often posted, with their message limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+	/// Twitter, or X, which allows 280 UTF-16 code units.
+	Twitter,
+	/// A single SMS, which allows 160 characters.
+	Sms,
+	/// Discord, which allows 2000 characters.
+	Discord,
+	/// IRC, where a line is 512 bytes. The limit is 400 bytes on purpose, not 512: the line
+	/// also holds the `PRIVMSG #channel :` command, the trailing CRLF, and the sender's
+	/// hostmask, which the server puts in front when it relays the line. A message which fills
+	/// all 512 bytes would be cut short on the way to everyone else.
+	Irc,
+	/// Mastodon, which allows 500 characters.
+	Mastodon,
+}
+
+impl Platform {
+	/// The longest message the platform allows, in its `limit_unit`.
+	pub fn character_limit(self) -> usize {
+		match self {
+			Platform::Twitter => 280,
+			Platform::Sms => 160,
+			Platform::Discord => 2000,
+			Platform::Irc => 400,
+			Platform::Mastodon => 500,
+		}
+	}
+	/// How the platform counts the length of a message.
+	pub fn limit_unit(self) -> LimitUnit {
+		match self {
+			Platform::Twitter => LimitUnit::Utf16,
+			Platform::Irc => LimitUnit::Bytes,
+			Platform::Sms | Platform::Discord | Platform::Mastodon => LimitUnit::Chars,
+		}
+	}
+	/// The default options, counting lengths the way the platform does.
+	pub fn options(self) -> Options {
+		Options {
+			limit_unit: self.limit_unit(),
+			..Options::default()
+		}
 	}
 }
 
 /// Don armor. Returns a list of messages, including split headers.
-pub fn don(data: &[u8], dict: &DictMappings, character_limit: usize) -> Vec<String> {
+pub fn don<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+) -> Vec<String> {
+	don_with(data, dict, character_limit, &Options::default())
+}
+
+/// Don armor with non-default options.
+pub fn don_with<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+) -> Vec<String> {
+	don_inner(
+		data,
+		dict,
+		character_limit,
+		options,
+		None,
+		&mut rand::thread_rng(),
+	)
+}
+
+/// Don armor, drawing every random choice from `rng`. The same data, mappings and generator
+/// state always produce exactly the same messages, which is useful for tests and for
+/// pipelines which have to be reproducible.
+pub fn don_with_rng<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	rng: &mut impl rand::Rng,
+) -> Vec<String> {
+	don_inner(data, dict, character_limit, &Options::default(), None, rng)
+}
+
+/// Don armor in messages which fit on `platform`.
+pub fn don_for<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	platform: Platform,
+) -> Vec<String> {
+	don_with(data, dict, platform.character_limit(), &platform.options())
+}
+
+/// Don armor, choosing between homophones so that each word is likely to follow the one
+/// before it according to `model`. Dictionaries without homophones are armored as usual.
+pub fn don_with_model<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	model: &BigramModel,
+) -> Vec<String> {
+	don_inner(
+		data,
+		dict,
+		character_limit,
+		options,
+		Some(model),
+		&mut rand::thread_rng(),
+	)
+}
+
+/// Don armor in at most `max_messages` messages, for channels which only allow so many posts.
+/// If the armor takes more, it's donned again with `Options::compress`, in case that makes it
+/// short enough. If it still takes too many, nothing is returned but the number it takes.
+pub fn don_limited<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	max_messages: usize,
+) -> Result<Vec<String>, CawError> {
+	let mut messages = don_with(data, dict, character_limit, options);
+	if messages.len() > max_messages && !options.compress {
+		let options = Options {
+			compress: true,
+			..options.clone()
+		};
+		messages = don_with(data, dict, character_limit, &options);
+	}
+	if messages.len() > max_messages {
+		return Err(CawError::TooManyMessages {
+			needed: messages.len(),
+			allowed: max_messages,
+		});
+	}
+	Ok(messages)
+}
+
+/// Don armor into `messages`, replacing whatever was there before. The strings which are
+/// already in `messages` are written over instead of being replaced, so a server which armors
+/// lots of small payloads can keep one `Vec` around and pass it to every call, and it will
+/// soon stop allocating new messages at all.
+pub fn don_into<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	messages: &mut Vec<String>,
+) {
+	don_inner_into(
+		data,
+		dict,
+		character_limit,
+		&Options::default(),
+		None,
+		&mut rand::thread_rng(),
+		messages,
+	)
+}
+
+pub(crate) fn don_inner<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	model: Option<&BigramModel>,
+	rng: &mut impl rand::Rng,
+) -> Vec<String> {
+	let mut messages = vec![];
+	don_inner_into(
+		data,
+		dict,
+		character_limit,
+		options,
+		model,
+		rng,
+		&mut messages,
+	);
+	messages
+}
+
+fn don_inner_into<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	model: Option<&BigramModel>,
+	rng: &mut impl rand::Rng,
+	messages: &mut Vec<String>,
+) {
+	let packed = Packed::new(dict, options, data, rng);
+	don_packed(
+		dict,
+		&packed,
+		character_limit,
+		options,
+		model,
+		rng,
+		messages,
+	)
+}
+
+/// Don the same data for several recipients who each have their own mappings, returning the
+/// messages for each of them in the same order as `dicts`. The data is only packed once for
+/// each chunk size among the mappings, which saves work over armoring it for each of them in
+/// turn.
+pub fn don_multi<D: Dictionary>(
+	data: &[u8],
+	dicts: &[&DictMappings<D>],
+	character_limit: usize,
+) -> Vec<Vec<String>> {
+	let options = Options::default();
 	let mut rng = rand::thread_rng();
-	// Build the un-split list of words
-	let mut words: Vec<&str> = data
-		.par_chunks(2)
-		// Convert each byte pair to a 16-bit word
-		.map(|pair| {
-			let a = pair[0];
-			let b = *pair.get(1).unwrap_or(&0);
-			((a as u16) << 8) + (b as u16)
+	// Without encryption, authentication, a nonce or a keystream, packing only depends on the
+	// mappings' chunk size, and on whether the data is armor under them
+	let mut packed: Vec<(ChunkSize, Packed)> = vec![];
+	dicts
+		.iter()
+		.map(|dict| {
+			let size = dict.chunk_size();
+			let index = match packed.iter().position(|v| v.0 == size) {
+				Some(index) => index,
+				None => {
+					packed.push((size, Packed::new(dict, &options, data, &mut rng)));
+					packed.len() - 1
+				}
+			};
+			let nested = is_armor(data, dict);
+			let mut own = None;
+			if nested != packed[index].1.flags.contains(&Flag::Nested) {
+				let mut v = packed[index].1.clone();
+				v.flags.retain(|v| *v != Flag::Nested);
+				if nested {
+					v.flags.push(Flag::Nested);
+				}
+				own = Some(v);
+			}
+			let mut messages = vec![];
+			don_packed(
+				dict,
+				own.as_ref().unwrap_or(&packed[index].1),
+				character_limit,
+				&options,
+				None,
+				&mut rng,
+				&mut messages,
+			);
+			messages
 		})
-		// Map each 16-bit word into an index into the dictionary
-		.map(|word| dict.words[word as usize])
-		.map(|index| DICTIONARY[index as usize])
+		.collect()
+}
+
+/// How big armor for some data would be, as worked out by `estimate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodeEstimate {
+	/// The number of messages.
+	pub messages: usize,
+	/// The length of all of the messages together, counted the way the limit is.
+	pub total_chars: usize,
+	/// How many times longer the messages are than the data. It's infinite for empty data.
+	pub expansion_ratio: f64,
+}
+
+/// Work out how many messages `don` would split `data_len` bytes into, and how long they'd
+/// be, without armoring anything. The split is decided the same way as `don` decides it, but
+/// since the words depend on the data, every data word is taken to be as long as the average
+/// word for a chunk. Real armor can come out a little longer or shorter.
+pub fn estimate<D: Dictionary>(
+	data_len: usize,
+	dict: &DictMappings<D>,
+	character_limit: usize,
+) -> EncodeEstimate {
+	let options = Options::default();
+	let mut splitter = Splitter::new(dict, character_limit, &options);
+	let lens = |words: &[u32]| -> Vec<usize> {
+		words
+			.iter()
+			.map(|v| splitter.measure(dict.dictionary.word(*v)))
+			.collect()
+	};
+	let average = |lens: &[usize]| lens.iter().sum::<usize>().div_ceil(lens.len().max(1));
+	let chunk_lens = lens(&dict.words);
+	let (begin, end) = (average(&lens(&dict.begin)), average(&lens(&dict.end)));
+	let (chunk, longest) = (average(&chunk_lens), *chunk_lens.iter().max().unwrap());
+	// Only the padding depends on the data, and only on its length
+	let group = dict.chunk_size().group();
+	let mut header = Header::new(&options);
+	let mut payload_len = data_len;
+	if !data_len.is_multiple_of(group) {
+		header.padded = true;
+		payload_len += header::padding(data_len, group).len();
+	}
+	header.fragments = Some(0);
+	let encoded = header.encode(dict);
+	let count_len = 2 * dict.chunk_size().chunks_per_value();
+	splitter.append_measured(begin);
+	for (position, index) in encoded.iter().enumerate() {
+		if position >= encoded.len() - count_len {
+			splitter.append_value();
+		} else {
+			splitter.append(dict.dictionary.word(*index));
+		}
+	}
+	let chunks = dict.chunk_size().chunks_for(payload_len);
+	let mut messages = 1;
+	let mut total_chars = 0;
+	// Words are given whole lengths which add up to the exact average
+	let sum: usize = chunk_lens.iter().sum();
+	let share = |position: usize| position * sum / chunk_lens.len();
+	let data_lens = (0..chunks).map(|v| share(v + 1) - share(v));
+	for len in data_lens.chain([end]) {
+		let before = splitter.len();
+		if splitter.push_measured(len) {
+			messages += 1;
+			total_chars += before;
+		}
+	}
+	// The fragment count is split as if it were written in the longest words, but it's
+	// likelier to be written in average ones
+	total_chars += splitter.len();
+	total_chars -= count_len * (longest - chunk);
+	EncodeEstimate {
+		messages,
+		total_chars,
+		expansion_ratio: total_chars as f64 / data_len as f64,
+	}
+}
+
+/// A payload which is ready to be mapped to words.
+#[derive(Clone)]
+struct Packed {
+	header: Header,
+	/// The payload cut into chunks.
+	values: Vec<u16>,
+	/// The flags which go after the `begin` marker.
+	flags: Vec<Flag>,
+}
+
+impl Packed {
+	fn new<D: Dictionary>(
+		dict: &DictMappings<D>,
+		options: &Options,
+		data: &[u8],
+		rng: &mut impl rand::Rng,
+	) -> Packed {
+		let (header, payload) = Header::pack(dict, options, data, rng);
+		let mut mask = header.mask(dict);
+		let values = chunk::split(&payload, dict.chunk_size())
+			.into_iter()
+			.map(|v| v ^ mask.next())
+			.collect();
+		let mut flags = if options.metadata {
+			Flag::for_payload(&header, data)
+		} else {
+			vec![]
+		};
+		// Armor inside armor is always flagged, so doffing can take off both layers
+		if is_armor(data, dict) {
+			flags.push(Flag::Nested);
+		}
+		Packed {
+			header,
+			values,
+			flags,
+		}
+	}
+}
+
+/// Map a packed payload to words and split them into messages.
+fn don_packed<D: Dictionary>(
+	dict: &DictMappings<D>,
+	packed: &Packed,
+	character_limit: usize,
+	options: &Options,
+	model: Option<&BigramModel>,
+	rng: &mut impl rand::Rng,
+	messages: &mut Vec<String>,
+) {
+	let mut header = packed.header.clone();
+	// Build the un-split list of words
+	let data_words: Vec<&str> = match model {
+		_ if !options.rhythm.is_empty() => packed
+			.values
+			.iter()
+			.zip(options.rhythm.iter().cycle())
+			.map(|(value, part)| dict.dictionary.word(dict.word_in_set(*value, part.set())))
+			.collect(),
+		// Each choice depends on the one before it, so this can't be done in parallel
+		Some(model) => {
+			let mut words: Vec<&str> = vec![];
+			for value in &packed.values {
+				let index = model.choose(dict, words.last().copied(), *value, rng);
+				words.push(dict.dictionary.word(index));
+			}
+			words
+		}
+		None if options.homophones => packed
+			.values
+			.iter()
+			.map(|v| dict.dictionary.word(dict.random_word_for(*v, rng)))
+			.collect(),
+		// Map each chunk into an index into the dictionary
+		None => simd::gather(&packed.values, &dict.words)
+			.par_iter()
+			.map(|index| dict.dictionary.word(*index))
+			.collect(),
+	};
+	let fillers = natural::usable_fillers(dict, &options.fillers, options.separator.as_str());
+	let mut words: Vec<&str> = vec![];
+	for word in data_words {
+		words.push(word);
+		words.extend(padding::chaff(dict, options.chaff, rng));
+		words.extend(natural::filler(&fillers, rng).copied());
+	}
+	// Write begin, the flags, the header, and end. The fragment count comes last in the
+	// header, and isn't known until the words have been split.
+	header.fragments = Some(0);
+	let encoded = header.encode(dict);
+	let flags: Vec<u32> = packed
+		.flags
+		.iter()
+		.filter_map(|v| dict.flags.get(*v as usize).copied())
 		.collect();
-	// Write begin and end
-	words.insert(
-		0,
-		DICTIONARY[*dict.begin.choose(&mut rng).unwrap() as usize],
+	let header_len = 1 + flags.len() + encoded.len();
+	words.splice(
+		0..0,
+		std::iter::once(*dict.begin.choose(rng).unwrap())
+			.chain(flags)
+			.chain(encoded)
+			.map(|index| dict.dictionary.word(index)),
 	);
-	words.push(DICTIONARY[*dict.end.choose(&mut rng).unwrap() as usize]);
-	// The positions of each split.
-	let mut splits: Vec<usize> = vec![0];
-	let mut count = 0usize;
-	let fragment_len = dict
-		.fragment
-		.iter()
-		.map(|v| DICTIONARY[*v as usize].len())
-		.max()
-		.unwrap_or(0);
-	for (index, word) in words.iter().enumerate() {
-		if count != 0 {
-			count += 1;
+	// The last message's flag goes after its marker, which isn't known until it's been split
+	let final_flag = match options.metadata {
+		true => dict
+			.flags
+			.get(Flag::Final as usize)
+			.map(|v| dict.dictionary.word(*v)),
+		false => None,
+	};
+	words.push(dict.dictionary.word(*dict.end.choose(rng).unwrap()));
+	let count_len = 2 * dict.chunk_size().chunks_per_value();
+	// The positions of each split, if the messages are at most `character_limit` long.
+	// Only random limits draw anything, so other armor comes out as it always has
+	let seed: u64 = match options.split {
+		SplitStrategy::Random(_) => rng.gen(),
+		_ => 0,
+	};
+	let split = |character_limit: usize| -> Vec<usize> {
+		let mut splits: Vec<usize> = vec![0];
+		let mut splitter = Splitter::new(dict, character_limit, options);
+		splitter.randomize(&mut Pcg64::seed_from_u64(seed));
+		for (index, word) in words.iter().enumerate() {
+			// The header is only read from the first message, so it can't be split
+			if (header_len - count_len..header_len).contains(&index) {
+				splitter.append_value();
+			} else if index < header_len {
+				splitter.append(word);
+			} else if let Some(flag) = final_flag.filter(|_| index + 1 == words.len()) {
+				// Whichever message the end marker lands in has room for the flag too
+				if splitter.push(flag) {
+					splits.push(index);
+					splitter.push(word);
+				} else if splitter.push(word) {
+					splits.push(index);
+					splitter.push(flag);
+				}
+			} else if splitter.push(word) {
+				splits.push(index);
+			}
 		}
-		count += word.len();
-		if count + fragment_len > character_limit {
-			splits.push(index);
-			count = word.len();
+		splits.push(words.len());
+		splits
+	};
+	let mut splits = split(character_limit);
+	if options.split == SplitStrategy::Balanced && splits.len() > 2 {
+		// Find the lowest limit which doesn't take any more messages
+		let (mut low, mut high) = (1, character_limit);
+		while low < high {
+			let middle = low + (high - low) / 2;
+			if split(middle).len() > splits.len() {
+				low = middle + 1;
+			} else {
+				high = middle;
+			}
+		}
+		splits = split(high);
+	}
+	// The number of messages which carry any of the armor
+	let carrying = splits.len() - 1;
+	let padder = match options.padding {
+		Some(block) if !dict.nulls.is_empty() && character_limit != usize::MAX => {
+			let padded = carrying.next_multiple_of(block.max(1));
+			splits.resize(padded + 1, words.len());
+			Some(Padder::new(dict, character_limit, options))
 		}
+		_ => None,
+	};
+	let last = u32::try_from(splits.len() - 2).expect("too many fragments");
+	let count = dict
+		.value_words((last >> 16) as u16)
+		.chain(dict.value_words(last as u16));
+	for (word, index) in words[header_len - count_len..header_len]
+		.iter_mut()
+		.zip(count)
+	{
+		*word = dict.dictionary.word(index);
 	}
-	splits.push(words.len());
+	// Each message gets a generator of its own, so they can be built in any order
+	let seeds: Vec<u64> = (1..splits.len()).map(|_| rng.gen()).collect();
 	// Copy each range into its own message.
-	splits
-		.par_windows(2)
+	messages.truncate(seeds.len());
+	messages.resize_with(seeds.len(), String::new);
+	messages
+		.par_iter_mut()
 		.enumerate()
-		.map(|(index, range)| {
-			let start = range[0];
-			let end = range[1];
-			let mut rng = rand::thread_rng();
+		.for_each(|(index, message)| {
+			let start = splits[index];
+			let end = splits[index + 1];
+			let mut rng = Pcg64::seed_from_u64(seeds[index]);
 			let mut result: Vec<&str> = vec![];
 			if index != 0 {
-				result.push(DICTIONARY[*dict.fragment.choose(&mut rng).unwrap() as usize]);
-				result.push(DICTIONARY[dict.words[index] as usize]);
-			}
-			result.append(
-				&mut words
-					.iter()
-					.skip(start)
-					.take(end - start)
-					.copied()
-					.collect(),
-			);
-			result.join(" ")
-		})
+				result.extend(fragment_header(dict, index, Ids::new(options), &mut rng));
+			}
+			result.extend_from_slice(&words[start..end]);
+			if let Some(flag) = final_flag.filter(|_| index + 1 == carrying) {
+				result.insert(1, flag);
+			}
+			if let Some(padder) = &padder {
+				padder.pad(&mut result, &mut rng);
+			}
+			message.clear();
+			let separator = options.separator.as_str();
+			if options.natural {
+				natural::render_into(dict, &result, separator, &mut rng, message);
+			} else {
+				for (position, word) in result.iter().enumerate() {
+					if position != 0 {
+						message.push_str(separator);
+					}
+					message.push_str(word);
+				}
+			}
+		});
+}
+
+/// Decides where messages are split, one word at a time.
+pub(crate) struct Splitter<'a, D: Dictionary> {
+	dict: &'a DictMappings<D>,
+	/// The length of the current message's words so far.
+	count: usize,
+	/// The position of the current message.
+	index: usize,
+	/// How many words the current message has so far.
+	words: usize,
+	/// The most words a message can have.
+	max_words: usize,
+	/// The length of the longest fragment marker.
+	fragment_len: usize,
+	character_limit: usize,
+	/// The limit of the current message, which is only below `character_limit` when the
+	/// limits are random.
+	limit: usize,
+	/// The shortest random limit, and the generator the limits are picked with, if they're
+	/// random.
+	random: Option<(usize, Pcg64)>,
+	unit: LimitUnit,
+	/// How much longer each word might get once the message is rendered.
+	word_overhead: usize,
+	/// The length of the separator between words.
+	separator_len: usize,
+	/// The IDs which every fragment repeats.
+	ids: Ids,
+}
+
+impl<'a, D: Dictionary> Splitter<'a, D> {
+	pub(crate) fn new(
+		dict: &'a DictMappings<D>,
+		character_limit: usize,
+		options: &Options,
+	) -> Splitter<'a, D> {
+		let word_overhead = if options.natural { 1 } else { 0 };
+		let fragment_len = dict
+			.fragment
+			.iter()
+			.map(|v| options.limit_unit.measure(dict.dictionary.word(*v)) + word_overhead)
+			.max()
+			.unwrap_or(0);
+		Splitter {
+			dict,
+			count: 0,
+			index: 0,
+			words: 0,
+			max_words: match options.split {
+				SplitStrategy::Words(words) => words.max(1),
+				_ => usize::MAX,
+			},
+			fragment_len,
+			character_limit,
+			limit: character_limit,
+			random: match options.split {
+				SplitStrategy::Random(shortest) => {
+					Some((shortest.min(character_limit), Pcg64::seed_from_u64(0)))
+				}
+				_ => None,
+			},
+			unit: options.limit_unit,
+			word_overhead,
+			separator_len: options.limit_unit.measure(options.separator.as_str()),
+			ids: Ids::new(options),
+		}
+	}
+	/// Pick the limits with a generator seeded from `rng`, if they're random, starting with
+	/// the current message's.
+	pub(crate) fn randomize(&mut self, rng: &mut impl rand::Rng) {
+		if let Some((_, random)) = &mut self.random {
+			*random = Pcg64::seed_from_u64(rng.gen());
+			self.next_limit();
+		}
+	}
+	/// Pick the next message's limit, if the limits are random.
+	fn next_limit(&mut self) {
+		if let Some((shortest, random)) = &mut self.random {
+			self.limit = rand::Rng::gen_range(random, *shortest..=self.character_limit);
+		}
+	}
+	fn measure(&self, word: &str) -> usize {
+		self.unit.measure(word) + self.word_overhead
+	}
+	/// The length of the current message so far.
+	fn len(&self) -> usize {
+		self.header_len() + self.count
+	}
+	/// The length of the fragment marker, sequence number and IDs before the current
+	/// message's words, including the separator after them.
+	fn header_len(&self) -> usize {
+		if self.index == 0 {
+			return 0;
+		}
+		let fields: usize = fragment_fields(self.dict, self.index, self.ids)
+			.into_iter()
+			.map(|v| self.separator_len + self.measure(self.dict.dictionary.word(v)))
+			.sum();
+		self.fragment_len + fields + self.separator_len
+	}
+	/// Account for a word which has to stay in the current message.
+	pub(crate) fn append(&mut self, word: &str) {
+		self.append_measured(self.measure(word))
+	}
+	/// Account for a word which has to stay in the current message given its measured length,
+	/// like `append`.
+	fn append_measured(&mut self, len: usize) {
+		self.words += 1;
+		if self.count == 0 {
+			self.count = len;
+		} else {
+			self.count += self.separator_len + len;
+		}
+	}
+	/// Account for a chunk's word which isn't known yet, by assuming it's the longest one.
+	pub(crate) fn append_value(&mut self) {
+		let longest = self
+			.dict
+			.words
+			.iter()
+			.map(|v| self.unit.measure(self.dict.dictionary.word(*v)))
+			.max()
+			.unwrap_or(0);
+		let separator_len = if self.count == 0 {
+			0
+		} else {
+			self.separator_len
+		};
+		self.count += separator_len + longest + self.word_overhead;
+		self.words += 1;
+	}
+	/// Account for the next word, returning whether it has to start a new message. A word
+	/// which doesn't fit in a message of its own still gets one.
+	pub(crate) fn push(&mut self, word: &str) -> bool {
+		self.push_measured(self.measure(word))
+	}
+	/// Account for the next word given its measured length, like `push`.
+	fn push_measured(&mut self, len: usize) -> bool {
+		if self.count == 0 {
+			self.count = len;
+			self.words = 1;
+			return false;
+		}
+		self.count += self.separator_len + len;
+		self.words += 1;
+		if self.header_len() + self.count > self.limit || self.words > self.max_words {
+			self.index += 1;
+			self.count = len;
+			self.words = 1;
+			self.next_limit();
+			return true;
+		}
+		false
+	}
+}
+
+/// The dictionary indices which follow the `fragment` marker of the message at `index`.
+/// Sequence numbers which don't fit in 16 bits are written as a sequence tag followed by
+/// their high and low halves, and the IDs come after the sequence number.
+fn fragment_fields<D: Dictionary>(dict: &DictMappings<D>, index: usize, ids: Ids) -> Vec<u32> {
+	let mut fields: Vec<u32> = match u16::try_from(index) {
+		Ok(sequence) => dict.value_words(sequence).collect(),
+		Err(_) => {
+			let sequence = u32::try_from(index).expect("too many fragments");
+			std::iter::once(dict.tags[Tag::Sequence as usize])
+				.chain(dict.value_words((sequence >> 16) as u16))
+				.chain(dict.value_words(sequence as u16))
+				.collect()
+		}
+	};
+	fields.extend(ids.encode(dict));
+	fields
+}
+
+/// The words which start every message other than the first.
+pub(crate) fn fragment_header<'a, D: Dictionary>(
+	dict: &'a DictMappings<D>,
+	index: usize,
+	ids: Ids,
+	rng: &mut impl rand::Rng,
+) -> Vec<&'a str> {
+	std::iter::once(*dict.fragment.choose(rng).unwrap())
+		.chain(fragment_fields(dict, index, ids))
+		.map(|v| dict.dictionary.word(v))
+		.collect()
+}
+
+/// Doff armor. Returns the recovered bytes, or the first problem found in the messages.
+pub fn doff<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+) -> Result<Vec<u8>, CawError> {
+	doff_inner(
+		messages,
+		dict,
+		&Tolerances::default(),
+		&Tokenizer::default(),
+	)
+	.map(|(data, _)| data)
+}
+
+/// Doff armor whose words are separated by something other than spaces, such as armor
+/// donned with `Options::separator`.
+pub fn doff_with_separator<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	separator: &Separator,
+) -> Result<Vec<u8>, CawError> {
+	let tokenizer = Tokenizer {
+		separator: separator.clone(),
+		..Tokenizer::default()
+	};
+	doff_with_tokenizer(messages, dict, &tokenizer)
+}
+
+/// Doff armor, splitting messages into words and stripping punctuation from them with
+/// `tokenizer`.
+pub fn doff_with_tokenizer<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	tokenizer: &Tokenizer,
+) -> Result<Vec<u8>, CawError> {
+	doff_inner(messages, dict, &Tolerances::default(), tokenizer).map(|(data, _)| data)
+}
+
+/// Doff armor, returning a report of everything which had to be worked around along with the
+/// recovered bytes.
+pub fn doff_with_report<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+) -> Result<(Vec<u8>, Report), CawError> {
+	doff_inner(
+		messages,
+		dict,
+		&Tolerances::default(),
+		&Tokenizer::default(),
+	)
+}
+
+/// Doff armor which might have been retyped by hand. A token which isn't in the dictionary
+/// is taken to be the closest word with a meaning, if one is a single character away, and
+/// every correction is reported along with the data. The armor should have a checksum, since
+/// a correction might be a different word than the one which was mistyped.
+pub fn doff_lenient<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+) -> Result<(Vec<u8>, Report), CawError> {
+	doff_with_policy(messages, dict, &DecodePolicy::Lenient)
+}
+
+/// Doff armor, putting up with as much or as little as `policy` allows, and returning a
+/// report of everything which had to be worked around along with the recovered bytes.
+pub fn doff_with_policy<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	policy: &DecodePolicy,
+) -> Result<(Vec<u8>, Report), CawError> {
+	doff_inner(messages, dict, &policy.tolerances(), &Tokenizer::default())
+}
+
+/// Doff armor only if it was donned at most `max_age` days ago, according to the date in its
+/// header, so old messages can't be replayed into a conversation. Armor without a date is
+/// refused too, since nobody can tell how old it is. The armor has to be donned with
+/// `Options::date`, and should be authenticated, since otherwise anyone can change the date.
+pub fn doff_fresh<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	max_age: u32,
+) -> Result<Vec<u8>, CawError> {
+	let (data, report) = doff_with_report(messages, dict)?;
+	check_age(&report, max_age)?;
+	Ok(data)
+}
+
+/// Refuse armor whose report doesn't have a date, or whose date is more than `max_age` days
+/// before today.
+pub(crate) fn check_age(report: &Report, max_age: u32) -> Result<(), CawError> {
+	match report.date {
+		Some(date) if (today() - date).num_days() <= max_age as i64 => Ok(()),
+		date => Err(CawError::Stale { date }),
+	}
+}
+
+/// Everything which had to be worked around while doffing armor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+	/// The tokens which were corrected. Only lenient doffing corrects anything.
+	pub corrections: Vec<Correction>,
+	/// The positions in the input of messages which were left out because an earlier message
+	/// had the same sequence number, such as a message which was pasted twice.
+	pub duplicates: Vec<usize>,
+	/// The metadata flags which any of the messages carried, in order, without repeats.
+	/// Flags this version of the library doesn't know are left out.
+	pub flags: Vec<Flag>,
+	/// The kind of content the header says the data is, if it says.
+	pub content_type: Option<ContentType>,
+	/// The file the data came from, if the armor kept its name and timestamp.
+	pub file: Option<FileInfo>,
+	/// The date of the mappings the armor was donned with, if its header says. It's the
+	/// latest date on or before today which the header's date could mean.
+	pub date: Option<NaiveDate>,
+	/// The time of day the armor was donned, to the minute in UTC, if its header says.
+	pub time: Option<NaiveTime>,
+}
+
+/// Doff several armored messages whose fragments were mixed together, such as two files
+/// sent through the same chat at once. Messages are grouped by the message ID in their
+/// headers, and each group is doffed on its own. Messages without an ID are grouped under
+/// `None`.
+///
+/// A message which can't be read at all is an error, since nobody knows which group it
+/// belongs to. Anything else only affects the result for its own group.
+pub fn doff_all<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+) -> Result<Payloads, CawError> {
+	let mut groups: BTreeMap<Option<u16>, Vec<Decoded>> = BTreeMap::new();
+	let tolerances = Tolerances::default();
+	for decoded in doff_messages(messages, dict, &tolerances, &Tokenizer::default())? {
+		groups
+			.entry(decoded.ids.message_id)
+			.or_default()
+			.push(decoded);
+	}
+	Ok(groups
+		.into_iter()
+		.map(|(id, decoded)| (id, assemble(dict, decoded).map(|(data, _)| data)))
+		.collect())
+}
+
+/// The session ID of a single message, or `None` if its armor doesn't have one.
+pub fn session_id<D: Dictionary>(
+	message: &str,
+	dict: &DictMappings<D>,
+) -> Result<Option<u32>, CawError> {
+	doff_message(
+		0,
+		message,
+		dict,
+		&Tolerances::default(),
+		&Tokenizer::default(),
+	)
+	.map(|v| v.ids.session_id)
+}
+
+/// The messages which belong to `session`, in the order they were given, so
+/// they can be doffed without the rest. Messages which can't be read are left out, since
+/// nobody can tell which session they belong to.
+pub fn filter_session<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	session: u32,
+) -> Vec<String> {
+	messages
+		.par_iter()
+		.filter(|v| session_id(v, dict) == Ok(Some(session)))
+		.cloned()
 		.collect()
 }
 
-/// Doff armor.
-pub fn doff(messages: &[String], dict: &DictMappings) -> Vec<u8> {
-	let indices: Vec<Vec<usize>> = messages
+/// The data recovered by `doff_all` from each group of messages, by message ID.
+pub type Payloads = BTreeMap<Option<u16>, Result<Vec<u8>, CawError>>;
+
+pub(crate) fn doff_inner<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	tolerances: &Tolerances,
+	tokenizer: &Tokenizer,
+) -> Result<(Vec<u8>, Report), CawError> {
+	let decoded = doff_messages(messages, dict, tolerances, tokenizer)?;
+	if decoded.iter().any(|v| v.ids != decoded[0].ids) {
+		return Err(CawError::MixedMessages);
+	}
+	if !tolerances.duplicates {
+		let mut seen = std::collections::BTreeSet::new();
+		if let Some(duplicate) = decoded.iter().find(|v| !seen.insert(v.sequence)) {
+			return Err(CawError::DuplicateMessage {
+				message: duplicate.message,
+			});
+		}
+	}
+	if !tolerances.missing_end && !decoded.iter().any(|v| v.last) {
+		return Err(CawError::MissingEnd);
+	}
+	let fillers = decoded
+		.iter()
+		.find_map(|v| v.header.as_ref())
+		.is_some_and(|v| v.fillers);
+	// Fillers can't be told apart from typos, so they're never corrected
+	if fillers && tolerances.typos && decoded.iter().any(|v| !v.corrections.is_empty()) {
+		let tolerances = Tolerances {
+			typos: false,
+			..tolerances.clone()
+		};
+		return doff_inner(messages, dict, &tolerances, tokenizer);
+	}
+	let (data, report) = assemble(dict, decoded)?;
+	if report.flags.contains(&Flag::Nested) {
+		if let Some(messages) = armor_lines(&data) {
+			let (data, _) = doff_inner(&messages, dict, tolerances, tokenizer)?;
+			return Ok((data, report));
+		}
+	}
+	Ok((data, report))
+}
+
+/// The lines of `data` which aren't blank, if it's text with any.
+fn armor_lines(data: &[u8]) -> Option<Vec<String>> {
+	let lines: Vec<String> = std::str::from_utf8(data)
+		.ok()?
+		.lines()
+		.filter(|v| !v.trim().is_empty())
+		.map(String::from)
+		.collect();
+	(!lines.is_empty()).then_some(lines)
+}
+
+/// Whether `data` is itself armor under `dict`, with one message to a line, which is what
+/// armoring something which was already armored looks like. Such data is twice as long once
+/// it's armored again, so it's worth warning about. `don`, `don_multi` and `don_iter` add
+/// `Flag::Nested` when it happens, whatever the options, and doffing then takes off both
+/// layers at once. `DonWriter` and `don_read` can't, since the first message goes out before
+/// they've seen all of the data.
+pub fn is_armor<D: Dictionary>(data: &[u8], dict: &DictMappings<D>) -> bool {
+	// Most data can be ruled out by the first word of its first line which isn't blank,
+	// without reading the rest
+	let Some(line) = data
+		.split(|v| *v == b'\n')
+		.find(|v| v.iter().any(|v| !v.is_ascii_whitespace()))
+		.and_then(|v| std::str::from_utf8(v).ok())
+	else {
+		return false;
+	};
+	let tokenizer = Tokenizer::default();
+	let first = natural::tokens(line.trim_end(), tokenizer.separator.as_str())
+		.next()
+		.and_then(|v| natural::lookup(dict, v, &tokenizer.punctuation));
+	if !first.is_some_and(|v| dict.begin.contains(&v) || dict.fragment.contains(&v)) {
+		return false;
+	}
+	armor_lines(data).is_some_and(|messages| doff(&messages, dict).is_ok())
+}
+
+/// Doff the armor of every message on its own.
+pub(crate) fn doff_messages<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	tolerances: &Tolerances,
+	tokenizer: &Tokenizer,
+) -> Result<Vec<Decoded>, CawError> {
+	messages
 		.par_iter()
-		.map(|v| {
-			v.split(' ')
-				.map(|v| {
-					DICTIONARY
-						.iter()
-						.enumerate()
-						.filter(|(_, w)| **w == v)
-						.map(|(n, _)| n)
-						.next()
+		.enumerate()
+		.map(|(message, v)| doff_message(message, v, dict, tolerances, tokenizer))
+		.collect()
+}
+
+/// Put the messages of one armor back together, recovering the data.
+pub(crate) fn assemble<D: Dictionary>(
+	dict: &DictMappings<D>,
+	mut decoded: Vec<Decoded>,
+) -> Result<(Vec<u8>, Report), CawError> {
+	let header = decoded
+		.iter()
+		.find_map(|v| v.header.clone())
+		.unwrap_or_default();
+	if !header.fillers {
+		if let Some(e) = decoded.iter_mut().find_map(|v| v.skipped.take()) {
+			return Err(e);
+		}
+	}
+	let corrections = decoded
+		.iter_mut()
+		.flat_map(|v| std::mem::take(&mut v.corrections))
+		.collect();
+	// The sort is stable, so the first copy of a message is the one which is kept
+	decoded.sort_by_key(|v| v.sequence);
+	let mut duplicates = vec![];
+	decoded.dedup_by(|v, kept| {
+		let duplicate = v.sequence == kept.sequence;
+		if duplicate {
+			duplicates.push(v.message);
+		}
+		duplicate
+	});
+	duplicates.sort_unstable();
+	let mut flags: Vec<Flag> = decoded.iter().flat_map(|v| v.flags.clone()).collect();
+	flags.sort_unstable();
+	flags.dedup();
+	let data = header.unpack(dict, header.reassemble(dict, decoded)?)?;
+	let (file, data) = match header.file {
+		true => FileInfo::split(data).map(|(file, data)| (Some(file), data))?,
+		false => (None, data),
+	};
+	Ok((
+		data,
+		Report {
+			corrections,
+			duplicates,
+			flags,
+			content_type: header.content_type,
+			file,
+			date: header.date.map(|v| header::date_from_value(v, today())),
+			time: header.time.and_then(header::time_from_value),
+		},
+	))
+}
+
+/// Doff armor which might have been donned on a nearby date, such as just before midnight
+/// UTC. The mappings for today are tried first, then the days on either side of it, each up
+/// to `days` away. Returns the recovered bytes along with the date whose mappings read them.
+///
+/// If no date works, the error is the one from today's mappings.
+pub fn doff_with_window(
+	messages: &[String],
+	seed: u128,
+	days: u32,
+) -> Result<(Vec<u8>, NaiveDate), CawError> {
+	let today = today();
+	let mut first_error = None;
+	for offset in std::iter::once(0).chain((1..=days as i64).flat_map(|v| [-v, v])) {
+		let date = today + chrono::Duration::days(offset);
+		match doff(messages, &DictMappings::from_seed(seed, &date)) {
+			Ok(data) => return Ok((data, date)),
+			Err(e) => {
+				first_error.get_or_insert(e);
+			}
+		}
+	}
+	Err(first_error.unwrap())
+}
+
+/// Doff armor which was donned with `Options::date` under the English mappings for `seed`,
+/// using the date in the header to pick the mappings instead of trying every date. Dates
+/// from the last 179 years can be told apart. Returns the recovered bytes along with the date
+/// whose mappings read them.
+///
+/// If the armor doesn't have a date, today's mappings are used, and the error is the one
+/// from today's mappings.
+pub fn doff_dated(messages: &[String], seed: u128) -> Result<(Vec<u8>, NaiveDate), CawError> {
+	let today = today();
+	let mut tried = vec![];
+	// The date's word follows begin, any flags, the version's tag and value, and the date's
+	// tag. The date is the only word which doesn't depend on the mappings, so each word where
+	// it could be gives a guess, and the guess is kept if its mappings find a begin marker
+	// and a header with that date. The fewest flags are tried first.
+	let words: Vec<Vec<Option<u32>>> = messages
+		.iter()
+		.map(|message| {
+			natural::tokens(message, " ")
+				.take(5 + FLAG_COUNT)
+				.map(|word| {
+					Language::English
+						.index_of_any_case(word.trim_matches(natural::DEFAULT_PUNCTUATION))
 				})
-				.flatten()
 				.collect()
 		})
 		.collect();
-	let mut numbered_data: Vec<(u16, Vec<usize>)> = indices
-		.par_iter()
-		.map(|v| {
-			if dict.begin.contains(&(v[0] as u32)) {
-				(0, v[1..].to_vec())
+	for position in 4..5 + FLAG_COUNT {
+		for words in &words {
+			let Some(words) = words
+				.get(..=position)
+				.and_then(|v| v.iter().copied().collect::<Option<Vec<u32>>>())
+			else {
+				continue;
+			};
+			let Ok(value) = u16::try_from(words[position]) else {
+				continue;
+			};
+			let date = header::date_from_value(value, today);
+			if tried.contains(&date) {
+				continue;
+			}
+			tried.push(date);
+			let dict = DictMappings::from_seed(seed, &date);
+			if header::first_message_date(&dict, &words) == Some(value) {
+				return doff(messages, &dict).map(|data| (data, date));
+			}
+		}
+	}
+	doff(messages, &DictMappings::from_seed(seed, &today)).map(|data| (data, today))
+}
+
+/// A single message with its armor removed.
+pub(crate) struct Decoded {
+	/// The position of the message in the input.
+	pub(crate) message: usize,
+	/// The position of the message in the order of the armor.
+	pub(crate) sequence: u32,
+	/// The IDs of the armor which the message belongs to.
+	pub(crate) ids: Ids,
+	/// The chunks carried by the message.
+	pub(crate) data: Vec<u16>,
+	/// Whether the message carried the end marker.
+	pub(crate) last: bool,
+	/// The flags which followed the message's marker.
+	pub(crate) flags: Vec<Flag>,
+	/// The header, if this is the first message.
+	pub(crate) header: Option<Header>,
+	/// The tokens which had to be corrected to read the message.
+	pub(crate) corrections: Vec<Correction>,
+	/// The error for the first token which wasn't in the dictionary, which was skipped in case
+	/// the armor has fillers.
+	pub(crate) skipped: Option<CawError>,
+}
+
+/// Doff the armor of a single message, which was at position `message` in the input and is
+/// split into words by `tokenizer`, putting up with what `tolerances` allows. Mistyped words
+/// are only corrected if it allows typos. Any other token which isn't in the dictionary is
+/// left out, and only becomes an error if the armor turns out not to have fillers, or the
+/// rest of the message can't be read without it.
+pub(crate) fn doff_message<D: Dictionary>(
+	message: usize,
+	text: &str,
+	dict: &DictMappings<D>,
+	tolerances: &Tolerances,
+	tokenizer: &Tokenizer,
+) -> Result<Decoded, CawError> {
+	let mut corrections = vec![];
+	let mut skipped = None;
+	let punctuation: &[char] = match tolerances.punctuation {
+		true => &tokenizer.punctuation,
+		false => &[],
+	};
+	let indices: Vec<u32> = natural::tokens(text, tokenizer.separator.as_str())
+		.enumerate()
+		.filter_map(|(position, word)| {
+			let index = match tolerances.case {
+				true => natural::lookup(dict, word, punctuation),
+				false => natural::lookup_exact(dict, word, punctuation),
+			};
+			if index.is_some() {
+				return index;
+			}
+			let nearest = if tolerances.typos {
+				typo::nearest(dict, word, punctuation)
 			} else {
-				assert!(dict.fragment.contains(&(v[0] as u32)));
-				let index: u16 = dict.reverse_lookup(v[1] as u32).unwrap();
-				(index, v[2..].to_vec())
+				None
+			};
+			match nearest {
+				Some((index, candidates)) => {
+					corrections.push(Correction {
+						message,
+						position,
+						typed: word.to_string(),
+						corrected: dict.dictionary.word(index).to_string(),
+						candidates,
+					});
+					Some(index)
+				}
+				None => {
+					skipped.get_or_insert_with(|| CawError::UnknownWord {
+						message,
+						word: word.to_string(),
+					});
+					None
+				}
 			}
 		})
 		.collect();
-	numbered_data.sort_by(|(a, _), (b, _)| a.cmp(b));
-	let binary_data: Vec<u8> = numbered_data
-		.par_iter()
-		.flat_map(|(_, words)| {
-			words
-				.iter()
-				.map(|v| dict.reverse_lookup(*v as u32))
-				.flatten()
-				.flat_map(|v| vec![(v >> 8) as u8, ((v << 8) >> 8) as u8])
-				.collect::<Vec<u8>>()
-		})
-		.collect();
-	binary_data
+	// A token which was left out is the likeliest cause of anything else going wrong
+	let mut decoded =
+		read_message(message, &indices, dict).map_err(|e| skipped.clone().unwrap_or(e))?;
+	decoded.corrections = corrections;
+	decoded.skipped = skipped;
+	Ok(decoded)
+}
+
+/// Read the markers, header and data of a single message from its dictionary indices.
+fn read_message<D: Dictionary>(
+	message: usize,
+	indices: &[u32],
+	dict: &DictMappings<D>,
+) -> Result<Decoded, CawError> {
+	let unmapped = |index: u32| CawError::UnmappedWord {
+		message,
+		word: dict.dictionary.word(index).to_string(),
+	};
+	// Sequence numbers are written in 16-bit values, which might take more than one word
+	let value = |words: &[u32]| -> Result<u16, CawError> {
+		let chunks = words
+			.iter()
+			.map(|v| dict.reverse_lookup(*v).ok_or_else(|| unmapped(*v)))
+			.collect::<Result<Vec<u16>, CawError>>()?;
+		dict.chunk_size()
+			.join_value(&chunks)
+			.ok_or_else(|| unmapped(words[0]))
+	};
+	let per_value = dict.chunk_size().chunks_per_value();
+	let (flags, after_marker) = Flag::parse(dict, indices.get(1..).unwrap_or_default());
+	let (sequence, ids, header, words) = match indices.first() {
+		Some(first) if dict.begin.contains(first) => {
+			let (header, rest) = Header::parse(dict, message, after_marker)?;
+			(0, header.ids, Some(header), rest)
+		}
+		Some(first) if dict.fragment.contains(first) => {
+			let sequence_tag = dict.tags[Tag::Sequence as usize];
+			let (sequence, rest) = match after_marker.split_first() {
+				Some((tag, rest)) if *tag == sequence_tag => {
+					if rest.len() < 2 * per_value {
+						return Err(CawError::MissingSequence { message });
+					}
+					let (high, rest) = rest.split_at(per_value);
+					let (low, rest) = rest.split_at(per_value);
+					(((value(high)? as u32) << 16) + value(low)? as u32, rest)
+				}
+				_ if after_marker.len() < per_value => {
+					return Err(CawError::MissingSequence { message })
+				}
+				_ => {
+					let (sequence, rest) = after_marker.split_at(per_value);
+					(value(sequence)? as u32, rest)
+				}
+			};
+			let (ids, rest) = Ids::parse(dict, message, rest)?;
+			(sequence, ids, None, rest)
+		}
+		_ => return Err(CawError::MissingMarker { message }),
+	};
+	let data = words
+		.iter()
+		// The end marker and nulls carry no data
+		.filter(|v| !dict.end.contains(v) && !dict.nulls.contains(v))
+		.map(|v| dict.reverse_lookup(*v).ok_or_else(|| unmapped(*v)))
+		.collect::<Result<Vec<u16>, CawError>>()?;
+	Ok(Decoded {
+		message,
+		sequence,
+		ids,
+		data,
+		last: words.iter().any(|v| dict.end.contains(v)),
+		flags,
+		header,
+		corrections: vec![],
+		skipped: None,
+	})
 }
 
 #[cfg(test)]
 mod tests {
+	// Most of the tests need the English list, and so do the things they use
+	#![cfg_attr(feature = "no-embedded-dict", allow(unused_imports, dead_code))]
 	use chrono::Utc;
 
-	use crate::DictMappings;
+	use crate::{today, CawError, DictMappings, Dictionary, Options};
 
 	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
 	fn reversibility() {
-		let dict = DictMappings::from_seed(69, &Utc::now().date());
+		let dict = DictMappings::from_seed(69, &today());
 		let test_data = "This is a very cool test string 😎".as_bytes();
 		let resultant_data = super::don(test_data, &dict, 50);
-		let doffed = super::doff(&resultant_data, &dict);
+		let doffed = super::doff(&resultant_data, &dict).unwrap();
 		assert_eq!(test_data, doffed);
 	}
 	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn homophones() {
+		use std::collections::HashSet;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = [0x42; 128];
+		let options = Options {
+			homophones: true,
+			..Options::default()
+		};
+		let distinct = |messages: &[String]| {
+			messages
+				.iter()
+				.flat_map(|v| v.split(' '))
+				.filter(|v| {
+					dict.dictionary
+						.index_of(v)
+						.and_then(|v| dict.reverse_lookup(v))
+						== Some(0x4242)
+				})
+				.collect::<HashSet<&str>>()
+				.len()
+		};
+		let messages = super::don_with(&test_data, &dict, 100, &options);
+		assert_eq!(distinct(&messages), 5);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		let mut messages = vec![];
+		super::don_read(&test_data[..], &dict, 100, &options, |v| {
+			messages.push(v);
+			Ok(())
+		})
+		.unwrap();
+		assert_eq!(distinct(&messages), 5);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		assert_eq!(distinct(&super::don(&test_data, &dict, 100)), 1);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn reproducible() {
+		use rand::SeedableRng;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "The same every time, down to the markers".as_bytes();
+		let armor = |seed| {
+			super::don_with_rng(
+				test_data,
+				&dict,
+				40,
+				&mut rand_pcg::Pcg64::seed_from_u64(seed),
+			)
+		};
+		let messages = armor(1);
+		assert!(messages.len() > 1);
+		assert_eq!(messages, armor(1));
+		assert_ne!(messages, armor(2));
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn limited() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Only five posts an hour. ".repeat(8);
+		let test_data = test_data.as_bytes();
+		let options = Options::default();
+		let needed = super::don(test_data, &dict, 100).len();
+		assert!(needed > 5);
+		// Compressing the repeated text makes it fit
+		let messages = super::don_limited(test_data, &dict, 100, &options, 5).unwrap();
+		assert!(messages.len() <= 5);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		let messages = super::don_limited(test_data, &dict, 100, &options, needed).unwrap();
+		assert_eq!(messages.len(), needed);
+		assert!(matches!(
+			super::don_limited(test_data, &dict, 100, &options, 1),
+			Err(CawError::TooManyMessages { allowed: 1, .. })
+		));
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn split_strategies() {
+		use super::SplitStrategy;
+		use rand::SeedableRng;
+		let date = chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+		let dict = DictMappings::from_seed(69, &date);
+		let test_data = "Split into messages which look alike. ".repeat(6);
+		let test_data = test_data.as_bytes();
+		let armor = |split| {
+			let options = Options {
+				split,
+				..Options::default()
+			};
+			let mut rng = rand_pcg::Pcg64::seed_from_u64(1);
+			super::don_inner(test_data, &dict, 120, &options, None, &mut rng)
+		};
+		let longest = |messages: &[String]| messages.iter().map(|v| v.len()).max().unwrap();
+		let greedy = armor(SplitStrategy::Greedy);
+		let balanced = armor(SplitStrategy::Balanced);
+		assert_eq!(balanced.len(), greedy.len());
+		assert!(longest(&balanced) <= longest(&greedy));
+		// Greedy splitting leaves the last message short
+		assert!(balanced.last().unwrap().len() > greedy.last().unwrap().len() + 10);
+		assert_eq!(super::doff(&balanced, &dict).unwrap(), test_data);
+		let fixed = armor(SplitStrategy::Words(7));
+		assert!(fixed.len() > greedy.len());
+		// Every message but the first starts with its marker and sequence number
+		assert!(fixed[1..].iter().all(|v| v.split(' ').count() <= 9));
+		assert_eq!(super::doff(&fixed, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn random_lengths() {
+		use super::SplitStrategy;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Nobody fills every message to the brim. ".repeat(8);
+		let test_data = test_data.as_bytes();
+		let options = Options {
+			split: SplitStrategy::Random(60),
+			..Options::default()
+		};
+		let messages = super::don_with(test_data, &dict, 120, &options);
+		assert!(messages.len() > super::don(test_data, &dict, 120).len());
+		assert!(messages.iter().all(|v| v.len() <= 120));
+		// The limits differ, so the messages do too, apart from the short first and last ones
+		let lens: Vec<usize> = messages[1..messages.len() - 1]
+			.iter()
+			.map(|v| v.len())
+			.collect();
+		assert!(lens.iter().max().unwrap() - lens.iter().min().unwrap() > 20);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn padding() {
+		let dict = DictMappings::from_seed(69, &today());
+		assert_eq!(dict.nulls.len(), 64);
+		let options = Options {
+			padding: Some(4),
+			checksum: true,
+			..Options::default()
+		};
+		for len in [1, 30, 90] {
+			let test_data = "x".repeat(len);
+			let test_data = test_data.as_bytes();
+			let messages = super::don_with(test_data, &dict, 100, &options);
+			assert!(messages.len().is_multiple_of(4));
+			assert!(messages.iter().all(|v| (95..=100).contains(&v.len())));
+			assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn chaff() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			chaff: 30,
+			..Options::default()
+		};
+		let test_data = "Scattered among words which mean nothing. ".repeat(3);
+		let test_data = test_data.as_bytes();
+		let messages = super::don_with(test_data, &dict, 1000, &options);
+		let nulls = messages[0]
+			.split(' ')
+			.filter(|v| {
+				let index = dict.dictionary.index_of(v).unwrap();
+				dict.nulls.contains(&index)
+			})
+			.count();
+		assert!(nulls > 5 && nulls < 40);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		let mut streamed = vec![];
+		let mut writer = super::DonWriter::with_options(&dict, 50, &options, |v| {
+			streamed.push(v);
+			Ok(())
+		});
+		std::io::Write::write_all(&mut writer, test_data).unwrap();
+		writer.finish().unwrap();
+		assert_eq!(super::doff(&streamed, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn nonce() {
+		use rand::SeedableRng;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "The same thing, over and over again".as_bytes();
+		let body = |options: &Options, seed: u64| {
+			let mut rng = rand_pcg::Pcg64::seed_from_u64(seed);
+			let messages = super::don_inner(test_data, &dict, 1000, options, None, &mut rng);
+			assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+			// Leave out the markers, which are picked at random anyway
+			let words: Vec<String> = messages[0].split(' ').map(String::from).collect();
+			words[1..words.len() - 1].to_vec()
+		};
+		let plain = Options::default();
+		assert_eq!(body(&plain, 1), body(&plain, 2));
+		let options = Options {
+			nonce: true,
+			..Options::default()
+		};
+		let (first, second) = (body(&options, 1), body(&options, 2));
+		assert_ne!(first, second);
+		assert_eq!(first.len(), second.len());
+		let mut streamed = vec![];
+		let mut writer = super::DonWriter::with_options(&dict, 50, &options, |v| {
+			streamed.push(v);
+			Ok(())
+		});
+		std::io::Write::write_all(&mut writer, test_data).unwrap();
+		writer.finish().unwrap();
+		let mut reader = super::DoffReader::new(&dict);
+		for message in &streamed {
+			reader.push(message).unwrap();
+		}
+		let mut doffed = vec![];
+		std::io::Read::read_to_end(&mut reader, &mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+		assert_eq!(
+			super::doff_partial(&streamed, &dict).unwrap().data,
+			test_data
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn keystream() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = [vec![0; 200], b"after the zeroes".to_vec()].concat();
+		let distinct = |messages: &[String]| {
+			let mut words: Vec<&str> = messages.iter().flat_map(|v| v.split(' ')).collect();
+			words.sort_unstable();
+			words.dedup();
+			words.len()
+		};
+		let plain = super::don(&test_data, &dict, 100);
+		let options = Options {
+			keystream: true,
+			..Options::default()
+		};
+		let messages = super::don_with(&test_data, &dict, 100, &options);
+		assert!(distinct(&messages) > distinct(&plain) * 3);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		let mut streamed = vec![];
+		let mut writer = super::DonWriter::with_options(&dict, 100, &options, |v| {
+			streamed.push(v);
+			Ok(())
+		});
+		std::io::Write::write_all(&mut writer, &test_data).unwrap();
+		writer.finish().unwrap();
+		assert_eq!(super::doff(&streamed, &dict).unwrap(), test_data);
+		assert_eq!(
+			super::doff_partial(&streamed, &dict).unwrap().data,
+			test_data
+		);
+		// Nobody knows where in the keystream the messages after a gap start
+		streamed.remove(1);
+		assert!(matches!(
+			super::doff_partial(&streamed, &dict),
+			Err(CawError::MissingFragments { .. })
+		));
+		// Error correction still makes up for a missing message
+		let options = Options {
+			keystream: true,
+			error_correction: 40,
+			..Options::default()
+		};
+		let mut messages = super::don_with(&test_data, &dict, 100, &options);
+		messages.remove(2);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn reused_messages() {
+		use rand::SeedableRng;
+		use rand_pcg::Pcg64;
+		let dict = DictMappings::from_seed(69, &today());
+		let mut messages = vec![];
+		let long = "The first payload needs quite a few more messages than the second".as_bytes();
+		super::don_into(long, &dict, 50, &mut messages);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), long);
+		let (first, capacity) = (messages[0].as_ptr(), messages[0].capacity());
+		// With the same generator state, the messages are the ones `don_with_rng` makes
+		let short = "Only a couple".as_bytes();
+		let mut rng = Pcg64::seed_from_u64(69);
+		let expected = super::don_with_rng(short, &dict, 50, &mut rng.clone());
+		super::don_inner_into(
+			short,
+			&dict,
+			50,
+			&Options::default(),
+			None,
+			&mut rng,
+			&mut messages,
+		);
+		assert_eq!(messages, expected);
+		if messages[0].len() <= capacity {
+			assert_eq!(messages[0].as_ptr(), first);
+		}
+		assert_eq!(super::doff(&messages, &dict).unwrap(), short);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
 	fn reverse_lookup() {
-		let dict = DictMappings::from_seed(69, &Utc::now().date());
-		for word in vec![32551, 1233, 43241, 3289, 123, 1234, 1] {
+		let dict = DictMappings::from_seed(69, &today());
+		for word in [32551, 1233, 43241, 3289, 123, 1234, 1] {
 			let entry = dict.words[word as usize];
 			let reverse = dict.reverse_lookup(entry).unwrap();
 			assert_eq!(word, reverse)
 		}
+		assert_eq!(dict.reverse_lookup(dict.begin[0]), None);
+		assert_eq!(dict.reverse_lookup(u32::MAX), None);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn malformed_input() {
+		let dict = DictMappings::from_seed(69, &today());
+		let mut messages = super::don(b"Hello", &dict, 50);
+		messages[0].push_str(" xyzzyplugh");
+		assert_eq!(
+			super::doff(&messages, &dict),
+			Err(CawError::UnknownWord {
+				message: 0,
+				word: "xyzzyplugh".to_string()
+			})
+		);
+		let end = dict.dictionary.word(dict.end[0]).to_string();
+		assert_eq!(
+			super::doff(&[end], &dict),
+			Err(CawError::MissingMarker { message: 0 })
+		);
+		let fragment = dict.dictionary.word(dict.fragment[0]).to_string();
+		assert_eq!(
+			super::doff(&[fragment], &dict),
+			Err(CawError::MissingSequence { message: 0 })
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn case_insensitive() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Typed on a phone".as_bytes();
+		let messages: Vec<String> = super::don(test_data, &dict, 50)
+			.iter()
+			.enumerate()
+			.map(|(n, message)| {
+				if n == 0 {
+					message.to_uppercase()
+				} else {
+					let mut chars = message.chars();
+					chars.next().unwrap().to_uppercase().chain(chars).collect()
+				}
+			})
+			.collect();
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		let words = vec!["Polish".to_string(), "polish".to_string()];
+		let words = crate::dictionary::WordList::new(words);
+		assert_eq!(words.index_of_any_case("polish"), Some(1));
+		assert_eq!(words.index_of_any_case("POLISH"), Some(0));
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn typos() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			checksum: true,
+			..Options::default()
+		};
+		let test_data = "Copied out by hand, one word at a time".as_bytes();
+		let messages = super::don_with(test_data, &dict, 1000, &options);
+		let words: Vec<&str> = messages[0].split(' ').collect();
+		let mut unambiguous = 0;
+		for position in 1..words.len() {
+			let mut typed = words.clone();
+			let typo = format!("{}q", words[position]);
+			if dict.dictionary.index_of(&typo).is_some() {
+				continue;
+			}
+			typed[position] = &typo;
+			let typed = vec![typed.join(" ")];
+			assert!(matches!(
+				super::doff(&typed, &dict),
+				Err(CawError::UnknownWord { .. })
+			));
+			let (data, corrections) = match super::doff_lenient(&typed, &dict) {
+				Ok((data, report)) => (data, report.corrections),
+				Err(e) => {
+					assert_eq!(e, CawError::ChecksumMismatch);
+					continue;
+				}
+			};
+			assert_eq!(corrections.len(), 1);
+			assert_eq!(corrections[0].position, position);
+			assert_eq!(corrections[0].typed, typo);
+			if corrections[0].candidates == 1 {
+				assert_eq!(corrections[0].corrected, words[position]);
+				assert_eq!(data, test_data);
+				unambiguous += 1;
+			}
+		}
+		assert!(unambiguous > 0);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn fillers() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			fillers: vec!["lol".to_string(), "tbh".to_string(), "🙃".to_string()],
+			..Options::default()
+		};
+		let test_data = "Nobody would suspect a thing, honestly. ".repeat(10);
+		let test_data = test_data.as_bytes();
+		let messages = super::don_with(test_data, &dict, 100, &options);
+		assert!(messages
+			.iter()
+			.flat_map(|m| m.split(' '))
+			.any(|word| options.fillers.iter().any(|v| v == word)));
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		assert_eq!(super::doff_lenient(&messages, &dict).unwrap().0, test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn separators() {
+		use super::Separator;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "No spaces allowed in here".as_bytes();
+		for separator in [
+			Separator::Newline,
+			Separator::Hyphen,
+			Separator::Custom("__".to_string()),
+		] {
+			for natural in [false, true] {
+				let options = Options {
+					separator: separator.clone(),
+					natural,
+					..Options::default()
+				};
+				let messages = super::don_with(test_data, &dict, 100, &options);
+				assert!(messages.len() > 1);
+				for message in &messages {
+					assert!(!message.contains(' '));
+					assert!(message.len() <= 100);
+				}
+				assert_eq!(
+					super::doff_with_separator(&messages, &dict, &separator).unwrap(),
+					test_data
+				);
+				// Doffing always splits at whitespace
+				assert_eq!(
+					super::doff(&messages, &dict).is_ok(),
+					separator == Separator::Newline
+				);
+			}
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn pasted_whitespace() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Pasted out of a word processor".as_bytes();
+		let messages: Vec<String> = super::don(test_data, &dict, 50)
+			.iter()
+			.enumerate()
+			.map(|(index, message)| {
+				let gap = ["\t", "  ", "\u{a0}", " \r\n "][index % 4];
+				format!(" {}\n", message.replace(' ', gap))
+			})
+			.collect();
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn punctuation() {
+		use super::Tokenizer;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Quoted back with a few remarks".as_bytes();
+		let decorations = [
+			("\"", "!"),
+			("\u{2018}", "\u{2019}"),
+			("(", "),"),
+			("", "..."),
+		];
+		let messages: Vec<String> = super::don(test_data, &dict, 50)
+			.iter()
+			.map(|message| {
+				let words: Vec<String> = message
+					.split(' ')
+					.enumerate()
+					.map(|(index, word)| {
+						let (before, after) = decorations[index % decorations.len()];
+						format!("{}{}{}", before, word, after)
+					})
+					.collect();
+				words.join(" ")
+			})
+			.collect();
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		let tokenizer = Tokenizer {
+			punctuation: vec![],
+			..Tokenizer::default()
+		};
+		assert!(super::doff_with_tokenizer(&messages, &dict, &tokenizer).is_err());
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn multiple_recipients() {
+		use super::{ChunkSize, Language, RotationPeriod};
+		use rand_pcg::Pcg64;
+		let dicts: Vec<DictMappings> = (0..3)
+			.map(|seed| DictMappings::from_seed(seed, &today()))
+			.collect();
+		let recipients: Vec<&DictMappings> = dicts.iter().collect();
+		let test_data = "The same news for everybody".as_bytes();
+		let armors = super::don_multi(test_data, &recipients, 50);
+		assert_eq!(armors.len(), 3);
+		for (messages, dict) in armors.iter().zip(&dicts) {
+			assert_eq!(super::doff(messages, dict).unwrap(), test_data);
+		}
+		assert_ne!(
+			super::doff(&armors[0], &dicts[1]).ok(),
+			Some(test_data.to_vec())
+		);
+		assert!(super::don_multi::<crate::Language>(test_data, &[], 50).is_empty());
+		// Mappings with other chunk sizes get a payload packed for them
+		let seed = RotationPeriod::Static.seed_string(69, &Utc::now());
+		let twelve = DictMappings::from_seed_string_sized::<Pcg64>(
+			Language::English,
+			&seed,
+			ChunkSize::Twelve,
+		);
+		for recipients in [[&dicts[0], &twelve], [&twelve, &dicts[0]]] {
+			let armors = super::don_multi(test_data, &recipients, 50);
+			for (messages, dict) in armors.iter().zip(recipients) {
+				assert_eq!(super::doff(messages, dict).unwrap(), test_data);
+			}
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn versions() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Written before versions were invented".as_bytes();
+		let mut messages = super::don(test_data, &dict, 60);
+		let version_tag = dict
+			.dictionary
+			.word(dict.tags[super::Tag::Version as usize]);
+		let mut words: Vec<&str> = messages[0].split(' ').collect();
+		assert_eq!(words[1], version_tag);
+		assert_eq!(words[2], dict.dictionary.word(dict.words[1]));
+		// Version 0 armor has no version field
+		let old: Vec<&str> = [&words[..1], &words[3..]].concat();
+		let mut old_messages = messages.clone();
+		old_messages[0] = old.join(" ");
+		assert_eq!(super::doff(&old_messages, &dict).unwrap(), test_data);
+		words[2] = dict.dictionary.word(dict.words[2]);
+		messages[0] = words.join(" ");
+		assert_eq!(
+			super::doff(&messages, &dict),
+			Err(CawError::UnsupportedVersion {
+				message: 0,
+				version: 2
+			})
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn content_types() {
+		use super::ContentType;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = r#"{"kind": "json"}"#.as_bytes();
+		let options = Options {
+			content_type: Some(ContentType::Json),
+			..Options::default()
+		};
+		let messages = super::don_with(test_data, &dict, 60, &options);
+		let (data, report) = super::doff_with_report(&messages, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.content_type, Some(ContentType::Json));
+		// A kind of content from a later version is ignored
+		let tag = dict
+			.dictionary
+			.word(dict.tags[super::Tag::ContentType as usize]);
+		let mut messages = messages;
+		let mut words: Vec<&str> = messages[0].split(' ').collect();
+		let at = words.iter().position(|v| *v == tag).unwrap();
+		assert_eq!(words[at + 1], dict.dictionary.word(dict.words[3]));
+		words[at + 1] = dict.dictionary.word(dict.words[9]);
+		messages[0] = words.join(" ");
+		let (data, report) = super::doff_with_report(&messages, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.content_type, None);
+		let messages = super::don(test_data, &dict, 60);
+		assert_eq!(
+			super::doff_with_report(&messages, &dict)
+				.unwrap()
+				.1
+				.content_type,
+			None
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn duplicates() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Copied out of the chat history twice".as_bytes();
+		let mut messages = super::don(test_data, &dict, 40);
+		assert!(messages.len() > 2);
+		messages.insert(0, messages[1].clone());
+		messages.push(messages[1].clone());
+		let (data, report) = super::doff_with_report(&messages, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.duplicates, vec![2, messages.len() - 1]);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn policy() {
+		use super::{DecodePolicy, Tolerances};
+		let dict = DictMappings::from_seed(69, &today());
+		let strict = |messages: &[String]| {
+			super::doff_with_policy(messages, &dict, &DecodePolicy::Strict).map(|(data, _)| data)
+		};
+		let test_data = "Read exactly as it was written".as_bytes();
+		let messages = super::don(test_data, &dict, 40);
+		assert!(messages.len() > 2);
+		assert_eq!(strict(&messages).unwrap(), test_data);
+		let mut repeated = messages.clone();
+		repeated.push(messages[1].clone());
+		assert_eq!(
+			strict(&repeated),
+			Err(CawError::DuplicateMessage {
+				message: messages.len()
+			})
+		);
+		let custom = DecodePolicy::Custom(Tolerances {
+			duplicates: true,
+			..DecodePolicy::Strict.tolerances()
+		});
+		assert_eq!(
+			super::doff_with_policy(&repeated, &dict, &custom)
+				.unwrap()
+				.0,
+			test_data
+		);
+		// The fragment count says how many messages there are, so the end marker isn't needed
+		let mut unended = messages.clone();
+		let last = unended.last_mut().unwrap();
+		last.truncate(last.rfind(' ').unwrap());
+		assert_eq!(super::doff(&unended, &dict).unwrap(), test_data);
+		assert_eq!(strict(&unended), Err(CawError::MissingEnd));
+		for altered in [messages[0].to_uppercase(), messages[0].replace(' ', ". ")] {
+			let mut altered = [vec![altered], messages[1..].to_vec()].concat();
+			assert_eq!(super::doff(&altered, &dict).unwrap(), test_data);
+			assert!(matches!(
+				strict(&altered),
+				Err(CawError::UnknownWord { message: 0, .. })
+			));
+			altered[0] = messages[0].clone();
+			assert_eq!(strict(&altered).unwrap(), test_data);
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn message_ids() {
+		let dict = DictMappings::from_seed(69, &today());
+		let first = "The first of two files sent at once".as_bytes();
+		let second = "And the second one, which is a little longer".as_bytes();
+		let third = "Nobody gave this one an ID".as_bytes();
+		let armor = |data, message_id| {
+			let options = Options {
+				message_id,
+				..Options::default()
+			};
+			super::don_with(data, &dict, 50, &options)
+		};
+		let (a, b, c) = (
+			armor(first, Some(1)),
+			armor(second, Some(2)),
+			armor(third, None),
+		);
+		assert!(a.len() > 1 && b.len() > 1);
+		assert_eq!(super::doff(&a, &dict).unwrap(), first);
+		let mut messages = vec![];
+		for n in 0..a.len().max(b.len()) {
+			messages.extend(a.get(n).cloned());
+			messages.extend(b.get(n).cloned());
+		}
+		assert_eq!(super::doff(&messages, &dict), Err(CawError::MixedMessages));
+		messages.extend(c);
+		let doffed = super::doff_all(&messages, &dict).unwrap();
+		assert_eq!(doffed.len(), 3);
+		assert_eq!(doffed[&Some(1)], Ok(first.to_vec()));
+		assert_eq!(doffed[&Some(2)], Ok(second.to_vec()));
+		assert_eq!(doffed[&None], Ok(third.to_vec()));
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn sessions() {
+		let dict = DictMappings::from_seed(69, &today());
+		let armor = |data: &str, session_id| {
+			let options = Options {
+				session_id,
+				..Options::default()
+			};
+			super::don_with(data.as_bytes(), &dict, 50, &options)
+		};
+		let ours = "A bot keeps track of several conversations";
+		let mut messages = armor(ours, Some(70_000));
+		assert!(messages.len() > 1);
+		assert_eq!(super::session_id(&messages[1], &dict), Ok(Some(70_000)));
+		messages.splice(1..1, armor("Somebody else's conversation", Some(3)));
+		messages.extend(armor("No conversation at all", None));
+		messages.push("just chatting".to_string());
+		assert!(super::doff(&messages, &dict).is_err());
+		let ours_only = super::filter_session(&messages, &dict, 70_000);
+		assert_eq!(super::doff(&ours_only, &dict).unwrap(), ours.as_bytes());
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn missing_fragments() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Lost somewhere in the middle of a long chat history".as_bytes();
+		let messages = super::don(test_data, &dict, 40);
+		let last = messages.len() - 1;
+		assert!(last > 3);
+		let missing = |dropped: &[usize]| {
+			let kept: Vec<String> = (0..messages.len())
+				.filter(|v| !dropped.contains(v))
+				.map(|v| messages[v].clone())
+				.collect();
+			super::doff(&kept, &dict)
+		};
+		assert_eq!(
+			missing(&[1, 3]),
+			Err(CawError::MissingFragments {
+				missing: vec![1, 3]
+			})
+		);
+		assert_eq!(
+			missing(&[last]),
+			Err(CawError::MissingFragments {
+				missing: vec![last as u32]
+			})
+		);
+		// Without the first message, nobody knows how many messages there are
+		assert_eq!(
+			missing(&[0, last]),
+			Err(CawError::MissingFragments {
+				missing: vec![0, last as u32]
+			})
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn many_fragments() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data: Vec<u8> = (0..140_000u32).map(|v| v as u8).collect();
+		// Every word gets a message of its own
+		let messages = super::don(&test_data, &dict, 1);
+		assert!(messages.len() > 1 << 16);
+		let sequence_tag = dict
+			.dictionary
+			.word(dict.tags[super::Tag::Sequence as usize]);
+		assert_eq!(messages[1 << 16].split(' ').nth(1), Some(sequence_tag));
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn checksum() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			checksum: true,
+			..Options::default()
+		};
+		let test_data = "Make sure nothing got lost".as_bytes();
+		let mut messages = super::don_with(test_data, &dict, 50, &options);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		// Drop the first data word of the second message
+		let mut words: Vec<&str> = messages[1].split(' ').collect();
+		words.remove(2);
+		messages[1] = words.join(" ");
+		assert_eq!(
+			super::doff(&messages, &dict),
+			Err(CawError::ChecksumMismatch)
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn error_correction() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			checksum: true,
+			error_correction: 32,
+			..Options::default()
+		};
+		let test_data = "Parity words can stand in for words which went missing".as_bytes();
+		let mut messages = super::don_with(test_data, &dict, 60, &options);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		// Swap a data word in the second message for another one
+		let mut words: Vec<&str> = messages[1].split(' ').collect();
+		let position = words.len() - 1;
+		let other = dict.dictionary.word(dict.words[1234]);
+		words[position] = if words[position] == other {
+			dict.dictionary.word(dict.words[4321])
+		} else {
+			other
+		};
+		messages[1] = words.join(" ");
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		// Lose a message from the middle as well
+		messages.remove(2);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		// Too much is missing once half the messages are gone
+		messages.truncate(messages.len() / 2);
+		assert_eq!(super::doff(&messages, &dict), Err(CawError::Uncorrectable));
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn odd_length() {
+		let dict = DictMappings::from_seed(69, &today());
+		for test_data in [&b"Odd"[..], &[0xff, 0, 0], &[0]] {
+			let messages = super::don(test_data, &dict, 50);
+			assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+			let options = Options {
+				checksum: true,
+				..Options::default()
+			};
+			let messages = super::don_with(test_data, &dict, 50, &options);
+			assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn date_window() {
+		let yesterday = today().pred_opt().unwrap();
+		let dict = DictMappings::from_seed(69, &yesterday);
+		let test_data = "Sent just before midnight".as_bytes();
+		let messages = super::don(test_data, &dict, 50);
+		assert!(super::doff_with_window(&messages, 69, 0).is_err());
+		assert_eq!(
+			super::doff_with_window(&messages, 69, 1),
+			Ok((test_data.to_vec(), yesterday))
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn dated() {
+		let date = today() - chrono::Duration::days(400);
+		let dict = DictMappings::from_seed(69, &date);
+		let options = Options {
+			date: Some(date),
+			..Options::default()
+		};
+		let test_data = "Found at the bottom of a drawer".as_bytes();
+		let mut messages = super::don_with(test_data, &dict, 60, &options);
+		messages.reverse();
+		assert_eq!(
+			super::doff_dated(&messages, 69).unwrap(),
+			(test_data.to_vec(), date)
+		);
+		// Flags push the date further from begin
+		let options = Options {
+			date: Some(date),
+			metadata: true,
+			..Options::default()
+		};
+		let messages = super::don_with(test_data, &dict, 60, &options);
+		assert_eq!(
+			super::doff_dated(&messages, 69).unwrap(),
+			(test_data.to_vec(), date)
+		);
+		// Armor without a date is read with today's mappings
+		let dict = DictMappings::from_seed(69, &today());
+		let messages = super::don(test_data, &dict, 60);
+		assert_eq!(super::doff_dated(&messages, 69).unwrap().1, today());
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn fresh() {
+		let date = today() - chrono::Duration::days(3);
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			date: Some(date),
+			..Options::default()
+		};
+		let test_data = "Only worth reading this week".as_bytes();
+		let messages = super::don_with(test_data, &dict, 60, &options);
+		assert_eq!(super::doff_fresh(&messages, &dict, 7).unwrap(), test_data);
+		assert_eq!(super::doff_fresh(&messages, &dict, 3).unwrap(), test_data);
+		assert_eq!(
+			super::doff_fresh(&messages, &dict, 2),
+			Err(CawError::Stale { date: Some(date) })
+		);
+		let messages = super::don(test_data, &dict, 60);
+		assert_eq!(
+			super::doff_fresh(&messages, &dict, 7),
+			Err(CawError::Stale { date: None })
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn timestamped() {
+		let dict = DictMappings::from_seed(69, &today());
+		let time = chrono::NaiveTime::from_hms_opt(13, 37, 42).unwrap();
+		let options = Options {
+			date: Some(today()),
+			time: Some(time),
+			..Options::default()
+		};
+		let test_data = "Sent after lunch".as_bytes();
+		let messages = super::don_with(test_data, &dict, 60, &options);
+		let (data, report) = super::doff_with_report(&messages, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.time, chrono::NaiveTime::from_hms_opt(13, 37, 0));
+		assert_eq!(report.date, Some(today()));
+		// Armor with a time can still be read by its date
+		assert_eq!(super::doff_dated(&messages, 69).unwrap().1, today());
+		let messages = super::don(test_data, &dict, 60);
+		assert_eq!(
+			super::doff_with_report(&messages, &dict).unwrap().1.time,
+			None
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn rotation() {
+		use super::RotationPeriod;
+		use chrono::TimeZone;
+		let at = Utc.with_ymd_and_hms(2024, 2, 29, 13, 37, 0).unwrap();
+		let daily = DictMappings::from_seed_rotating(69, RotationPeriod::Daily, &at);
+		assert_eq!(
+			daily.words,
+			DictMappings::from_seed(69, &at.date_naive()).words
+		);
+		let sydney = chrono::FixedOffset::east_opt(11 * 3600).unwrap();
+		let local = sydney.with_ymd_and_hms(2024, 3, 1, 5, 0, 0).unwrap();
+		assert_eq!(super::utc_date(&local), at.date_naive());
+		let later = Utc.with_ymd_and_hms(2024, 2, 29, 14, 0, 0).unwrap();
+		let next_week = Utc.with_ymd_and_hms(2024, 3, 4, 0, 0, 0).unwrap();
+		for (period, same, different) in [
+			(RotationPeriod::Hourly, at, later),
+			(RotationPeriod::Weekly, later, next_week),
+		] {
+			let dict = DictMappings::from_seed_rotating(69, period, &at);
+			assert_eq!(
+				dict.words,
+				DictMappings::from_seed_rotating(69, period, &same).words
+			);
+			assert_ne!(
+				dict.words,
+				DictMappings::from_seed_rotating(69, period, &different).words
+			);
+		}
+		let first = Utc.timestamp_opt(0, 0).unwrap();
+		assert_eq!(
+			DictMappings::from_seed_rotating(69, RotationPeriod::Static, &first).words,
+			DictMappings::from_seed_rotating(69, RotationPeriod::Static, &at).words
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn other_generators() {
+		use super::{Language, RotationPeriod};
+		use rand::rngs::StdRng;
+		let at = Utc::now();
+		let seed = RotationPeriod::Daily.seed_string(69, &at);
+		let pcg = DictMappings::from_seed_string(Language::English, &seed);
+		assert_eq!(pcg.words, DictMappings::from_seed(69, &today()).words);
+		let std = DictMappings::from_seed_string_with::<StdRng>(Language::English, &seed);
+		assert_ne!(std.words, pcg.words);
+		assert_eq!(std.key, pcg.key);
+		let test_data = "Shuffled some other way".as_bytes();
+		let messages = crate::don(test_data, &std, 50);
+		assert_eq!(crate::doff(&messages, &std).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn estimates() {
+		let dict = DictMappings::from_seed(69, &today());
+		let estimate = crate::estimate(0, &dict, 280);
+		assert_eq!(estimate.messages, 1);
+		assert!(estimate.expansion_ratio.is_infinite());
+		assert_eq!(crate::estimate(1, &dict, 280).messages, 1);
+		for (len, limit) in [(1001, 280), (5000, 2000), (20_000, 500)] {
+			let data: Vec<u8> = (0..len).map(|_| rand::random()).collect();
+			let messages = crate::don(&data, &dict, limit);
+			let total: usize = messages.iter().map(|v| v.len()).sum();
+			let estimate = crate::estimate(len, &dict, limit);
+			assert!(estimate.messages.abs_diff(messages.len()) <= 1 + messages.len() / 20);
+			assert!(estimate.total_chars.abs_diff(total) <= total / 10);
+			assert!(estimate.expansion_ratio > 1.0);
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn derivations() {
+		use super::{ChunkSize, Derivation, Language, RotationPeriod};
+		use rand_pcg::Pcg64;
+		use std::collections::HashSet;
+		let seed = RotationPeriod::Daily.seed_string(69, &Utc::now());
+		let build = |derivation| {
+			DictMappings::from_seed_string_derived::<Pcg64>(
+				Language::English,
+				&seed,
+				ChunkSize::Sixteen,
+				derivation,
+			)
+		};
+		let full = build(Derivation::Full);
+		assert_eq!(full.words, DictMappings::from_seed(69, &today()).words);
+		let partial = build(Derivation::Partial);
+		assert_ne!(partial.words, full.words);
+		assert_eq!(partial.homophones.len(), full.homophones.len());
+		let assigned: HashSet<u32> = [
+			&partial.begin,
+			&partial.end,
+			&partial.fragment,
+			&partial.words,
+			&partial.tags,
+			&partial.homophones,
+		]
+		.iter()
+		.flat_map(|v| v.iter().copied())
+		.collect();
+		assert_eq!(assigned.len(), 15 + 65536 + 64 + partial.homophones.len());
+		let test_data = "Shuffled only as far as it needs to be".as_bytes();
+		let messages = crate::don(test_data, &partial, 50);
+		assert_eq!(crate::doff(&messages, &partial).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn metadata() {
+		use super::Flag;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Flags fly after the marker, flags fly after the marker".as_bytes();
+		let options = Options {
+			metadata: true,
+			compress: true,
+			..Options::default()
+		};
+		let messages = crate::don_with(test_data, &dict, 60, &options);
+		assert!(messages.len() > 1);
+		// Only the first message, whose header can't be split, can be longer
+		assert!(messages[1..].iter().all(|v| v.len() <= 60));
+		let (data, report) = crate::doff_with_report(&messages, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.flags, [Flag::Compressed, Flag::Text, Flag::Final]);
+		// The last message says so
+		let last = messages.last().unwrap().split(' ').nth(1).unwrap();
+		assert_eq!(
+			dict.dictionary.index_of(last),
+			Some(dict.flags[Flag::Final as usize])
+		);
+		assert!(crate::detect(&messages[0], &dict).is_likely());
+		// Flags which aren't understood yet are skipped
+		let mut unknown = messages.clone();
+		let flag = dict.dictionary.word(dict.flags[15]);
+		unknown[0] = unknown[0].replacen(' ', &format!(" {} ", flag), 1);
+		let (data, report) = crate::doff_with_report(&unknown, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.flags, [Flag::Compressed, Flag::Text, Flag::Final]);
+		let plain = crate::don(&[0xff, 0xfe], &dict, 1000);
+		assert!(crate::doff_with_report(&plain, &dict)
+			.unwrap()
+			.1
+			.flags
+			.is_empty());
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn nested() {
+		use super::Flag;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Armor on armor".as_bytes();
+		let inner = crate::don(test_data, &dict, 50).join("\n");
+		assert!(crate::is_armor(inner.as_bytes(), &dict));
+		assert!(!crate::is_armor(test_data, &dict));
+		assert!(crate::is_armor(
+			format!("\n  \n{}\n", inner).as_bytes(),
+			&dict
+		));
+		assert!(!crate::is_armor(&[0xff, 0xfe], &dict));
+		let tomorrow = DictMappings::from_seed(69, &today().succ_opt().unwrap());
+		assert!(!crate::is_armor(inner.as_bytes(), &tomorrow));
+		let outer = crate::don(inner.as_bytes(), &dict, 50);
+		let (data, report) = crate::doff_with_report(&outer, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.flags, [Flag::Nested]);
+		// Under other mappings, the armor is just text
+		let outer = crate::don(inner.as_bytes(), &tomorrow, 50);
+		assert_eq!(crate::doff(&outer, &tomorrow).unwrap(), inner.as_bytes());
+	}
+	#[test]
+	fn marker_counts() {
+		use super::{dictionary::WordList, ChunkSize, Derivation, MarkerCounts};
+		use rand_pcg::Pcg64;
+		let words = WordList::new((0..400).map(|n| format!("word{}", n)).collect());
+		let build = |markers| {
+			DictMappings::from_seed_string_markers::<Pcg64>(
+				words.clone(),
+				"69",
+				ChunkSize::Eight,
+				Derivation::Full,
+				markers,
+			)
+		};
+		let default = build(MarkerCounts::default());
+		assert_eq!(
+			default.words,
+			DictMappings::from_seed_string(words.clone(), "69").words
+		);
+		let noisy = MarkerCounts {
+			begin: 20,
+			end: 20,
+			fragment: 20,
+		};
+		assert_eq!(noisy.min_len(ChunkSize::Eight), 380);
+		let noisy = build(noisy);
+		assert_eq!(noisy.begin.len(), 20);
+		assert_eq!(noisy.fragment.len(), 20);
+		assert_ne!(noisy.key, default.key);
+		let tight = build(MarkerCounts {
+			begin: 1,
+			end: 1,
+			fragment: 1,
+		});
+		assert_eq!(tight.end.len(), 1);
+		let test_data = "Louder for the people in the back".as_bytes();
+		for dict in [&noisy, &tight] {
+			let messages = crate::don(test_data, dict, 50);
+			assert!(messages.len() > 1);
+			assert_eq!(crate::doff(&messages, dict).unwrap(), test_data);
+			assert!(crate::doff(&messages, &default).is_err());
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn fingerprint() {
+		use super::dictionary::WordList;
+		let dict = DictMappings::from_seed(69, &today());
+		let phrase = dict.fingerprint();
+		assert_eq!(phrase.split(' ').count(), 3);
+		assert_eq!(phrase, DictMappings::from_seed(69, &today()).fingerprint());
+		assert_ne!(phrase, DictMappings::from_seed(70, &today()).fingerprint());
+		let tomorrow = today() + chrono::Duration::days(1);
+		assert_ne!(phrase, DictMappings::from_seed(69, &tomorrow).fingerprint());
+		let words: Vec<String> = (0..400).map(|n| format!("word{}", n)).collect();
+		let mut other = words.clone();
+		other.swap(0, 1);
+		assert_ne!(
+			DictMappings::from_dictionary(WordList::new(words), 69, &today()).fingerprint(),
+			DictMappings::from_dictionary(WordList::new(other), 69, &today()).fingerprint()
+		);
+	}
+	#[test]
+	fn smallest_dictionary() {
+		use super::{dictionary::WordList, ChunkSize, Derivation, MarkerCounts};
+		use rand_pcg::Pcg64;
+		let markers = MarkerCounts {
+			begin: 1,
+			end: 1,
+			fragment: 1,
+		};
+		assert_eq!(markers.min_len(ChunkSize::Eight), 323);
+		let words = WordList::new((0..323).map(|n| format!("word{}", n)).collect());
+		let dict = DictMappings::from_seed_string_markers::<Pcg64>(
+			words,
+			"69",
+			ChunkSize::Eight,
+			Derivation::Full,
+			markers,
+		);
+		let test_data = "Every one of the words is spoken for".as_bytes();
+		let options = Options {
+			checksum: true,
+			..Options::default()
+		};
+		let messages = crate::don_with(test_data, &dict, 60, &options);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn chunk_sizes() {
+		use super::{dictionary::WordList, ChunkSize, Language, RotationPeriod};
+		use rand_pcg::Pcg64;
+		let test_data = "Small dictionaries take more words to say the same thing!".as_bytes();
+		let sixteen = crate::don(test_data, &DictMappings::from_seed(69, &today()), 1000);
+		let mut lens = vec![];
+		for (len, size) in [(5000, ChunkSize::Twelve), (400, ChunkSize::Eight)] {
+			let words = WordList::new((0..len).map(|n| format!("word{}", n)).collect());
+			let dict = DictMappings::from_dictionary(words, 69, &today());
+			assert_eq!(dict.chunk_size(), size);
+			let messages = crate::don(test_data, &dict, 1000);
+			assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+			lens.push(messages[0].split(' ').count());
+			// Long enough to need sequence numbers, and a missing message to correct
+			let options = Options {
+				checksum: true,
+				error_correction: 64,
+				message_id: Some(1234),
+				..Options::default()
+			};
+			let mut messages = crate::don_with(test_data, &dict, 60, &options);
+			assert!(messages.len() > 4);
+			assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+			messages.remove(2);
+			assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+			let mut doffed = vec![];
+			let messages = crate::don_iter(test_data, &dict, 60).collect::<Vec<_>>();
+			crate::doff_into(&messages, &dict, &mut doffed).unwrap();
+			assert_eq!(doffed, test_data);
+		}
+		assert!(lens[0] > sixteen[0].split(' ').count());
+		assert!(lens[1] > lens[0]);
+		// Armor from mappings with a different chunk size is refused
+		let seed = RotationPeriod::Static.seed_string(69, &Utc::now());
+		let twelve = DictMappings::from_seed_string_sized::<Pcg64>(
+			Language::English,
+			&seed,
+			ChunkSize::Twelve,
+		);
+		let messages = crate::don(test_data, &twelve, 1000);
+		assert_eq!(crate::doff(&messages, &twelve).unwrap(), test_data);
+		let mut eight = twelve;
+		eight.words.truncate(256);
+		eight.inverse = super::inverse(eight.dictionary.len(), &eight.words, &[]);
+		assert!(matches!(
+			crate::doff(&messages, &eight),
+			Err(CawError::WrongChunkSize { bits: 12, .. })
+		));
+	}
+	#[cfg(feature = "serde")]
+	#[test]
+	fn serde() {
+		let dict = DictMappings::from_seed(69, &today());
+		let json = serde_json::to_string(&dict).unwrap();
+		let loaded: DictMappings = serde_json::from_str(&json).unwrap();
+		assert_eq!(loaded.words, dict.words);
+		assert_eq!(loaded.reverse_lookup(dict.words[1234]), Some(1234));
+		let test_data = "Mappings from somewhere else".as_bytes();
+		let messages = super::don(test_data, &dict, 50);
+		assert_eq!(super::doff(&messages, &loaded).unwrap(), test_data);
+		let corrupted = json.replacen("[", "[0,", 1);
+		assert!(serde_json::from_str::<DictMappings>(&corrupted).is_err());
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn compression() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "caw caw caw caw caw caw caw caw caw caw caw caw caw!".repeat(20);
+		let test_data = test_data.as_bytes();
+		for options in [
+			Options {
+				compress: true,
+				..Options::default()
+			},
+			Options {
+				compress: true,
+				checksum: true,
+				error_correction: 4,
+				encrypt: true,
+				authenticate: true,
+				..Options::default()
+			},
+		] {
+			let messages = super::don_with(test_data, &dict, 280, &options);
+			assert!(messages.len() < super::don(test_data, &dict, 280).len());
+			assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		}
+		// Data which doesn't shrink is armored as it is
+		let options = Options {
+			compress: true,
+			..Options::default()
+		};
+		let test_data = [0xc4, 0x01];
+		let messages = super::don_with(&test_data, &dict, 280, &options);
+		// begin, the version's tag and value, the fragment count's tag and two values, the
+		// data, and end
+		assert_eq!(messages[0].split(' ').count(), 8);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn encryption() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			encrypt: true,
+			..Options::default()
+		};
+		let test_data = "Nobody else should be able to read this".as_bytes();
+		let messages = super::don_with(test_data, &dict, 50, &options);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		// Someone who worked out the mappings still can't read it without the key
+		let mut guessed = DictMappings::from_seed(69, &today());
+		guessed.key = [0; 32];
+		assert_eq!(
+			super::doff(&messages, &guessed),
+			Err(CawError::DecryptionFailed)
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn authentication() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			authenticate: true,
+			..Options::default()
+		};
+		let test_data = "Only someone with the seed could have sent this".as_bytes();
+		let mut messages = super::don_with(test_data, &dict, 60, &options);
+		assert!(messages.len() > 3);
+		assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		// Swap the sequence numbers of two fragments
+		let mut first: Vec<String> = messages[1].split(' ').map(String::from).collect();
+		let mut second: Vec<String> = messages[2].split(' ').map(String::from).collect();
+		std::mem::swap(&mut first[1], &mut second[1]);
+		messages[1] = first.join(" ");
+		messages[2] = second.join(" ");
+		assert_eq!(
+			super::doff(&messages, &dict),
+			Err(CawError::AuthenticationFailed)
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn platforms() {
+		use super::Platform;
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "A little longer than a tweet. ".repeat(20);
+		let test_data = test_data.as_bytes();
+		for platform in [
+			Platform::Twitter,
+			Platform::Sms,
+			Platform::Discord,
+			Platform::Irc,
+			Platform::Mastodon,
+		] {
+			let messages = super::don_for(test_data, &dict, platform);
+			let unit = platform.limit_unit();
+			assert!(messages
+				.iter()
+				.all(|m| unit.measure(m) <= platform.character_limit()));
+			assert_eq!(super::doff(&messages, &dict).unwrap(), test_data);
+		}
+	}
+
+	#[test]
+	fn limit_units() {
+		use super::LimitUnit;
+		let text = "é👍🏽";
+		assert_eq!(LimitUnit::Bytes.measure(text), 10);
+		assert_eq!(LimitUnit::Chars.measure(text), 3);
+		assert_eq!(LimitUnit::Graphemes.measure(text), 2);
+		assert_eq!(LimitUnit::Utf16.measure(text), 5);
+		let words = (0..70000).map(|n| format!("ñandú{}", n)).collect();
+		let dict =
+			DictMappings::from_dictionary(crate::dictionary::WordList::new(words), 69, &today());
+		let test_data = "Accents take up more than one byte each. ".repeat(8);
+		let test_data = test_data.as_bytes();
+		let bytes = super::don(test_data, &dict, 100);
+		let options = Options {
+			limit_unit: LimitUnit::Chars,
+			..Options::default()
+		};
+		let chars = super::don_with(test_data, &dict, 100, &options);
+		assert!(chars.len() < bytes.len());
+		assert!(bytes.iter().all(|m| m.len() <= 100));
+		assert!(chars.iter().all(|m| m.chars().count() <= 100));
+		assert_eq!(super::doff(&chars, &dict).unwrap(), test_data);
 	}
 }
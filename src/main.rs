@@ -0,0 +1,397 @@
+//! `caw`, a command line interface for donning and doffing armor.
+
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::exit;
+
+use chrono::Utc;
+use courier_caw::dictionary::WordList;
+use courier_caw::{
+	doff, doff_into_with_progress, doff_with_report, don_read_with_progress, ArchiveBuilder,
+	ArchiveReader, DictMappings, Dictionary, FileInfo, Language, Options, Platform, Progress,
+	RotationPeriod, Seed,
+};
+
+const USAGE: &str = "usage: caw don --seed <seed> --limit <limit> [--rotation <period>] [FILE]
+       caw don --seed <seed> --platform <platform> [--rotation <period>] [FILE]
+       caw doff --seed <seed> [--rotation <period>] [FILE...]
+       caw fingerprint --seed <seed> [--rotation <period>]
+
+Reads from standard input when no file is given. `don` writes one message per
+line, and `doff` expects one message per line. The rotation period is one of
+hourly, daily (the default), weekly or static.
+
+`--passphrase <phrase>` can be given instead of `--seed`, to derive the seed
+from a phrase with Argon2id, or `--mnemonic <words>` to read the seed from a
+12-word BIP39 mnemonic.
+
+`--platform` fits messages to twitter, sms, discord, irc or mastodon, setting
+the limit and counting lengths the way the platform does. `--limit` can still
+lower it.
+
+`--wordlist <file>` uses a newline-separated wordlist instead of the built-in
+English one. Both parties must use the same wordlist.
+
+`--file` keeps the name and modification time of the file being donned in the
+armor, and makes `doff` write the data to a new file with that name in the
+current directory instead of standard output.
+
+`--archive` makes `don` armor every file in the directory it's given as one
+armor, and makes `doff` write the files back under the current directory.
+
+`fingerprint` prints three words which depend on the mappings, so two people
+can check that they have the same ones by reading them out.
+
+`--progress` shows how many bytes and messages have been processed on standard
+error.";
+
+/// The options shared by every subcommand.
+#[derive(Debug, PartialEq)]
+struct Args {
+	seed: u128,
+	limit: Option<usize>,
+	platform: Option<Platform>,
+	rotation: RotationPeriod,
+	wordlist: Option<String>,
+	file: bool,
+	archive: bool,
+	progress: bool,
+	files: Vec<String>,
+}
+
+fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+	let mut seed = None;
+	let mut limit = None;
+	let mut platform = None;
+	let mut rotation = RotationPeriod::default();
+	let mut wordlist = None;
+	let mut file = false;
+	let mut archive = false;
+	let mut progress = false;
+	let mut files = vec![];
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+		match arg.as_str() {
+			"--seed" => {
+				let value = value("--seed")?;
+				seed = Some(
+					value
+						.parse()
+						.map_err(|_| format!("{:?} isn't a valid seed", value))?,
+				)
+			}
+			"--passphrase" => seed = Some(Seed::from_passphrase(&value("--passphrase")?).into()),
+			"--mnemonic" => {
+				let value = value("--mnemonic")?;
+				seed = Some(
+					Seed::from_mnemonic(&value)
+						.map_err(|e| e.to_string())?
+						.into(),
+				)
+			}
+			"--limit" => {
+				let value = value("--limit")?;
+				limit = Some(
+					value
+						.parse()
+						.map_err(|_| format!("{:?} isn't a valid limit", value))?,
+				)
+			}
+			"--platform" => {
+				platform = Some(match value("--platform")?.as_str() {
+					"twitter" | "x" => Platform::Twitter,
+					"sms" => Platform::Sms,
+					"discord" => Platform::Discord,
+					"irc" => Platform::Irc,
+					"mastodon" => Platform::Mastodon,
+					other => return Err(format!("{:?} isn't a platform", other)),
+				})
+			}
+			"--rotation" => {
+				rotation = match value("--rotation")?.as_str() {
+					"hourly" => RotationPeriod::Hourly,
+					"daily" => RotationPeriod::Daily,
+					"weekly" => RotationPeriod::Weekly,
+					"static" => RotationPeriod::Static,
+					other => return Err(format!("{:?} isn't a rotation period", other)),
+				}
+			}
+			"--wordlist" => wordlist = Some(value("--wordlist")?),
+			"--file" => file = true,
+			"--archive" => archive = true,
+			"--progress" => progress = true,
+			"-" => files.push(arg),
+			flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+			_ => files.push(arg),
+		}
+	}
+	Ok(Args {
+		seed: seed.ok_or("--seed, --passphrase or --mnemonic is required")?,
+		limit,
+		platform,
+		rotation,
+		wordlist,
+		file,
+		archive,
+		progress,
+		files,
+	})
+}
+
+/// Open every file in `files`, or standard input if there are none.
+fn inputs(files: &[String]) -> io::Result<Vec<Box<dyn Read>>> {
+	if files.is_empty() {
+		return Ok(vec![Box::new(io::stdin())]);
+	}
+	files
+		.iter()
+		.map(|file| -> io::Result<Box<dyn Read>> {
+			if file == "-" {
+				Ok(Box::new(io::stdin()))
+			} else {
+				Ok(Box::new(File::open(file)?))
+			}
+		})
+		.collect()
+}
+
+/// The mappings for right now.
+fn mappings<D: Dictionary>(args: &Args, dictionary: D) -> DictMappings<D> {
+	DictMappings::from_dictionary_rotating(dictionary, args.seed, args.rotation, &Utc::now())
+}
+
+/// Show progress on standard error if it was asked for, overwriting the line each time.
+fn progress(show: bool) -> impl FnMut(Progress) {
+	move |progress| {
+		if show {
+			eprint!("\r{} bytes, {} messages", progress.bytes, progress.messages);
+		}
+	}
+}
+
+/// The subcommands.
+enum Command {
+	Don,
+	Doff,
+	Fingerprint,
+}
+
+/// The mappings for whichever wordlist the arguments name.
+type Mappings<'a> = DictMappings<&'a dyn Dictionary>;
+
+/// Run a subcommand with the mappings for the wordlist the arguments name.
+fn run(command: Command, args: Args) -> Result<(), Box<dyn Error>> {
+	let wordlist = match &args.wordlist {
+		Some(path) => {
+			let wordlist = WordList::from_file(path).map_err(|e| format!("{}: {}", path, e))?;
+			if wordlist.validate().too_small() {
+				return Err(format!("{} has too few words", path).into());
+			}
+			Some(wordlist)
+		}
+		None => None,
+	};
+	let dictionary: &dyn Dictionary = match &wordlist {
+		Some(wordlist) => wordlist,
+		None if Language::English.is_empty() => {
+			return Err("this build has no built-in wordlist, so --wordlist is required".into())
+		}
+		None => &Language::English,
+	};
+	let dict = mappings(&args, dictionary);
+	match command {
+		Command::Don => run_don(args, &dict),
+		Command::Doff => run_doff(args, &dict),
+		Command::Fingerprint => {
+			println!("{}", dict.fingerprint());
+			Ok(())
+		}
+	}
+}
+
+fn run_don(args: Args, dict: &Mappings<'_>) -> Result<(), Box<dyn Error>> {
+	let (limit, mut options) = match args.platform {
+		Some(platform) => (
+			args.limit.unwrap_or(platform.character_limit()),
+			platform.options(),
+		),
+		None => (
+			args.limit.ok_or("--limit or --platform is required")?,
+			Options::default(),
+		),
+	};
+	if args.files.len() > 1 {
+		return Err("don takes at most one file".into());
+	}
+	if args.file {
+		match args.files.first().filter(|v| *v != "-") {
+			Some(path) => options.file = Some(FileInfo::from_path(path)?),
+			None => return Err("--file needs a file to don".into()),
+		}
+	}
+	let mut stdout = io::stdout().lock();
+	if args.archive {
+		let dir = args
+			.files
+			.first()
+			.ok_or("--archive needs a directory to don")?;
+		let archive = ArchiveBuilder::new().directory(dir)?.build();
+		don_read_with_progress(
+			&archive[..],
+			dict,
+			limit,
+			&options,
+			|message| writeln!(stdout, "{}", message),
+			progress(args.progress),
+		)?;
+		if args.progress {
+			eprintln!();
+		}
+		return Ok(());
+	}
+	// The input is armored as it's read, so files of any size can be armored
+	for input in inputs(&args.files)? {
+		don_read_with_progress(
+			input,
+			dict,
+			limit,
+			&options,
+			|message| writeln!(stdout, "{}", message),
+			progress(args.progress),
+		)?;
+	}
+	if args.progress {
+		eprintln!();
+	}
+	Ok(())
+}
+
+fn run_doff(args: Args, dict: &Mappings<'_>) -> Result<(), Box<dyn Error>> {
+	let mut messages = vec![];
+	for input in inputs(&args.files)? {
+		for line in BufReader::new(input).lines() {
+			let line = line?;
+			if !line.trim().is_empty() {
+				messages.push(line);
+			}
+		}
+	}
+	if args.archive {
+		let data = doff(&messages, dict)?;
+		ArchiveReader::new(&data)?.extract(".")?;
+		return Ok(());
+	}
+	if !args.file {
+		doff_into_with_progress(
+			&messages,
+			dict,
+			&mut io::stdout().lock(),
+			progress(args.progress),
+		)?;
+		if args.progress {
+			eprintln!();
+		}
+		return Ok(());
+	}
+	let (data, report) = doff_with_report(&messages, dict)?;
+	let info = report.file.ok_or("the armor doesn't have a file name")?;
+	// Only the name is used, so armor can't write anywhere but the current directory
+	let name = Path::new(&info.name)
+		.file_name()
+		.ok_or("the armor's file name is empty")?;
+	let mut file = OpenOptions::new().write(true).create_new(true).open(name)?;
+	file.write_all(&data)?;
+	if let Some(modified) = info.modified_time() {
+		file.set_modified(modified)?;
+	}
+	Ok(())
+}
+
+fn main() {
+	let mut args = std::env::args().skip(1);
+	let command = match args.next().as_deref() {
+		Some("don") => Command::Don,
+		Some("doff") => Command::Doff,
+		Some("fingerprint") => Command::Fingerprint,
+		_ => {
+			eprintln!("{}", USAGE);
+			exit(2)
+		}
+	};
+	let args = match parse(args) {
+		Ok(args) => args,
+		Err(e) => {
+			eprintln!("caw: {}\n{}", e, USAGE);
+			exit(2)
+		}
+	};
+	if let Err(e) = run(command, args) {
+		eprintln!("caw: {}", e);
+		exit(1)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{parse, Args};
+	use courier_caw::{Platform, RotationPeriod};
+
+	fn args(args: &[&str]) -> Vec<String> {
+		args.iter().map(|v| v.to_string()).collect()
+	}
+
+	#[test]
+	fn parsing() {
+		assert_eq!(
+			parse(args(&["--seed", "69", "--limit", "280", "message.txt"])),
+			Ok(Args {
+				seed: 69,
+				limit: Some(280),
+				platform: None,
+				rotation: RotationPeriod::Daily,
+				wordlist: None,
+				file: false,
+				archive: false,
+				progress: false,
+				files: vec!["message.txt".to_string()],
+			})
+		);
+		assert!(parse(args(&["--limit", "280"])).is_err());
+		assert!(parse(args(&["--seed", "nice"])).is_err());
+		assert!(parse(args(&["--seed", "69", "--verbose"])).is_err());
+		assert_eq!(
+			parse(args(&["--seed", "69", "--rotation", "weekly"])).map(|v| v.rotation),
+			Ok(RotationPeriod::Weekly)
+		);
+		assert!(parse(args(&["--seed", "69", "--rotation", "yearly"])).is_err());
+		assert_eq!(
+			parse(args(&["--seed", "69", "--platform", "mastodon"])).map(|v| v.platform),
+			Ok(Some(Platform::Mastodon))
+		);
+		assert_eq!(
+			parse(args(&["--seed", "69", "--wordlist", "words.txt"])).map(|v| v.wordlist),
+			Ok(Some("words.txt".to_string()))
+		);
+		assert_eq!(
+			parse(args(&["--seed", "69", "--file", "notes.txt"])).map(|v| v.file),
+			Ok(true)
+		);
+		assert_eq!(
+			parse(args(&["--seed", "69", "--archive", "notes"])).map(|v| v.archive),
+			Ok(true)
+		);
+		assert_eq!(
+			parse(args(&["--seed", "69", "--progress"])).map(|v| v.progress),
+			Ok(true)
+		);
+		let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+		                abandon abandon about";
+		assert_eq!(
+			parse(args(&["--mnemonic", mnemonic])).map(|v| v.seed),
+			Ok(0)
+		);
+	}
+}
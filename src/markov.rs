@@ -0,0 +1,95 @@
+//! Choosing between homophones so that neighbouring words look like they belong together.
+
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::{DictMappings, Dictionary};
+
+/// Word and word pair counts from a sample of real text, used by `don_with_model`.
+///
+/// Both parties don't need the same model, since the choice of homophone doesn't change
+/// what a word means.
+#[derive(Debug, Clone, Default)]
+pub struct BigramModel {
+	unigrams: HashMap<String, u32>,
+	bigrams: HashMap<String, HashMap<String, u32>>,
+}
+
+impl BigramModel {
+	/// Count the words in `text`, which are its runs of letters, ignoring case.
+	pub fn train(text: &str) -> BigramModel {
+		let mut model = BigramModel::default();
+		let mut previous: Option<String> = None;
+		for word in text
+			.split(|c: char| !c.is_alphabetic())
+			.filter(|v| !v.is_empty())
+		{
+			let word = word.to_lowercase();
+			*model.unigrams.entry(word.clone()).or_default() += 1;
+			if let Some(previous) = previous {
+				*model
+					.bigrams
+					.entry(previous)
+					.or_default()
+					.entry(word.clone())
+					.or_default() += 1;
+			}
+			previous = Some(word);
+		}
+		model
+	}
+	/// How much more likely `next` is to follow `previous` than a word the model has never
+	/// seen.
+	fn weight(&self, previous: Option<&str>, next: &str) -> f64 {
+		let bigram = previous
+			.and_then(|previous| self.bigrams.get(previous))
+			.and_then(|nexts| nexts.get(next))
+			.copied()
+			.unwrap_or(0);
+		let unigram = self.unigrams.get(next).copied().unwrap_or(0);
+		1.0 + unigram as f64 + 100.0 * bigram as f64
+	}
+	/// Pick one of the dictionary indices which mean `value`, favouring ones which are likely
+	/// to follow `previous`.
+	pub(crate) fn choose<D: Dictionary>(
+		&self,
+		dict: &DictMappings<D>,
+		previous: Option<&str>,
+		value: u16,
+		rng: &mut impl Rng,
+	) -> u32 {
+		let candidates: Vec<(u32, f64)> = dict
+			.words_for(value)
+			.map(|index| (index, self.weight(previous, dict.dictionary.word(index))))
+			.collect();
+		let total: f64 = candidates.iter().map(|(_, weight)| weight).sum();
+		let mut point = rng.gen_range(0.0..total);
+		for (index, weight) in &candidates {
+			if point < *weight {
+				return *index;
+			}
+			point -= weight;
+		}
+		candidates.last().unwrap().0
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::BigramModel;
+	use crate::{today, DictMappings, Dictionary, Options};
+
+	#[test]
+	fn follows_the_model() {
+		let dict = DictMappings::from_seed(69, &today());
+		assert_eq!(dict.homophones.len(), 4 * 65536);
+		let favourite = dict.dictionary.word(dict.homophones[2 * 65536 + 0x6361]);
+		let model = BigramModel::train(&format!("{} ", favourite).repeat(10));
+		let test_data = "cacacacacaca".as_bytes();
+		let messages = crate::don_with_model(test_data, &dict, 100, &Options::default(), &model);
+		let words = messages.iter().flat_map(|v| v.split(' '));
+		assert!(words.filter(|v| *v == favourite).count() > 3);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+	}
+}
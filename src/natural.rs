@@ -0,0 +1,177 @@
+//! Dressing messages up as sentences, and undoing it.
+//!
+//! Rendering only ever capitalizes the first letter of a word and adds a period or comma
+//! after it, and only when `lookup` would still find the original word. Decoding always
+//! normalizes, so it doesn't need to know whether a message was rendered, and ignores case,
+//! so messages survive keyboards which capitalize them.
+
+use rand::prelude::SliceRandom;
+use rand::Rng;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{DictMappings, Dictionary};
+
+/// The punctuation which rendering can add.
+pub(crate) const PUNCTUATION: &[char] = &['.', ','];
+
+/// The punctuation which decoding strips from tokens unless told otherwise, which is what
+/// rendering adds along with what people and chat clients tend to add around words, and the
+/// variation selector which keyboards add to emoji.
+pub(crate) const DEFAULT_PUNCTUATION: &[char] = &[
+	'.', ',', '!', '?', ';', ':', '"', '\'', '`', '(', ')', '[', ']', '{', '}', '*', '_', '~',
+	'\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{ab}', '\u{bb}', '\u{2026}', '\u{a1}',
+	'\u{bf}', '\u{fe0f}',
+];
+
+/// Split a message into tokens at `separator` and at any whitespace, so messages still split
+/// after pasting adds tabs, doubled spaces, non-breaking spaces or line breaks. Empty tokens
+/// are left out. An empty separator splits the message at every grapheme cluster instead.
+pub(crate) fn tokens<'t>(
+	text: &'t str,
+	separator: &'t str,
+) -> Box<dyn Iterator<Item = &'t str> + 't> {
+	if separator.is_empty() {
+		return Box::new(text.split_whitespace().flat_map(|v| v.graphemes(true)));
+	}
+	Box::new(text.split(separator).flat_map(str::split_whitespace))
+}
+
+/// Find the dictionary index of a token which might have been rendered, decorated with
+/// `punctuation` at either end, or had its case changed.
+pub(crate) fn lookup<D: Dictionary>(
+	dict: &DictMappings<D>,
+	token: &str,
+	punctuation: &[char],
+) -> Option<u32> {
+	if let Some(index) = dict.dictionary.index_of_any_case(token) {
+		return Some(index);
+	}
+	dict.dictionary
+		.index_of_any_case(token.trim_matches(punctuation))
+}
+
+/// Find the dictionary index of a token which is written exactly as the dictionary has it,
+/// apart from `punctuation` at either end.
+pub(crate) fn lookup_exact<D: Dictionary>(
+	dict: &DictMappings<D>,
+	token: &str,
+	punctuation: &[char],
+) -> Option<u32> {
+	if let Some(index) = dict.dictionary.index_of(token) {
+		return Some(index);
+	}
+	dict.dictionary.index_of(token.trim_matches(punctuation))
+}
+
+/// The fillers which decoding will skip, which are the ones that aren't words, even once
+/// punctuation is stripped, and don't contain the separator or whitespace.
+pub(crate) fn usable_fillers<'f, D: Dictionary>(
+	dict: &DictMappings<D>,
+	fillers: &'f [String],
+	separator: &str,
+) -> Vec<&'f str> {
+	fillers
+		.iter()
+		.map(|v| v.as_str())
+		.filter(|v| {
+			!v.is_empty()
+				&& !v.contains(separator)
+				&& !v.contains(char::is_whitespace)
+				&& lookup(dict, v, DEFAULT_PUNCTUATION).is_none()
+		})
+		.collect()
+}
+
+/// Perhaps choose a filler to go after a word.
+pub(crate) fn filler<'f, T>(fillers: &'f [T], rng: &mut impl Rng) -> Option<&'f T> {
+	if fillers.is_empty() || !rng.gen_ratio(1, 8) {
+		return None;
+	}
+	fillers.choose(rng)
+}
+
+/// Capitalize the first letter of `word`, if that doesn't change its length.
+fn capitalize(word: &str) -> Option<String> {
+	let mut chars = word.chars();
+	let first = chars.next()?;
+	let upper: String = first.to_uppercase().collect();
+	if upper.len() != first.len_utf8() {
+		return None;
+	}
+	Some(upper + chars.as_str())
+}
+
+/// Join the words of a message into sentences, with `separator` between them. The result is
+/// at most one byte longer per word than joining them with the separator.
+pub(crate) fn render<D: Dictionary>(
+	dict: &DictMappings<D>,
+	words: &[&str],
+	separator: &str,
+	rng: &mut impl Rng,
+) -> String {
+	let mut result = String::new();
+	render_into(dict, words, separator, rng, &mut result);
+	result
+}
+
+/// Join the words of a message into sentences at the end of `result`.
+pub(crate) fn render_into<D: Dictionary>(
+	dict: &DictMappings<D>,
+	words: &[&str],
+	separator: &str,
+	rng: &mut impl Rng,
+	result: &mut String,
+) {
+	let mut sentence_start = true;
+	for (position, word) in words.iter().enumerate() {
+		let original = dict.dictionary.index_of(word);
+		let mut token = word.to_string();
+		if sentence_start {
+			if let Some(capitalized) = capitalize(word) {
+				if lookup(dict, &capitalized, PUNCTUATION) == original {
+					token = capitalized;
+				}
+			}
+		}
+		let punctuation = if position + 1 == words.len() || rng.gen_ratio(1, 8) {
+			Some('.')
+		} else if rng.gen_ratio(1, 8) {
+			Some(',')
+		} else {
+			None
+		};
+		sentence_start = false;
+		if let Some(punctuation) = punctuation {
+			let punctuated = format!("{}{}", token, punctuation);
+			if lookup(dict, &punctuated, PUNCTUATION) == original {
+				token = punctuated;
+				sentence_start = punctuation == '.';
+			}
+		}
+		if position != 0 {
+			result.push_str(separator);
+		}
+		result.push_str(&token);
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use crate::{today, DictMappings, Options};
+
+	#[test]
+	fn sentences() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			natural: true,
+			..Options::default()
+		};
+		let test_data = "Dressed up to look like somebody wrote it".as_bytes();
+		let messages = crate::don_with(test_data, &dict, 60, &options);
+		for message in &messages {
+			assert!(message.starts_with(char::is_uppercase));
+			assert!(message.ends_with('.'));
+		}
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+	}
+}
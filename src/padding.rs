@@ -0,0 +1,97 @@
+//! Nulls, which carry nothing, for padding messages out to the character limit and for
+//! mixing chaff in with the words which carry the data.
+
+use std::collections::BTreeMap;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::{DictMappings, Dictionary, LimitUnit, Options};
+
+/// Perhaps choose a null to go after a word, `percent` times in a hundred.
+pub(crate) fn chaff<'a, D: Dictionary>(
+	dict: &'a DictMappings<D>,
+	percent: u8,
+	rng: &mut impl Rng,
+) -> Option<&'a str> {
+	if dict.nulls.is_empty() || !rng.gen_ratio(percent.min(100) as u32, 100) {
+		return None;
+	}
+	dict.nulls.choose(rng).map(|v| dict.dictionary.word(*v))
+}
+
+/// Pads messages with nulls until they're as close to the limit as the nulls' lengths allow.
+pub(crate) struct Padder<'a> {
+	/// The nulls, by how much room each takes up along with the separator before it.
+	by_len: BTreeMap<usize, Vec<&'a str>>,
+	/// For each amount of room up to the limit, the length of the last null of a run which
+	/// fills it exactly, if any run does.
+	reached: Vec<Option<usize>>,
+	limit: usize,
+	unit: LimitUnit,
+	/// The length of the separator between words.
+	separator_len: usize,
+	/// How much longer each word might get once the message is rendered.
+	word_overhead: usize,
+}
+
+impl<'a> Padder<'a> {
+	pub(crate) fn new<D: Dictionary>(
+		dict: &'a DictMappings<D>,
+		limit: usize,
+		options: &Options,
+	) -> Padder<'a> {
+		let unit = options.limit_unit;
+		let separator_len = unit.measure(options.separator.as_str());
+		let word_overhead = if options.natural { 1 } else { 0 };
+		let mut by_len: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+		for null in &dict.nulls {
+			let word = dict.dictionary.word(*null);
+			by_len
+				.entry(separator_len + unit.measure(word) + word_overhead)
+				.or_default()
+				.push(word);
+		}
+		// Nothing is needed to fill no room at all
+		let mut reached = vec![None; limit + 1];
+		reached[0] = Some(0);
+		for room in 1..=limit {
+			reached[room] = by_len
+				.keys()
+				.copied()
+				.find(|len| *len <= room && reached[room - len].is_some());
+		}
+		Padder {
+			by_len,
+			reached,
+			limit,
+			unit,
+			separator_len,
+			word_overhead,
+		}
+	}
+	/// Add nulls, picked at random, to the end of a message's words, filling as much of the
+	/// room left under the limit as possible.
+	pub(crate) fn pad(&self, words: &mut Vec<&'a str>, rng: &mut impl Rng) {
+		let len = words
+			.iter()
+			.map(|v| self.unit.measure(v) + self.word_overhead)
+			.sum::<usize>()
+			+ self.separator_len * words.len().saturating_sub(1);
+		let Some(room) = self.limit.checked_sub(len) else {
+			return;
+		};
+		let mut room = (0..=room)
+			.rev()
+			.find(|v| self.reached[*v].is_some())
+			.unwrap();
+		let mut lens = vec![];
+		while room != 0 {
+			let len = self.reached[room].unwrap();
+			lens.push(len);
+			room -= len;
+		}
+		lens.shuffle(rng);
+		words.extend(lens.iter().map(|v| *self.by_len[v].choose(rng).unwrap()));
+	}
+}
@@ -0,0 +1,190 @@
+//! Recovering as much as possible from armor which is missing some of its messages.
+
+use std::ops::Range;
+
+use crate::chunk::Joiner;
+use crate::header::unpadded_len;
+use crate::{assemble, doff_message, CawError, DictMappings, Dictionary, Tokenizer, Tolerances};
+
+/// The data which could be recovered from damaged armor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recovered {
+	/// The bytes of every message which could be read, in order, with nothing in place of the
+	/// missing ones.
+	pub data: Vec<u8>,
+	/// Where messages are missing from the data, in order.
+	pub gaps: Vec<Gap>,
+	/// The positions in the input of messages which couldn't be read, and were left out.
+	pub unreadable: Vec<usize>,
+}
+
+/// A run of messages which are missing from recovered data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gap {
+	/// The position in the data where the missing bytes belong.
+	pub at: usize,
+	/// The sequence numbers of the missing messages. If the armor doesn't say how many
+	/// messages there are and the last one is missing, only the first sequence number after
+	/// the messages which arrived is included.
+	pub messages: Range<u32>,
+}
+
+/// Doff armor, recovering whatever can be recovered if some of the messages are missing or
+/// can't be read. Messages which can't be read are left out and reported, and the data of
+/// the rest is returned with a gap wherever messages are missing.
+///
+/// Nobody knows how much data a missing message carried, so gaps are empty. With 12-bit
+/// chunks, the data after a gap is taken to start on a whole byte, which is half a byte off
+/// if an odd number of chunks went missing. If the first message is missing, so is the
+/// header, and everything else is taken to be plain data, so any checksum or padding is left
+/// at the end, and armor with a nonce or keystream can't be recovered at all.
+///
+/// Compressed, encrypted, authenticated, error corrected or keystreamed armor can only be read
+/// whole, so missing messages are as much of an error as they are for `doff`, although error
+/// correction might still make up for them. So are messages from more than one armor.
+pub fn doff_partial<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+) -> Result<Recovered, CawError> {
+	let tokenizer = Tokenizer::default();
+	let mut unreadable = vec![];
+	let mut decoded = vec![];
+	for (message, text) in messages.iter().enumerate() {
+		match doff_message(message, text, dict, &Tolerances::default(), &tokenizer) {
+			Ok(v) => decoded.push(v),
+			Err(_) => unreadable.push(message),
+		}
+	}
+	let header = decoded.iter().find_map(|v| v.header.clone());
+	// A word which was skipped is only a filler if the armor has them, and otherwise leaves
+	// the rest of its message out of place
+	if !header.as_ref().is_some_and(|v| v.fillers) {
+		decoded.retain(|v| {
+			if v.skipped.is_some() {
+				unreadable.push(v.message);
+			}
+			v.skipped.is_none()
+		});
+		unreadable.sort_unstable();
+	}
+	if decoded.iter().any(|v| v.ids != decoded[0].ids) {
+		return Err(CawError::MixedMessages);
+	}
+	// The sort is stable, so the first copy of a message is the one which is kept
+	decoded.sort_by_key(|v| v.sequence);
+	decoded.dedup_by_key(|v| v.sequence);
+	let known = header.clone().unwrap_or_default();
+	let missing = known.missing(&decoded);
+	// Nobody knows where in the keystream the chunks after a gap start
+	if header.is_some() && (missing.is_empty() || known.is_whole_payload() || known.keystream) {
+		let (data, _) = assemble(dict, decoded)?;
+		return Ok(Recovered {
+			data,
+			gaps: vec![],
+			unreadable,
+		});
+	}
+	let size = dict.chunk_size();
+	let mut data = vec![];
+	let mut gaps = vec![];
+	let mut joiner = Joiner::new(size);
+	let mut mask = known.mask(dict);
+	let mut expected = 0;
+	for v in &decoded {
+		if v.sequence != expected {
+			gaps.push(Gap {
+				at: data.len(),
+				messages: expected..v.sequence,
+			});
+			joiner = Joiner::new(size);
+		}
+		for chunk in &v.data {
+			joiner.push(chunk ^ mask.next(), &mut data);
+		}
+		expected = v.sequence + 1;
+	}
+	match missing.last() {
+		Some(last) if *last >= expected => gaps.push(Gap {
+			at: data.len(),
+			messages: expected..last + 1,
+		}),
+		// Padding and checksums are at the very end, so they can only be removed if it arrived
+		_ if header.is_some() => {
+			if known.padded {
+				if let Ok(len) = unpadded_len(&data, size.group()) {
+					data.truncate(len);
+				}
+			}
+			if known.checksum {
+				data.truncate(data.len().saturating_sub(4));
+			}
+		}
+		_ => {}
+	}
+	Ok(Recovered {
+		data,
+		gaps,
+		unreadable,
+	})
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::{doff_partial, Gap};
+	use crate::{don_with, today, CawError, DictMappings, Options};
+
+	#[test]
+	fn partial() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data: Vec<u8> = (0..=254).collect();
+		let options = Options {
+			checksum: true,
+			..Options::default()
+		};
+		let mut messages = don_with(&test_data, &dict, 100, &options);
+		assert!(messages.len() > 4);
+		let whole = doff_partial(&messages, &dict).unwrap();
+		assert_eq!(whole.data, test_data);
+		assert!(whole.gaps.is_empty());
+		// The padding and checksum are still removed with a message missing from the middle
+		messages.remove(2);
+		messages.insert(1, "not armor at all".to_string());
+		let recovered = doff_partial(&messages, &dict).unwrap();
+		assert_eq!(recovered.unreadable, [1]);
+		assert_eq!(recovered.gaps.len(), 1);
+		let Gap { at, messages: gap } = recovered.gaps[0].clone();
+		assert_eq!(gap, 2..3);
+		let after = recovered.data.len() - at;
+		assert_eq!(recovered.data[..at], test_data[..at]);
+		assert_eq!(recovered.data[at..], test_data[test_data.len() - after..]);
+		// Without the first message, nothing is known about the header
+		messages.drain(..2);
+		let recovered = doff_partial(&messages, &dict).unwrap();
+		assert_eq!(
+			recovered.gaps[0],
+			Gap {
+				at: 0,
+				messages: 0..1
+			}
+		);
+		assert_eq!(recovered.gaps[1].messages, 2..3);
+		assert!(recovered.unreadable.is_empty());
+		// The header says how many messages come after the last one to arrive
+		let messages = don_with(&test_data, &dict, 100, &options);
+		let recovered = doff_partial(&messages[..2], &dict).unwrap();
+		assert_eq!(recovered.gaps[0].messages, 2..messages.len() as u32);
+		assert_eq!(recovered.data[..], test_data[..recovered.data.len()]);
+		// Compressed data can't be recovered in part
+		let options = Options {
+			compress: true,
+			..Options::default()
+		};
+		let test_data = "Compress me! ".repeat(100);
+		let mut messages = don_with(test_data.as_bytes(), &dict, 100, &options);
+		messages.remove(1);
+		assert!(matches!(
+			doff_partial(&messages, &dict),
+			Err(CawError::MissingFragments { .. })
+		));
+	}
+}
@@ -0,0 +1,218 @@
+//! Wordlists which know the part of speech of their words, so armor can follow a rhythm of
+//! nouns, verbs and adjectives instead of putting words in any order.
+
+use std::io::{self, BufRead};
+
+use chrono::Datelike;
+use rand::prelude::SliceRandom;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+
+use crate::dictionary::WordList;
+use crate::header::{FLAG_COUNT, TAG_COUNT};
+use crate::{derive_seed, inverse, ChunkSize, DictMappings, Dictionary};
+
+/// The parts of speech which armor can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PartOfSpeech {
+	/// A noun, which is each chunk's word in `DictMappings::words`.
+	Noun,
+	/// A verb, which is each chunk's word in the first set of homophones.
+	Verb,
+	/// An adjective, which is each chunk's word in the second set of homophones.
+	Adjective,
+}
+
+impl PartOfSpeech {
+	const ALL: [PartOfSpeech; 3] = [
+		PartOfSpeech::Noun,
+		PartOfSpeech::Verb,
+		PartOfSpeech::Adjective,
+	];
+	/// The set of words which mean each chunk in this part of speech: 0 for `words`, and one
+	/// more than the set of homophones otherwise.
+	pub(crate) fn set(self) -> usize {
+		self as usize
+	}
+}
+
+/// A wordlist whose words are tagged with their parts of speech.
+#[derive(Debug, Clone)]
+pub struct TaggedWordList {
+	words: WordList,
+	parts: Vec<Option<PartOfSpeech>>,
+}
+
+impl TaggedWordList {
+	/// Build a wordlist from words and their parts of speech. Untagged words can only be used
+	/// as markers and tags.
+	pub fn new(words: Vec<(String, Option<PartOfSpeech>)>) -> TaggedWordList {
+		let (words, parts) = words.into_iter().unzip();
+		TaggedWordList {
+			words: WordList::new(words),
+			parts,
+		}
+	}
+	/// Read a wordlist with one word to a line, followed by a tab and `noun`, `verb` or
+	/// `adjective` if it's tagged. Blank lines are skipped, and any other tag is an error.
+	pub fn read(reader: impl BufRead) -> io::Result<TaggedWordList> {
+		let mut words = vec![];
+		for line in reader.lines() {
+			let line = line?;
+			if line.trim().is_empty() {
+				continue;
+			}
+			let (word, part) = match line.split_once('\t') {
+				Some((word, part)) => (word, Some(part.trim())),
+				None => (line.as_str(), None),
+			};
+			let part = match part {
+				None => None,
+				Some("noun") => Some(PartOfSpeech::Noun),
+				Some("verb") => Some(PartOfSpeech::Verb),
+				Some("adjective") => Some(PartOfSpeech::Adjective),
+				Some(part) => {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("unknown part of speech {:?}", part),
+					))
+				}
+			};
+			words.push((word.trim().to_string(), part));
+		}
+		Ok(TaggedWordList::new(words))
+	}
+	/// The part of speech of the word at `index`, if it's tagged.
+	pub fn part_of_speech(&self, index: u32) -> Option<PartOfSpeech> {
+		self.parts[index as usize]
+	}
+	/// The largest chunk size which every part of speech has enough words for, even if the
+	/// markers, tags and flags are all taken from it.
+	fn chunk_size(&self) -> Option<ChunkSize> {
+		let fewest = PartOfSpeech::ALL
+			.iter()
+			.map(|part| self.parts.iter().filter(|v| **v == Some(*part)).count())
+			.min()
+			.unwrap_or(0);
+		[ChunkSize::Sixteen, ChunkSize::Twelve, ChunkSize::Eight]
+			.iter()
+			.copied()
+			.find(|v| fewest >= 15 + TAG_COUNT + FLAG_COUNT + v.values())
+	}
+}
+
+impl Dictionary for TaggedWordList {
+	fn len(&self) -> usize {
+		self.words.len()
+	}
+	fn word(&self, index: u32) -> &str {
+		self.words.word(index)
+	}
+	fn index_of(&self, word: &str) -> Option<u32> {
+		self.words.index_of(word)
+	}
+	fn index_of_any_case(&self, word: &str) -> Option<u32> {
+		self.words.index_of_any_case(word)
+	}
+}
+
+impl DictMappings<TaggedWordList> {
+	/// Build mappings with a word for every chunk in each part of speech, from a shared seed
+	/// and the current date. The nouns are the chunks' `words`, and the verbs and adjectives
+	/// are the two sets of homophones, so any armor made from these mappings can be doffed
+	/// as usual, and `Options::rhythm` picks the part of speech of each word.
+	///
+	/// The whole list is shuffled like any other dictionary. The markers, tags and flags are
+	/// the first 95 words of it, and each part of speech's words are the next words tagged
+	/// with it, in order. The chunk size is the largest which each part of speech would have
+	/// enough words for even if every marker, tag and flag were taken from it. Panics if
+	/// that's smaller than 8 bits, which takes 351 words of each part of speech.
+	pub fn from_tagged(
+		dictionary: TaggedWordList,
+		seed: u128,
+		date: &impl Datelike,
+	) -> DictMappings<TaggedWordList> {
+		use sha2::{Digest, Sha256};
+		let seed = format!("{}{}{}{}", seed, date.year(), date.month(), date.day());
+		let chunk_size = dictionary
+			.chunk_size()
+			.expect("too few words of some part of speech");
+		let mut rng = Pcg64::from_seed(derive_seed::<Pcg64>(&seed));
+		let mut list: Vec<u32> = (0..dictionary.len() as u32).collect();
+		list.shuffle(&mut rng);
+		let (reserved, rest) = list.split_at(15 + TAG_COUNT + FLAG_COUNT);
+		let mut sets = PartOfSpeech::ALL.iter().map(|part| {
+			rest.iter()
+				.copied()
+				.filter(|v| dictionary.part_of_speech(*v) == Some(*part))
+				.take(chunk_size.values())
+				.collect::<Vec<u32>>()
+		});
+		let words = sets.next().unwrap();
+		let homophones: Vec<u32> = sets.flatten().collect();
+		DictMappings {
+			inverse: inverse(dictionary.len(), &words, &homophones),
+			begin: reserved[0..5].to_vec(),
+			end: reserved[5..10].to_vec(),
+			fragment: reserved[10..15].to_vec(),
+			tags: reserved[15..15 + TAG_COUNT].to_vec(),
+			flags: reserved[15 + TAG_COUNT..].to_vec(),
+			nulls: vec![],
+			frequencies: vec![],
+			dictionary,
+			words,
+			homophones,
+			key: Sha256::digest(format!("caw encryption key {}", seed)).into(),
+			mac_key: Sha256::digest(format!("caw authentication key {}", seed)).into(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{PartOfSpeech, TaggedWordList};
+	use crate::{today, ChunkSize, DictMappings, Options};
+
+	#[test]
+	fn rhythm() {
+		let text: String = (0..400)
+			.map(|n| {
+				format!(
+					"noun{0}\tnoun\nverb{0}\tverb\nadj{0}\tadjective\nother{0}\n",
+					n
+				)
+			})
+			.collect();
+		let list = TaggedWordList::read(text.as_bytes()).unwrap();
+		assert_eq!(list.part_of_speech(2), Some(PartOfSpeech::Adjective));
+		assert_eq!(list.part_of_speech(3), None);
+		let dict = DictMappings::from_tagged(list, 69, &today());
+		assert_eq!(dict.chunk_size(), ChunkSize::Eight);
+		let test_data = "Colorless green ideas sleep furiously".as_bytes();
+		let rhythm = vec![
+			PartOfSpeech::Adjective,
+			PartOfSpeech::Noun,
+			PartOfSpeech::Verb,
+		];
+		let options = Options {
+			rhythm: rhythm.clone(),
+			..Options::default()
+		};
+		let messages = crate::don_with(test_data, &dict, 1000, &options);
+		assert_eq!(messages.len(), 1);
+		// The data words come between the header and the end marker
+		let words: Vec<&str> = messages[0].split(' ').collect();
+		let data = &words[words.len() - 1 - test_data.len()..words.len() - 1];
+		for (position, word) in data.iter().enumerate() {
+			let prefix = ["noun", "verb", "adj"][rhythm[position % 3].set()];
+			assert!(
+				word.starts_with(prefix),
+				"{} isn't a {:?}",
+				word,
+				rhythm[position % 3]
+			);
+		}
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+		assert!(TaggedWordList::read("word\tadverb".as_bytes()).is_err());
+	}
+}
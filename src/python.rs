@@ -0,0 +1,53 @@
+//! Bindings for Python, built with the `python` feature as the `courier_caw` package.
+//!
+//! Donning and doffing let go of the GIL, so other Python threads can run meanwhile.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::{today, CawError};
+
+/// Today's mappings for a seed.
+#[pyclass(name = "DictMappings", frozen)]
+pub struct Mappings(crate::DictMappings);
+
+#[pymethods]
+impl Mappings {
+	/// Build today's mappings from a 128-bit seed.
+	#[new]
+	fn new(seed: u128) -> Mappings {
+		Mappings(crate::DictMappings::from_seed(seed, &today()))
+	}
+}
+
+impl From<CawError> for PyErr {
+	fn from(e: CawError) -> PyErr {
+		PyValueError::new_err(e.to_string())
+	}
+}
+
+/// Don armor, returning a list of messages.
+#[pyfunction]
+fn don(py: Python<'_>, data: &[u8], mappings: &Mappings, character_limit: usize) -> Vec<String> {
+	py.allow_threads(|| crate::don(data, &mappings.0, character_limit))
+}
+
+/// Doff a list of messages, returning the recovered bytes.
+#[pyfunction]
+fn doff<'py>(
+	py: Python<'py>,
+	messages: Vec<String>,
+	mappings: &Mappings,
+) -> PyResult<Bound<'py, PyBytes>> {
+	let data = py.allow_threads(|| crate::doff(&messages, &mappings.0))?;
+	Ok(PyBytes::new(py, &data))
+}
+
+#[pymodule]
+fn courier_caw(module: &Bound<'_, PyModule>) -> PyResult<()> {
+	module.add_class::<Mappings>()?;
+	module.add_function(wrap_pyfunction!(don, module)?)?;
+	module.add_function(wrap_pyfunction!(doff, module)?)?;
+	Ok(())
+}
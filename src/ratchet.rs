@@ -0,0 +1,126 @@
+//! Mappings which are forward secret, because each day's are derived from the day before's.
+
+use chrono::{Datelike, NaiveDate};
+use sha2::{Digest, Sha256};
+
+use crate::{DictMappings, Dictionary, Language};
+
+/// A hash ratchet which the mappings are built from instead of the seed and date.
+///
+/// Each day's state is the SHA-256 hash of the previous day's, so moving the ratchet forward
+/// throws away everything needed to rebuild earlier mappings. Someone who later learns the
+/// seed or the current state can't read armor from before it. Both parties have to start
+/// their ratchets from the same seed on the same date, and keep them with `to_bytes`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Ratchet {
+	state: [u8; 32],
+	date: NaiveDate,
+}
+
+/// The state is secret, so it's left out.
+impl std::fmt::Debug for Ratchet {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Ratchet")
+			.field("date", &self.date)
+			.finish_non_exhaustive()
+	}
+}
+
+impl Ratchet {
+	/// Start a ratchet from a shared seed on `date`. The seed should be discarded afterwards,
+	/// or it can be used to rebuild every state.
+	pub fn new(seed: u128, date: NaiveDate) -> Ratchet {
+		let seed = format!(
+			"caw ratchet {}{}{}{}",
+			seed,
+			date.year(),
+			date.month(),
+			date.day()
+		);
+		Ratchet {
+			state: Sha256::digest(seed).into(),
+			date,
+		}
+	}
+	/// The date which the current state is for.
+	pub fn date(&self) -> NaiveDate {
+		self.date
+	}
+	/// Move on to the next day, forgetting the current state.
+	pub fn advance(&mut self) {
+		self.state = Sha256::digest(self.state).into();
+		self.date = self.date.succ_opt().expect("the ratchet ran out of dates");
+	}
+	/// Move on to `date`. Returns false without changing anything if `date` has passed, since
+	/// earlier states can't be recovered.
+	pub fn advance_to(&mut self, date: NaiveDate) -> bool {
+		if date < self.date {
+			return false;
+		}
+		while self.date < date {
+			self.advance();
+		}
+		true
+	}
+	/// The English mappings for the current date.
+	pub fn mappings(&self) -> DictMappings {
+		self.mappings_in(Language::English)
+	}
+	/// The mappings for any dictionary for the current date.
+	///
+	/// Panics if the dictionary has fewer than 335 words.
+	pub fn mappings_in<D: Dictionary>(&self, dictionary: D) -> DictMappings<D> {
+		let state: String = self.state.iter().map(|v| format!("{:02x}", v)).collect();
+		DictMappings::from_seed_string(dictionary, &format!("ratchet {}", state))
+	}
+	/// The ratchet as 36 bytes: the state, then the date as a big-endian count of days since
+	/// the first of January in the year 1. Anyone with these bytes can read that day's armor
+	/// and every later day's, so they should be kept as safe as the seed.
+	pub fn to_bytes(&self) -> [u8; 36] {
+		let mut bytes = [0; 36];
+		bytes[..32].copy_from_slice(&self.state);
+		bytes[32..].copy_from_slice(&self.date.num_days_from_ce().to_be_bytes());
+		bytes
+	}
+	/// Restore a ratchet saved by `to_bytes`. Returns `None` if the date is impossible.
+	pub fn from_bytes(bytes: &[u8; 36]) -> Option<Ratchet> {
+		let mut state = [0; 32];
+		state.copy_from_slice(&bytes[..32]);
+		let days = i32::from_be_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
+		Some(Ratchet {
+			state,
+			date: NaiveDate::from_num_days_from_ce_opt(days)?,
+		})
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use chrono::NaiveDate;
+
+	use super::Ratchet;
+	use crate::DictMappings;
+
+	#[test]
+	fn ratchet() {
+		let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+		let mut sender = Ratchet::new(69, start);
+		let mut receiver = Ratchet::from_bytes(&sender.to_bytes()).unwrap();
+		assert_eq!(receiver, sender);
+		let first = sender.mappings();
+		assert_ne!(first.words, DictMappings::from_seed(69, &start).words);
+		sender.advance();
+		assert_eq!(sender.date(), start.succ_opt().unwrap());
+		assert_ne!(sender.mappings().words, first.words);
+		let test_data = "Nobody can read yesterday's mail".as_bytes();
+		let messages = crate::don(test_data, &sender.mappings(), 50);
+		assert!(receiver.advance_to(sender.date()));
+		assert_eq!(
+			crate::doff(&messages, &receiver.mappings()).unwrap(),
+			test_data
+		);
+		// Earlier days are gone
+		assert!(!receiver.advance_to(start));
+		assert_eq!(receiver, sender);
+	}
+}
@@ -0,0 +1,180 @@
+//! Putting armor back together one message at a time, in whatever order the messages arrive.
+
+use crate::header::Ids;
+use crate::{
+	assemble, doff_message, CawError, Decoded, DictMappings, Dictionary, Header, Tokenizer,
+	Tolerances,
+};
+
+/// What became of a message pushed to a `Reassembler`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushResult {
+	/// The message was kept, and the armor is still waiting for these messages, by sequence
+	/// number. Until the first message and either the last one or a fragment count have
+	/// arrived, nobody knows how many messages there are, so only the next sequence number
+	/// after the last one to arrive is listed.
+	Pending {
+		/// The sequence numbers of the messages which haven't arrived yet.
+		missing: Vec<u32>,
+	},
+	/// A message with the same sequence number had already arrived, so this one was ignored.
+	Duplicate,
+	/// The message completed the armor, and this is its data.
+	Complete(Vec<u8>),
+}
+
+/// Collects the messages of one armor as they arrive, in any order, and doffs it as soon as
+/// every message is there, so callers don't have to gather and sort messages themselves or
+/// guess whether any are missing.
+///
+/// Unlike `DoffReader`, nothing is read until the armor is complete, so compressed, encrypted
+/// and error corrected armor can be reassembled too. Once the armor has been doffed, or has
+/// failed to doff, the reassembler starts over, ready for the next armor.
+pub struct Reassembler<'a, D: Dictionary> {
+	dict: &'a DictMappings<D>,
+	/// The messages which have arrived, sorted by sequence number.
+	decoded: Vec<Decoded>,
+	/// The header, once the first message has arrived.
+	header: Option<Header>,
+	/// The IDs of the first message to arrive.
+	ids: Option<Ids>,
+	/// How many messages have been pushed so far.
+	received: usize,
+	/// How messages are split into words.
+	tokenizer: Tokenizer,
+}
+
+impl<'a, D: Dictionary> Reassembler<'a, D> {
+	/// Create a reassembler with no messages.
+	pub fn new(dict: &'a DictMappings<D>) -> Reassembler<'a, D> {
+		Reassembler::with_tokenizer(dict, Tokenizer::default())
+	}
+	/// Create a reassembler which splits messages into words with `tokenizer`.
+	pub fn with_tokenizer(dict: &'a DictMappings<D>, tokenizer: Tokenizer) -> Reassembler<'a, D> {
+		Reassembler {
+			dict,
+			decoded: vec![],
+			header: None,
+			ids: None,
+			received: 0,
+			tokenizer,
+		}
+	}
+	/// Feed a message to the reassembler. Errors refer to messages by the order they were
+	/// pushed in since the reassembler last started over. A message which can't be read, or
+	/// which belongs to other armor, is an error and is left out, and the messages which
+	/// already arrived are kept.
+	pub fn push(&mut self, message: &str) -> Result<PushResult, CawError> {
+		let decoded = doff_message(
+			self.received,
+			message,
+			self.dict,
+			&Tolerances::default(),
+			&self.tokenizer,
+		)?;
+		if *self.ids.get_or_insert(decoded.ids) != decoded.ids {
+			return Err(CawError::MixedMessages);
+		}
+		self.received += 1;
+		let Err(position) = self
+			.decoded
+			.binary_search_by_key(&decoded.sequence, |v| v.sequence)
+		else {
+			return Ok(PushResult::Duplicate);
+		};
+		if let Some(header) = &decoded.header {
+			self.header = Some(header.clone());
+		}
+		self.decoded.insert(position, decoded);
+		let missing = self.missing();
+		if self.header.is_none() || !missing.is_empty() {
+			return Ok(PushResult::Pending { missing });
+		}
+		let decoded = std::mem::take(&mut self.decoded);
+		self.clear();
+		let (data, _) = assemble(self.dict, decoded)?;
+		Ok(PushResult::Complete(data))
+	}
+	/// The sequence numbers of the messages which haven't arrived yet, as far as anyone knows.
+	pub fn missing(&self) -> Vec<u32> {
+		let header = self.header.clone().unwrap_or_default();
+		header.missing(&self.decoded)
+	}
+	/// Forget every message, ready for the next armor.
+	pub fn clear(&mut self) {
+		self.decoded.clear();
+		self.header = None;
+		self.ids = None;
+		self.received = 0;
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::{PushResult, Reassembler};
+	use crate::{don, don_with, today, CawError, DictMappings, Options};
+
+	#[test]
+	fn reassembler() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data: Vec<u8> = (0..=255).collect();
+		let messages = don(&test_data, &dict, 100);
+		assert!(messages.len() > 3);
+		let mut reassembler = Reassembler::new(&dict);
+		// Without the first message, nobody knows how many there are
+		assert_eq!(
+			reassembler.push(&messages[2]).unwrap(),
+			PushResult::Pending {
+				missing: vec![0, 1, 3]
+			}
+		);
+		assert_eq!(
+			reassembler.push(&messages[2]).unwrap(),
+			PushResult::Duplicate
+		);
+		let last = messages.len() as u32 - 1;
+		let PushResult::Pending { missing } = reassembler.push(&messages[0]).unwrap() else {
+			panic!("the armor isn't complete yet");
+		};
+		assert_eq!(
+			missing,
+			std::iter::once(1).chain(3..=last).collect::<Vec<_>>()
+		);
+		assert!(matches!(
+			reassembler.push("not armor at all"),
+			Err(CawError::MissingMarker { .. })
+		));
+		for message in messages[3..].iter().rev() {
+			assert!(matches!(
+				reassembler.push(message).unwrap(),
+				PushResult::Pending { .. }
+			));
+		}
+		assert_eq!(reassembler.missing(), [1]);
+		assert_eq!(
+			reassembler.push(&messages[1]).unwrap(),
+			PushResult::Complete(test_data.clone())
+		);
+		// The reassembler starts over for the next armor, which can need the whole payload
+		assert!(reassembler.missing().contains(&0));
+		let options = Options {
+			compress: true,
+			checksum: true,
+			..Options::default()
+		};
+		let test_data = "Compress me! ".repeat(100);
+		let messages = don_with(test_data.as_bytes(), &dict, 50, &options);
+		let mut results: Vec<PushResult> = messages
+			.iter()
+			.rev()
+			.map(|v| reassembler.push(v).unwrap())
+			.collect();
+		assert_eq!(
+			results.pop().unwrap(),
+			PushResult::Complete(test_data.into_bytes())
+		);
+		assert!(results
+			.iter()
+			.all(|v| matches!(v, PushResult::Pending { .. })));
+	}
+}
@@ -0,0 +1,169 @@
+//! A record of recently doffed armor, for noticing when the same messages are sent again.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use crate::{doff, doff_messages, CawError, DictMappings, Dictionary, Tokenizer, Tolerances};
+
+/// Remembers digests of the armor it has doffed, so armor which is sent again is refused
+/// instead of being read as if it were new. The digest is of what the armor decodes to, so
+/// changing its case, punctuation or spacing, picking other words for the same chunks, or
+/// repeating messages doesn't make it new. The same data donned again is the same armor too,
+/// unless something like `Options::nonce` or `Options::message_id` tells the two apart.
+///
+/// The guard can be shared between threads. It holds at most `capacity` digests, forgetting
+/// the oldest ones first, and forgets digests once they're older than `ttl`. Armor which is
+/// replayed after it's been forgotten is read as usual, so `doff_fresh` is worth using as well.
+#[derive(Debug)]
+pub struct ReplayGuard {
+	capacity: usize,
+	ttl: Duration,
+	/// The digests and when they were first seen, with the newest last.
+	entries: Mutex<VecDeque<([u8; 32], Instant)>>,
+}
+
+impl ReplayGuard {
+	/// Create a guard which remembers at most `capacity` sets of messages for `ttl` each.
+	pub fn new(capacity: usize, ttl: Duration) -> ReplayGuard {
+		ReplayGuard {
+			capacity,
+			ttl,
+			entries: Mutex::new(VecDeque::new()),
+		}
+	}
+	/// The digest of a set of messages: the SHA-256 of the dictionary indices of the header,
+	/// each as 4 big-endian bytes, followed by the payload's chunks in sequence order, each as
+	/// 2 big-endian bytes. Messages which repeat a sequence number are only counted once, and
+	/// the fragment count is left out of the header, so splitting the same chunks into other
+	/// messages doesn't change the digest either.
+	pub fn digest<D: Dictionary>(
+		messages: &[String],
+		dict: &DictMappings<D>,
+	) -> Result<[u8; 32], CawError> {
+		let mut decoded = doff_messages(
+			messages,
+			dict,
+			&Tolerances::default(),
+			&Tokenizer::default(),
+		)?;
+		decoded.sort_by_key(|v| v.sequence);
+		decoded.dedup_by_key(|v| v.sequence);
+		let mut hasher = Sha256::new();
+		if let Some(header) = decoded.iter().find_map(|v| v.header.as_ref()) {
+			let mut header = header.clone();
+			header.fragments = None;
+			for index in header.encode(dict) {
+				hasher.update(index.to_be_bytes());
+			}
+		}
+		for chunk in decoded.iter().flat_map(|v| &v.data) {
+			hasher.update(chunk.to_be_bytes());
+		}
+		Ok(hasher.finalize().into())
+	}
+	/// Remember `messages`, returning whether they'd already been seen.
+	pub fn check<D: Dictionary>(
+		&self,
+		messages: &[String],
+		dict: &DictMappings<D>,
+	) -> Result<bool, CawError> {
+		let digest = ReplayGuard::digest(messages, dict)?;
+		Ok(self.check_digest(digest))
+	}
+	/// Remember `digest`, returning whether it had already been seen.
+	fn check_digest(&self, digest: [u8; 32]) -> bool {
+		let now = Instant::now();
+		// Nothing panics while the lock is held, but a poisoned guard is still usable
+		let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+		entries.retain(|v| now.duration_since(v.1) < self.ttl);
+		if entries.iter().any(|v| v.0 == digest) {
+			return true;
+		}
+		entries.push_back((digest, now));
+		while entries.len() > self.capacity {
+			entries.pop_front();
+		}
+		false
+	}
+	/// Doff armor, like `doff`, refusing it if the same armor has already been doffed. Only
+	/// armor which can be doffed is remembered, so garbage can't push real armor out.
+	pub fn doff<D: Dictionary>(
+		&self,
+		messages: &[String],
+		dict: &DictMappings<D>,
+	) -> Result<Vec<u8>, CawError> {
+		let data = doff(messages, dict)?;
+		if self.check(messages, dict)? {
+			return Err(CawError::Replayed);
+		}
+		Ok(data)
+	}
+	/// The number of digests the guard remembers, including ones which have expired but
+	/// haven't been forgotten yet.
+	pub fn len(&self) -> usize {
+		self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+	}
+	/// Whether the guard remembers nothing.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+	/// Forget every digest.
+	pub fn clear(&self) {
+		self.entries
+			.lock()
+			.unwrap_or_else(|e| e.into_inner())
+			.clear();
+	}
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use std::time::Duration;
+
+	use super::ReplayGuard;
+	use crate::{don, don_with, today, CawError, DictMappings, Options};
+
+	#[test]
+	fn replay() {
+		let dict = DictMappings::from_seed(69, &today());
+		let guard = ReplayGuard::new(2, Duration::from_secs(60));
+		let test_data = "Transfer ten pounds".as_bytes();
+		let mut messages = don(test_data, &dict, 30);
+		assert!(messages.len() > 1);
+		assert_eq!(guard.doff(&messages, &dict).unwrap(), test_data);
+		assert_eq!(guard.doff(&messages, &dict), Err(CawError::Replayed));
+		// Reordering the messages doesn't make them new
+		messages.reverse();
+		assert_eq!(guard.doff(&messages, &dict), Err(CawError::Replayed));
+		// Neither is changing its case and spacing, or repeating a message
+		let mut altered: Vec<String> = messages
+			.iter()
+			.map(|v| v.to_uppercase().replace(' ', "  "))
+			.collect();
+		altered.push(altered[0].clone());
+		assert_eq!(guard.doff(&altered, &dict), Err(CawError::Replayed));
+		// The same data armored again with a nonce is different armor
+		let options = Options {
+			nonce: true,
+			..Options::default()
+		};
+		let armor = || don_with(test_data, &dict, 30, &options);
+		assert!(guard.doff(&armor(), &dict).is_ok());
+		// Garbage isn't remembered
+		assert!(guard.doff(&["nonsense".to_string()], &dict).is_err());
+		assert_eq!(guard.len(), 2);
+		// The oldest digest is forgotten to make room
+		assert!(guard.doff(&armor(), &dict).is_ok());
+		messages.reverse();
+		assert_eq!(guard.doff(&messages, &dict).unwrap(), test_data);
+		guard.clear();
+		assert!(guard.is_empty());
+		// Digests expire
+		let guard = ReplayGuard::new(2, Duration::ZERO);
+		assert!(guard.doff(&messages, &dict).is_ok());
+		assert!(guard.doff(&messages, &dict).is_ok());
+	}
+}
@@ -0,0 +1,189 @@
+//! Ways of agreeing on the 128-bit seed which mappings are built from.
+
+use std::convert::TryInto;
+use std::fmt;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use bip39::Mnemonic;
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+/// The salt for passphrases. It has to be the same for everyone, so that both parties derive
+/// the same seed from the same passphrase.
+const PASSPHRASE_SALT: &[u8] = b"courier_caw passphrase seed";
+
+/// The salt for subkeys.
+const SUBKEY_SALT: &[u8] = b"courier_caw subkey";
+
+/// A shared seed, which converts to and from the `u128` that `DictMappings::from_seed` takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Seed(pub u128);
+
+impl From<u128> for Seed {
+	fn from(seed: u128) -> Seed {
+		Seed(seed)
+	}
+}
+
+impl From<Seed> for u128 {
+	fn from(seed: Seed) -> u128 {
+		seed.0
+	}
+}
+
+/// How much work Argon2id does to turn a passphrase into a seed. Both parties must use the
+/// same parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassphraseParams {
+	/// The memory to use, in KiB.
+	pub memory_kib: u32,
+	/// The number of passes over the memory.
+	pub iterations: u32,
+	/// The number of lanes which are filled in parallel.
+	pub parallelism: u32,
+}
+
+impl Default for PassphraseParams {
+	/// The parameters recommended by OWASP: 19 MiB, 2 passes and 1 lane.
+	fn default() -> PassphraseParams {
+		PassphraseParams {
+			memory_kib: 19 * 1024,
+			iterations: 2,
+			parallelism: 1,
+		}
+	}
+}
+
+impl Seed {
+	/// Derive a seed from a shared passphrase with Argon2id and the default parameters.
+	pub fn from_passphrase(passphrase: &str) -> Seed {
+		Seed::from_passphrase_with(passphrase, &PassphraseParams::default())
+	}
+	/// Derive a seed from a shared passphrase with Argon2id.
+	///
+	/// Panics if Argon2 doesn't accept the parameters, such as less than 8 KiB of memory per
+	/// lane.
+	pub fn from_passphrase_with(passphrase: &str, params: &PassphraseParams) -> Seed {
+		let params = Params::new(
+			params.memory_kib,
+			params.iterations,
+			params.parallelism,
+			Some(16),
+		)
+		.expect("invalid Argon2 parameters");
+		let mut output = [0; 16];
+		Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+			.hash_password_into(passphrase.as_bytes(), PASSPHRASE_SALT, &mut output)
+			.expect("Argon2 accepts any passphrase");
+		Seed(u128::from_be_bytes(output))
+	}
+	/// A seed of its own for the channel or purpose named by `label`, so one master seed can
+	/// be used in several places without armor in one having anything in common with armor
+	/// in another. Anyone with the master seed and the label can derive it.
+	///
+	/// The subkey is the first 16 bytes, big-endian, of HKDF-SHA256 with the master seed as
+	/// 16 big-endian bytes for the input key, `courier_caw subkey` for the salt and the label
+	/// in UTF-8 for the info. Labels are compared byte for byte, so both parties must spell
+	/// them exactly the same, such as `irc:#general` or `matrix:!room:example.org`.
+	pub fn subkey(&self, label: &str) -> Seed {
+		let mut output = [0; 16];
+		Hkdf::<Sha256>::new(Some(SUBKEY_SALT), &self.0.to_be_bytes())
+			.expand(label.as_bytes(), &mut output)
+			.expect("16 bytes is a valid HKDF output length");
+		Seed(u128::from_be_bytes(output))
+	}
+	/// The seed as a 12-word English BIP39 mnemonic, which is easier to read out or write
+	/// down than a number.
+	pub fn to_mnemonic(&self) -> String {
+		Mnemonic::from_entropy(&self.0.to_be_bytes())
+			.expect("16 bytes is a valid entropy length")
+			.to_string()
+	}
+	/// Read a seed from a 12-word English BIP39 mnemonic. Case and extra whitespace are
+	/// ignored, but the checksum word has to be right.
+	pub fn from_mnemonic(mnemonic: &str) -> Result<Seed, InvalidMnemonic> {
+		let words: Vec<String> = mnemonic
+			.split_whitespace()
+			.map(|word| word.to_lowercase())
+			.collect();
+		let mnemonic = Mnemonic::parse_in_normalized(bip39::Language::English, &words.join(" "))
+			.map_err(|_| InvalidMnemonic)?;
+		let entropy: [u8; 16] = mnemonic
+			.to_entropy()
+			.try_into()
+			.map_err(|_| InvalidMnemonic)?;
+		Ok(Seed(u128::from_be_bytes(entropy)))
+	}
+}
+
+/// A mnemonic which isn't 12 words from the BIP39 English wordlist with a valid checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidMnemonic;
+
+impl fmt::Display for InvalidMnemonic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "the mnemonic isn't a valid 12-word BIP39 phrase")
+	}
+}
+
+impl std::error::Error for InvalidMnemonic {}
+
+#[cfg(test)]
+mod tests {
+	use super::{InvalidMnemonic, PassphraseParams, Seed};
+
+	#[test]
+	fn passphrase() {
+		let params = PassphraseParams {
+			memory_kib: 64,
+			iterations: 1,
+			parallelism: 1,
+		};
+		let seed = Seed::from_passphrase_with("correct horse battery staple", &params);
+		assert_eq!(
+			seed,
+			Seed::from_passphrase_with("correct horse battery staple", &params)
+		);
+		assert_ne!(seed, Seed::from_passphrase_with("correct horse", &params));
+		let stronger = PassphraseParams {
+			iterations: 2,
+			..params
+		};
+		assert_ne!(
+			seed,
+			Seed::from_passphrase_with("correct horse battery staple", &stronger)
+		);
+	}
+	#[test]
+	fn subkey() {
+		let master = Seed(69);
+		let general = master.subkey("#general");
+		assert_eq!(general, Seed(337854552356956620637805080612793543996));
+		assert_eq!(general, master.subkey("#general"));
+		assert_ne!(general, master.subkey("#random"));
+		assert_ne!(general, master);
+		assert_ne!(general, Seed(70).subkey("#general"));
+	}
+	#[test]
+	fn mnemonic() {
+		// The first test vector from the BIP39 specification
+		assert_eq!(
+			Seed(0).to_mnemonic(),
+			"abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+			 abandon about"
+		);
+		let seed = Seed(0x7f7f_7f7f_7f7f_7f7f_7f7f_7f7f_7f7f_7f7f);
+		let mnemonic = seed.to_mnemonic();
+		assert_eq!(
+			mnemonic,
+			"legal winner thank year wave sausage worth useful legal winner thank yellow"
+		);
+		assert_eq!(Seed::from_mnemonic(&mnemonic), Ok(seed));
+		assert_eq!(
+			Seed::from_mnemonic(&format!("  {}\n", mnemonic.to_uppercase())),
+			Ok(seed)
+		);
+		let wrong_checksum = mnemonic.replace("yellow", "abandon");
+		assert_eq!(Seed::from_mnemonic(&wrong_checksum), Err(InvalidMnemonic));
+	}
+}
@@ -0,0 +1,95 @@
+//! Fast paths for cutting bytes into 16-bit chunks and looking up the dictionary index of
+//! each chunk, which is most of the work of donning a big payload.
+//!
+//! With the `simd` feature, x86-64 processors which support it pack bytes with SSSE3 and
+//! look up indices with AVX2, which is checked when they're used. Everything else uses plain
+//! loops, which the compiler can often vectorize on its own.
+
+/// Pack pairs of bytes into 16-bit values, most significant first, filling out the last one
+/// with a zero byte.
+pub(crate) fn pack_pairs(bytes: &[u8]) -> Vec<u16> {
+	let mut values = Vec::with_capacity(bytes.len().div_ceil(2));
+	#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+	if is_x86_feature_detected!("ssse3") {
+		// SAFETY: the processor supports SSSE3
+		unsafe { x86::pack_pairs(bytes, &mut values) };
+	}
+	let (pairs, rest) = bytes[values.len() * 2..].as_chunks::<2>();
+	values.extend(pairs.iter().map(|v| u16::from_be_bytes(*v)));
+	values.extend(rest.first().map(|v| u16::from_be_bytes([*v, 0])));
+	values
+}
+
+/// The entry of `table` for each value, like the dictionary index of each chunk.
+///
+/// Panics if a value is past the end of the table.
+pub(crate) fn gather(values: &[u16], table: &[u32]) -> Vec<u32> {
+	let mut gathered = Vec::with_capacity(values.len());
+	// Only tables with an entry for every value are safe to gather from without checking
+	#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+	if table.len() > u16::MAX as usize && is_x86_feature_detected!("avx2") {
+		// SAFETY: the processor supports AVX2, and every value is in the table
+		unsafe { x86::gather(values, table, &mut gathered) };
+	}
+	gathered.extend(values[gathered.len()..].iter().map(|v| table[*v as usize]));
+	gathered
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod x86 {
+	use std::arch::x86_64::*;
+
+	/// Pack every whole block of 16 bytes into `values`, leaving the rest.
+	#[target_feature(enable = "ssse3")]
+	pub(super) unsafe fn pack_pairs(bytes: &[u8], values: &mut Vec<u16>) {
+		let swap = _mm_setr_epi8(1, 0, 3, 2, 5, 4, 7, 6, 9, 8, 11, 10, 13, 12, 15, 14);
+		let (blocks, _) = bytes.as_chunks::<16>();
+		values.reserve(blocks.len() * 8);
+		for block in blocks {
+			let swapped = _mm_shuffle_epi8(_mm_loadu_si128(block.as_ptr() as *const __m128i), swap);
+			let at = values.len();
+			_mm_storeu_si128(values.as_mut_ptr().add(at) as *mut __m128i, swapped);
+			values.set_len(at + 8);
+		}
+	}
+
+	/// Look up every whole block of 8 values in `table`, which must have an entry for every
+	/// 16-bit value, leaving the rest.
+	#[target_feature(enable = "avx2")]
+	pub(super) unsafe fn gather(values: &[u16], table: &[u32], gathered: &mut Vec<u32>) {
+		let (blocks, _) = values.as_chunks::<8>();
+		gathered.reserve(blocks.len() * 8);
+		for block in blocks {
+			let indices = _mm256_cvtepu16_epi32(_mm_loadu_si128(block.as_ptr() as *const __m128i));
+			let entries = _mm256_i32gather_epi32::<4>(table.as_ptr() as *const i32, indices);
+			let at = gathered.len();
+			_mm256_storeu_si256(gathered.as_mut_ptr().add(at) as *mut __m256i, entries);
+			gathered.set_len(at + 8);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::chunk::{self, ChunkSize};
+
+	#[test]
+	fn matches_plain_loops() {
+		let bytes: Vec<u8> = (0..1000u32).map(|v| (v * 7919 % 251) as u8).collect();
+		for len in [0, 1, 2, 15, 16, 17, 33, 999, 1000] {
+			let plain: Vec<u16> = bytes[..len]
+				.chunks(2)
+				.map(|v| u16::from_be_bytes([v[0], *v.get(1).unwrap_or(&0)]))
+				.collect();
+			assert_eq!(super::pack_pairs(&bytes[..len]), plain);
+			assert_eq!(chunk::split(&bytes[..len], ChunkSize::Sixteen), plain);
+		}
+		let table: Vec<u32> = (0..=u16::MAX as u32).map(|v| v * 3 + 1).collect();
+		let values = super::pack_pairs(&bytes);
+		let plain: Vec<u32> = values.iter().map(|v| table[*v as usize]).collect();
+		for len in [0, 7, 8, 9, 500] {
+			assert_eq!(super::gather(&values[..len], &table), plain[..len]);
+		}
+		assert_eq!(super::gather(&[3, 1], &[5, 6, 7, 8]), [8, 6]);
+	}
+}
@@ -0,0 +1,1336 @@
+//! Streaming versions of `don` and `doff`, for payloads too big to hold in memory.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rand::prelude::SliceRandom;
+
+use crate::chunk::{Chunker, Joiner};
+use crate::header::{padding, unpadded_len, Ids, Mask};
+use crate::padding::chaff;
+use crate::{
+	doff_message, fragment_header, is_armor, natural, CawError, Decoded, DictMappings, Dictionary,
+	FileInfo, Flag, Header, Options, PartOfSpeech, Separator, Splitter, Tokenizer, Tolerances,
+};
+
+/// Dons armor incrementally. Bytes written to a `DonWriter` are armored as they arrive,
+/// and each message is handed to the sink as soon as it fills up.
+///
+/// The messages are identical in format to the ones produced by `don`. Call `finish` once
+/// all of the data has been written to emit the last message. Error correction needs to see
+/// the whole payload before anything can be written, so with it enabled every message is
+/// emitted by `finish`. Streamed armor doesn't say how many messages it has, since the first
+/// message is emitted before anyone knows. For the same reason, data which is itself armor
+/// isn't flagged with `Flag::Nested`, so doffing it only takes off the outer layer.
+pub struct DonWriter<'a, D: Dictionary, F: FnMut(String) -> io::Result<()>> {
+	dict: &'a DictMappings<D>,
+	sink: F,
+	splitter: Splitter<'a, D>,
+	/// The header, until it's been added to the first message.
+	header: Option<Header>,
+	/// Whether the first message flags the data as armor itself.
+	nested: bool,
+	/// The options and data, if nothing can be written until the whole payload is known.
+	deferred: Option<(Options, Vec<u8>)>,
+	/// The file record which goes in front of the data, until it's been written.
+	file: Option<Vec<u8>>,
+	/// Whether the payload ends with its padding count.
+	padded: bool,
+	/// Whether messages are rendered as sentences.
+	natural: bool,
+	/// Whether chunks are written as homophones picked at random.
+	homophones: bool,
+	/// The parts of speech the chunks are written in, over and over.
+	rhythm: Vec<PartOfSpeech>,
+	/// How many chunks have been written so far.
+	chunks: usize,
+	/// The fillers which can be sprinkled between words.
+	fillers: Vec<String>,
+	/// The percentage of chunks which are followed by a null.
+	chaff: u8,
+	/// What the chunks are XORed with, once the header is known.
+	mask: Mask,
+	/// What goes between words.
+	separator: String,
+	/// The bits which are still waiting to make up a whole chunk.
+	pending: Chunker,
+	/// How many bytes of payload have been written so far.
+	written: usize,
+	/// The words of the message currently being filled.
+	words: Vec<Cow<'a, str>>,
+	/// The position of the message currently being filled.
+	index: usize,
+	/// The running checksum of the data, if one was requested.
+	checksum: Option<crc32fast::Hasher>,
+	/// The IDs which every fragment repeats.
+	ids: Ids,
+	/// How many bytes have been written to the writer so far.
+	offset: u64,
+	/// Told about each write and message.
+	progress: Option<Box<dyn ProgressSink + Send + 'a>>,
+	/// Set from elsewhere to stop the writer.
+	cancel: Option<&'a AtomicBool>,
+}
+
+/// How far a long `don` or `doff` has got.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+	/// How many bytes of data have been read or written.
+	pub bytes: u64,
+	/// How many messages have been emitted or read.
+	pub messages: usize,
+}
+
+/// Told how far a long `don` or `doff` has got, so it can be shown. Closures taking a
+/// `Progress` are sinks.
+pub trait ProgressSink {
+	/// Called whenever more data has been processed, or another message has been emitted
+	/// or read.
+	fn progress(&mut self, progress: Progress);
+}
+
+impl<F: FnMut(Progress)> ProgressSink for F {
+	fn progress(&mut self, progress: Progress) {
+		self(progress)
+	}
+}
+
+/// Where a `DonWriter` had got to, so that armoring can carry on from there later, even in
+/// another process. Resuming needs the same mappings, character limit and options, and the
+/// input from `offset` onwards, and makes the messages which would have followed. Words are
+/// still picked at random, so there's no random state to keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+	/// How many bytes had been written, which is where to carry on reading the input from.
+	pub offset: u64,
+	/// How many messages had been emitted.
+	pub messages: usize,
+	/// Whether the header had been written.
+	started: bool,
+	/// Whether the file record had been written.
+	file_written: bool,
+	/// The words of the message which was being filled.
+	words: Vec<String>,
+	/// The length of those words, as the splitter measures it.
+	len: usize,
+	/// How many chunks had been written.
+	chunks: usize,
+	/// The bits which were still waiting to make up a whole chunk, and how many there were.
+	leftover: (u32, u32),
+	/// How many bytes of payload had been written.
+	written: usize,
+	/// The checksum of the payload so far, if one was requested.
+	checksum: Option<u32>,
+	/// The nonce the chunks were XORed with.
+	nonce: Option<u16>,
+}
+
+impl<'a, D: Dictionary, F: FnMut(String) -> io::Result<()>> DonWriter<'a, D, F> {
+	/// Create a writer which passes each finished message to `sink`.
+	pub fn new(dict: &'a DictMappings<D>, character_limit: usize, sink: F) -> DonWriter<'a, D, F> {
+		DonWriter::with_options(dict, character_limit, &Options::default(), sink)
+	}
+	/// Create a writer with non-default options.
+	pub fn with_options(
+		dict: &'a DictMappings<D>,
+		character_limit: usize,
+		options: &Options,
+		sink: F,
+	) -> DonWriter<'a, D, F> {
+		let deferred = Header::needs_whole_payload(options);
+		let mut header = Header::new(options);
+		let mut splitter = Splitter::new(dict, character_limit, options);
+		splitter.randomize(&mut rand::thread_rng());
+		// Nobody knows whether the data will need padding until it's all been written
+		header.padded = !deferred;
+		if (options.nonce || options.keystream) && !deferred {
+			header.nonce = Some(rand::Rng::gen(&mut rand::thread_rng()));
+		}
+		DonWriter {
+			dict,
+			sink,
+			splitter,
+			natural: options.natural,
+			homophones: options.homophones,
+			rhythm: options.rhythm.clone(),
+			chunks: 0,
+			chaff: options.chaff,
+			mask: header.mask(dict),
+			fillers: natural::usable_fillers(dict, &options.fillers, options.separator.as_str())
+				.into_iter()
+				.map(String::from)
+				.collect(),
+			separator: options.separator.as_str().to_string(),
+			checksum: if header.checksum && !deferred {
+				Some(crc32fast::Hasher::new())
+			} else {
+				None
+			},
+			// Deferred payloads are packed all at once, record and all
+			file: match &options.file {
+				Some(file) if !deferred => Some(file.encode()),
+				_ => None,
+			},
+			padded: header.padded,
+			header: if deferred { None } else { Some(header) },
+			nested: false,
+			deferred: if deferred {
+				Some((options.clone(), vec![]))
+			} else {
+				None
+			},
+			pending: Chunker::new(dict.chunk_size()),
+			written: 0,
+			words: vec![],
+			index: 0,
+			ids: Ids::new(options),
+			offset: 0,
+			progress: None,
+			cancel: None,
+		}
+	}
+	/// Stop armoring with `CawError::Cancelled` as soon as `cancel` is set. It's checked
+	/// before every write and at the end, and writes take at most `CANCEL_CHUNK` bytes at a
+	/// time so that big ones don't hold it up.
+	pub fn with_cancel(mut self, cancel: &'a AtomicBool) -> DonWriter<'a, D, F> {
+		self.cancel = Some(cancel);
+		self
+	}
+	/// Fail if the writer has been cancelled.
+	fn check_cancel(&self) -> io::Result<()> {
+		check_cancel(self.cancel)
+	}
+	/// Tell `progress` how many bytes have been written and how many messages have been
+	/// emitted, after every write and every message.
+	pub fn with_progress(mut self, progress: impl ProgressSink + Send + 'a) -> DonWriter<'a, D, F> {
+		self.progress = Some(Box::new(progress));
+		self
+	}
+	/// Tell the progress sink, if there is one, how far the writer has got.
+	fn report(&mut self) {
+		if let Some(progress) = &mut self.progress {
+			progress.progress(Progress {
+				bytes: self.offset,
+				messages: self.index,
+			});
+		}
+	}
+	/// Create a writer which carries on from `checkpoint`, passing each message after the
+	/// ones which were already emitted to `sink`. Write the input from `checkpoint.offset`
+	/// onwards to it.
+	///
+	/// Panics if the options need the whole payload before anything can be written, since
+	/// writers with them never make checkpoints.
+	pub fn resume(
+		dict: &'a DictMappings<D>,
+		character_limit: usize,
+		options: &Options,
+		checkpoint: &Checkpoint,
+		sink: F,
+	) -> DonWriter<'a, D, F> {
+		assert!(
+			!Header::needs_whole_payload(options),
+			"armor which needs the whole payload can't be resumed"
+		);
+		let mut writer = DonWriter::with_options(dict, character_limit, options, sink);
+		let mut header = Header::new(options);
+		header.nonce = checkpoint.nonce;
+		writer.mask = header.mask(dict);
+		writer.mask.skip(checkpoint.chunks);
+		if let Some(header) = &mut writer.header {
+			header.nonce = checkpoint.nonce;
+		}
+		if checkpoint.started {
+			writer.header = None;
+		}
+		if checkpoint.file_written {
+			writer.file = None;
+		}
+		writer.words = checkpoint.words.iter().cloned().map(Cow::Owned).collect();
+		writer.splitter.index = checkpoint.messages;
+		writer.splitter.count = checkpoint.len;
+		writer.splitter.words = checkpoint.words.len();
+		writer.index = checkpoint.messages;
+		writer.chunks = checkpoint.chunks;
+		writer.pending = Chunker::with_leftover(dict.chunk_size(), checkpoint.leftover);
+		writer.written = checkpoint.written;
+		if let Some(checksum) = checkpoint.checksum {
+			writer.checksum = Some(crc32fast::Hasher::new_with_initial_len(
+				checksum,
+				checkpoint.written as u64,
+			));
+		}
+		writer.offset = checkpoint.offset;
+		writer
+	}
+	/// Where the writer has got to, or `None` if the options need the whole payload before
+	/// anything can be written. A checkpoint can be taken between any two writes.
+	pub fn checkpoint(&self) -> Option<Checkpoint> {
+		if self.deferred.is_some() {
+			return None;
+		}
+		Some(Checkpoint {
+			offset: self.offset,
+			messages: self.index,
+			started: self.header.is_none(),
+			file_written: self.file.is_none(),
+			words: self.words.iter().map(|v| v.to_string()).collect(),
+			len: self.splitter.count,
+			chunks: self.chunks,
+			leftover: self.pending.leftover(),
+			written: self.written,
+			checksum: self.checksum.clone().map(|v| v.finalize()),
+			nonce: self.mask.nonce(),
+		})
+	}
+	/// Add a word to the current message, emitting it first if the word doesn't fit.
+	fn push(&mut self, word: Cow<'a, str>) -> io::Result<()> {
+		if self.splitter.push(&word) {
+			self.emit()?;
+		}
+		self.words.push(word);
+		Ok(())
+	}
+	/// Hand the current message to the sink and start the next one.
+	fn emit(&mut self) -> io::Result<()> {
+		let mut result: Vec<&str> = vec![];
+		if self.index != 0 {
+			result.extend(fragment_header(
+				self.dict,
+				self.index,
+				self.ids,
+				&mut rand::thread_rng(),
+			));
+		}
+		result.extend(self.words.iter().map(|v| v.as_ref()));
+		self.index += 1;
+		let message = if self.natural {
+			natural::render(self.dict, &result, &self.separator, &mut rand::thread_rng())
+		} else {
+			result.join(&self.separator)
+		};
+		self.words.clear();
+		(self.sink)(message)?;
+		self.report();
+		Ok(())
+	}
+	/// Armor payload bytes, starting the first message if it hasn't been started yet.
+	fn write_payload(&mut self, buf: &[u8]) -> io::Result<()> {
+		let dict = self.dict;
+		if let Some(header) = self.header.take() {
+			let begin = *dict.begin.choose(&mut rand::thread_rng()).unwrap();
+			let nested = match self.nested {
+				true => dict.flags.get(Flag::Nested as usize).copied(),
+				false => None,
+			};
+			// The header is only read from the first message, so it can't be split
+			for index in std::iter::once(begin)
+				.chain(nested)
+				.chain(header.encode(dict))
+			{
+				let word = dict.dictionary.word(index);
+				self.splitter.append(word);
+				self.words.push(word.into());
+			}
+		}
+		self.written += buf.len();
+		for byte in buf {
+			if let Some(chunk) = self.pending.push(*byte) {
+				self.push_chunk(chunk)?;
+			}
+		}
+		Ok(())
+	}
+	/// Write the file record, if it hasn't been written yet, as if it were data.
+	fn write_file(&mut self) -> io::Result<()> {
+		if let Some(file) = self.file.take() {
+			if let Some(checksum) = &mut self.checksum {
+				checksum.update(&file);
+			}
+			self.write_payload(&file)?;
+		}
+		Ok(())
+	}
+	/// Add the word for a chunk, and maybe a null and a filler after it.
+	fn push_chunk(&mut self, chunk: u16) -> io::Result<()> {
+		let dict = self.dict;
+		let chunk = chunk ^ self.mask.next();
+		let index = if !self.rhythm.is_empty() {
+			let part = self.rhythm[self.chunks % self.rhythm.len()];
+			dict.word_in_set(chunk, part.set())
+		} else if self.homophones {
+			dict.random_word_for(chunk, &mut rand::thread_rng())
+		} else {
+			dict.words[chunk as usize]
+		};
+		self.chunks += 1;
+		self.push(dict.dictionary.word(index).into())?;
+		if let Some(null) = chaff(dict, self.chaff, &mut rand::thread_rng()) {
+			self.push(null.into())?;
+		}
+		if let Some(filler) = natural::filler(&self.fillers, &mut rand::thread_rng()) {
+			self.push(filler.clone().into())?;
+		}
+		Ok(())
+	}
+	/// Write the end of the armor and emit the last message.
+	pub fn finish(mut self) -> io::Result<()> {
+		self.check_cancel()?;
+		let dict = self.dict;
+		self.write_file()?;
+		if let Some((options, data)) = self.deferred.take() {
+			let (header, payload) = Header::pack(dict, &options, &data, &mut rand::thread_rng());
+			self.mask = header.mask(dict);
+			self.header = Some(header);
+			self.write_payload(&payload)?;
+		}
+		if let Some(checksum) = self.checksum.take() {
+			self.write_payload(&checksum.finalize().to_be_bytes())?;
+		}
+		if self.padded {
+			self.write_payload(&padding(self.written, dict.chunk_size().group()))?;
+		}
+		// Make sure the first message has been started, even without any data
+		self.write_payload(&[])?;
+		// Error corrected payloads don't always fill their last chunk
+		if let Some(chunk) = self.pending.clone().finish() {
+			self.push_chunk(chunk)?;
+		}
+		let end = dict
+			.dictionary
+			.word(*dict.end.choose(&mut rand::thread_rng()).unwrap());
+		self.push(end.into())?;
+		self.emit()
+	}
+}
+
+impl<'a, D: Dictionary, F: FnMut(String) -> io::Result<()>> Write for DonWriter<'a, D, F> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.check_cancel()?;
+		let buf = match self.cancel {
+			Some(_) => &buf[..buf.len().min(CANCEL_CHUNK)],
+			None => buf,
+		};
+		self.offset += buf.len() as u64;
+		if let Some((_, data)) = &mut self.deferred {
+			data.extend_from_slice(buf);
+			self.report();
+			return Ok(buf.len());
+		}
+		self.write_file()?;
+		if let Some(checksum) = &mut self.checksum {
+			checksum.update(buf);
+		}
+		self.write_payload(buf)?;
+		self.report();
+		Ok(buf.len())
+	}
+	/// Messages are only emitted once they're full, so there is nothing to flush.
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// How many bytes a cancellable `DonWriter` armors between checks.
+pub const CANCEL_CHUNK: usize = 64 * 1024;
+
+/// Fail with `CawError::Cancelled` if `cancel` has been set.
+fn check_cancel(cancel: Option<&AtomicBool>) -> io::Result<()> {
+	match cancel {
+		Some(cancel) if cancel.load(Ordering::Relaxed) => Err(CawError::Cancelled.into()),
+		_ => Ok(()),
+	}
+}
+
+/// Don armor for everything `reader` produces, handing each message to `sink` as soon as
+/// it's full. Only a little of the input and the message being filled are held at once, so
+/// memory use doesn't grow with the size of the input, unless the options need the whole
+/// payload before anything can be written (see `DonWriter`).
+pub fn don_read<D: Dictionary>(
+	reader: impl Read,
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	sink: impl FnMut(String) -> io::Result<()>,
+) -> io::Result<()> {
+	don_read_with_progress(reader, dict, character_limit, options, sink, |_| {})
+}
+
+/// Like `don_read`, telling `progress` how much of the input has been read and how many
+/// messages have been emitted as it goes.
+pub fn don_read_with_progress<D: Dictionary>(
+	mut reader: impl Read,
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	sink: impl FnMut(String) -> io::Result<()>,
+	progress: impl ProgressSink + Send,
+) -> io::Result<()> {
+	let mut writer =
+		DonWriter::with_options(dict, character_limit, options, sink).with_progress(progress);
+	io::copy(&mut reader, &mut writer)?;
+	writer.finish()
+}
+
+/// Like `don_read`, giving up with `CawError::Cancelled`, wrapped in an `io::Error`, as soon
+/// as `cancel` is set. Messages which were emitted before then have already gone to `sink`.
+pub fn don_read_cancellable<D: Dictionary>(
+	mut reader: impl Read,
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	sink: impl FnMut(String) -> io::Result<()>,
+	cancel: &AtomicBool,
+) -> io::Result<()> {
+	let mut writer =
+		DonWriter::with_options(dict, character_limit, options, sink).with_cancel(cancel);
+	io::copy(&mut reader, &mut writer)?;
+	writer.finish()
+}
+
+/// Don armor lazily, only armoring as much of the data as it takes to fill each message as
+/// it's asked for, so messages can be posted as they're made. The messages are the ones a
+/// `DonWriter` would make, except that data which is itself armor is flagged with
+/// `Flag::Nested`, like `don` flags it, since all of the data is there to check.
+pub fn don_iter<'a, D: Dictionary>(
+	data: &'a [u8],
+	dict: &'a DictMappings<D>,
+	character_limit: usize,
+) -> impl Iterator<Item = String> + 'a {
+	// Shared with the writer's sink, and behind a mutex so the iterator can be sent to
+	// another thread
+	let ready = Arc::new(Mutex::new(VecDeque::new()));
+	let sink = {
+		let ready = ready.clone();
+		move |message| {
+			ready
+				.lock()
+				.unwrap_or_else(|e| e.into_inner())
+				.push_back(message);
+			Ok(())
+		}
+	};
+	let mut writer = DonWriter::new(dict, character_limit, sink);
+	writer.nested = is_armor(data, dict);
+	let mut writer = Some(writer);
+	let mut rest = data;
+	std::iter::from_fn(move || loop {
+		if let Some(message) = ready.lock().unwrap_or_else(|e| e.into_inner()).pop_front() {
+			return Some(message);
+		}
+		if rest.is_empty() {
+			writer.take()?.finish().expect("the sink never fails");
+		} else {
+			let (piece, remaining) = rest.split_at(rest.len().min(ITER_CHUNK));
+			writer
+				.as_mut()?
+				.write_all(piece)
+				.expect("the sink never fails");
+			rest = remaining;
+		}
+	})
+}
+
+/// How many bytes `don_iter` armors at a time while filling a message.
+const ITER_CHUNK: usize = 64;
+
+/// Doff armor, writing the recovered bytes to `sink` as soon as every message before them has
+/// been read, instead of collecting them first. Armor which can't be doffed fails with
+/// `InvalidData`, wrapping the `CawError`.
+///
+/// Nothing can be written until the whole payload has been read if the armor needs it (see
+/// `DonWriter`), but otherwise the data before a problem might already have been written.
+/// Like `doff`, any file record is left out of what's written.
+pub fn doff_into<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	sink: &mut impl Write,
+) -> io::Result<()> {
+	doff_into_with_progress(messages, dict, sink, |_| {})
+}
+
+/// Like `doff_into`, telling `progress` how many messages have been read and how many bytes
+/// have been written after each message.
+pub fn doff_into_with_progress<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	sink: &mut impl Write,
+	progress: impl ProgressSink + Send,
+) -> io::Result<()> {
+	doff_into_inner(messages, dict, sink, progress, None)
+}
+
+/// Like `doff_into`, giving up with `CawError::Cancelled`, wrapped in an `io::Error`, as soon
+/// as `cancel` is set. It's checked before each message is read.
+pub fn doff_into_cancellable<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	sink: &mut impl Write,
+	cancel: &AtomicBool,
+) -> io::Result<()> {
+	doff_into_inner(messages, dict, sink, |_| {}, Some(cancel))
+}
+
+fn doff_into_inner<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	sink: &mut impl Write,
+	mut progress: impl ProgressSink,
+	cancel: Option<&AtomicBool>,
+) -> io::Result<()> {
+	let mut reader = DoffReader::new(dict);
+	let mut bytes = 0;
+	let mut buf = [0; 8192];
+	for (index, message) in messages.iter().enumerate() {
+		check_cancel(cancel)?;
+		reader.push(message)?;
+		// `io::copy` doesn't say how much it copied before the next message was missing
+		loop {
+			match reader.read(&mut buf) {
+				Ok(0) => break,
+				Ok(count) => {
+					sink.write_all(&buf[..count])?;
+					bytes += count as u64;
+				}
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+				Err(e) => return Err(e),
+			}
+		}
+		progress.progress(Progress {
+			bytes,
+			messages: index + 1,
+		});
+	}
+	if !reader.is_finished() {
+		check_cancel(cancel)?;
+		// Messages are missing, or the end marker is. `doff` can make up for either, so only
+		// what it finds past the bytes which were already written is left to write.
+		let data = crate::doff(messages, dict)?;
+		let rest = data.get(bytes as usize..).ok_or(CawError::Uncorrectable)?;
+		sink.write_all(rest)?;
+		progress.progress(Progress {
+			bytes: data.len() as u64,
+			messages: messages.len(),
+		});
+	}
+	Ok(())
+}
+
+/// Doffs armor incrementally. Messages are fed to a `DoffReader` one at a time in any
+/// order, and their bytes can be read as soon as every message before them has arrived.
+///
+/// Reading fails with `WouldBlock` while the next message is still missing, and reaches
+/// end-of-file once the message carrying the end marker has been read.
+pub struct DoffReader<'a, D: Dictionary> {
+	dict: &'a DictMappings<D>,
+	/// Messages which arrived before the messages preceding them.
+	waiting: BTreeMap<u32, Decoded>,
+	/// Bytes which are ready to be read.
+	ready: VecDeque<u8>,
+	/// Joins the chunks of messages which have been made ready into bytes.
+	joiner: Joiner,
+	/// The position of the next message to be made ready.
+	next: usize,
+	/// How many messages have been pushed so far.
+	received: usize,
+	/// Whether the message carrying the end marker has been made ready.
+	finished: bool,
+	/// The running checksum of the bytes read so far, until the armor's checksum is verified.
+	checksum: Option<crc32fast::Hasher>,
+	/// The header and the chunks which have arrived so far, if nothing can be read until the
+	/// whole payload has arrived.
+	whole: Option<(Header, Vec<Option<u16>>)>,
+	/// Whether the payload ends with its padding count, until the padding is removed.
+	padded: bool,
+	/// Whether the messages may contain fillers, which are skipped.
+	fillers: bool,
+	/// Whether the data starts with a file record which hasn't been taken off yet.
+	file_pending: bool,
+	/// The file record, once it's been taken off the data.
+	file: Option<FileInfo>,
+	/// What the chunks were XORed with, once the header has arrived.
+	mask: Mask,
+	/// The positions of messages which were ignored because they had already arrived.
+	duplicates: Vec<usize>,
+	/// The IDs of the first message, once one has been pushed.
+	ids: Option<Ids>,
+	/// How messages are split into words.
+	tokenizer: Tokenizer,
+}
+
+impl<'a, D: Dictionary> DoffReader<'a, D> {
+	/// Create a reader with no messages.
+	pub fn new(dict: &'a DictMappings<D>) -> DoffReader<'a, D> {
+		DoffReader::with_tokenizer(dict, Tokenizer::default())
+	}
+	/// Create a reader for armor whose words are separated by `separator`.
+	pub fn with_separator(dict: &'a DictMappings<D>, separator: &Separator) -> DoffReader<'a, D> {
+		let tokenizer = Tokenizer {
+			separator: separator.clone(),
+			..Tokenizer::default()
+		};
+		DoffReader::with_tokenizer(dict, tokenizer)
+	}
+	/// Create a reader which splits messages into words with `tokenizer`.
+	pub fn with_tokenizer(dict: &'a DictMappings<D>, tokenizer: Tokenizer) -> DoffReader<'a, D> {
+		DoffReader {
+			dict,
+			waiting: BTreeMap::new(),
+			ready: VecDeque::new(),
+			joiner: Joiner::new(dict.chunk_size()),
+			next: 0,
+			received: 0,
+			finished: false,
+			checksum: None,
+			whole: None,
+			padded: false,
+			fillers: false,
+			file_pending: false,
+			file: None,
+			mask: Mask::default(),
+			duplicates: vec![],
+			ids: None,
+			tokenizer,
+		}
+	}
+	/// Feed a message to the reader. Errors refer to messages by the order they were pushed in.
+	///
+	/// If the armor has a checksum, it's verified by the push which completes the armor. A
+	/// message which has already arrived is ignored, and listed in `duplicates`.
+	pub fn push(&mut self, message: &str) -> Result<(), CawError> {
+		let decoded = doff_message(
+			self.received,
+			message,
+			self.dict,
+			&Tolerances::default(),
+			&self.tokenizer,
+		)?;
+		if *self.ids.get_or_insert(decoded.ids) != decoded.ids {
+			return Err(CawError::MixedMessages);
+		}
+		self.received += 1;
+		if (decoded.sequence as usize) < self.next || self.waiting.contains_key(&decoded.sequence) {
+			self.duplicates.push(decoded.message);
+			return Ok(());
+		}
+		self.waiting.insert(decoded.sequence, decoded);
+		while let Some(mut decoded) = self.waiting.remove(&(self.next as u32)) {
+			if let Some(header) = &decoded.header {
+				self.fillers = header.fillers;
+				self.file_pending = header.file;
+				self.mask = header.mask(self.dict);
+			}
+			if let Some(e) = decoded.skipped.take().filter(|_| !self.fillers) {
+				return Err(e);
+			}
+			match decoded.header {
+				Some(header) if header.is_whole_payload() => self.whole = Some((header, vec![])),
+				Some(header) => {
+					if header.checksum {
+						self.checksum = Some(crc32fast::Hasher::new());
+					}
+					self.padded = header.padded;
+				}
+				None => {}
+			}
+			for chunk in decoded.data {
+				let chunk = chunk ^ self.mask.next();
+				match &mut self.whole {
+					Some((_, chunks)) => chunks.push(Some(chunk)),
+					None => self.joiner.push(chunk, &mut self.ready),
+				}
+			}
+			self.finished |= decoded.last;
+			self.next += 1;
+		}
+		if self.finished {
+			// The chunks are joined the same way `doff` joins them, whatever their size
+			if let Some((header, chunks)) = self.whole.take() {
+				let payload = header.join(self.dict.chunk_size(), &chunks)?;
+				self.ready.extend(header.unpack(self.dict, payload)?);
+			}
+		}
+		self.take_file()?;
+		if self.finished {
+			if self.padded {
+				self.padded = false;
+				let group = self.dict.chunk_size().group();
+				let len = unpadded_len(self.ready.make_contiguous(), group)?;
+				self.ready.truncate(len);
+			}
+			if let Some(mut checksum) = self.checksum.take() {
+				let split = self
+					.ready
+					.len()
+					.checked_sub(4)
+					.ok_or(CawError::ChecksumMismatch)?;
+				let expected: Vec<u8> = self.ready.drain(split..).collect();
+				let (front, back) = self.ready.as_slices();
+				checksum.update(front);
+				checksum.update(back);
+				if checksum.finalize().to_be_bytes() != expected[..] {
+					return Err(CawError::ChecksumMismatch);
+				}
+			}
+		}
+		Ok(())
+	}
+	/// Whether every message has arrived.
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+	/// The record of the file the data came from, once it's arrived, if the armor has one. It
+	/// isn't part of the data which is read.
+	pub fn file(&self) -> Option<&FileInfo> {
+		self.file.as_ref()
+	}
+	/// Take the file record off the front of the data, once all of it has arrived.
+	fn take_file(&mut self) -> Result<(), CawError> {
+		if !self.file_pending {
+			return Ok(());
+		}
+		let record_len = match (self.ready.front(), self.ready.get(1)) {
+			(Some(high), Some(low)) => 2 + u16::from_be_bytes([*high, *low]) as usize + 16,
+			_ => usize::MAX,
+		};
+		if self.ready.len() < record_len {
+			return match self.finished {
+				true => Err(CawError::BadFileInfo),
+				false => Ok(()),
+			};
+		}
+		let record: Vec<u8> = self.ready.drain(..record_len).collect();
+		// The checksum covers the record too, and nothing after it has been read yet
+		if let Some(checksum) = &mut self.checksum {
+			checksum.update(&record);
+		}
+		self.file = Some(FileInfo::split(record)?.0);
+		self.file_pending = false;
+		Ok(())
+	}
+	/// The positions, in the order they were pushed, of messages which were ignored because
+	/// a message with the same sequence number had already arrived.
+	pub fn duplicates(&self) -> &[usize] {
+		&self.duplicates
+	}
+}
+
+impl<'a, D: Dictionary> Read for DoffReader<'a, D> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		// The last bytes might turn out to be the checksum or padding, and the first ones the file
+		// record, so they can't be read yet
+		let held = if self.whole.is_some() || self.file_pending {
+			self.ready.len()
+		} else {
+			let padding = if self.padded {
+				1 + self.dict.chunk_size().group()
+			} else {
+				0
+			};
+			self.checksum.as_ref().map_or(0, |_| 4) + padding
+		};
+		let available = self.ready.len().saturating_sub(held);
+		if available == 0 && !self.finished {
+			return Err(io::ErrorKind::WouldBlock.into());
+		}
+		let count = available.min(buf.len());
+		for (slot, byte) in buf.iter_mut().zip(self.ready.drain(..count)) {
+			*slot = byte;
+		}
+		if let Some(checksum) = &mut self.checksum {
+			checksum.update(&buf[..count]);
+		}
+		Ok(count)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![cfg_attr(feature = "no-embedded-dict", allow(unused_imports, dead_code))]
+	use std::io::{self, ErrorKind, Read, Write};
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	use super::{DoffReader, DonWriter, Progress};
+	use crate::{today, CawError, DictMappings, Dictionary, FileInfo, Options};
+
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn matches_don() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data =
+			"This is a much longer test string, written in several pieces 🎉!".as_bytes();
+		let mut messages = vec![];
+		let mut writer = DonWriter::new(&dict, 50, |message| {
+			messages.push(message);
+			Ok(())
+		});
+		for piece in test_data.chunks(7) {
+			writer.write_all(piece).unwrap();
+		}
+		writer.finish().unwrap();
+		assert!(messages.len() > 1);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn resume() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "A transfer which takes days, one message at a time. ".repeat(8);
+		let test_data = test_data.as_bytes();
+		for options in [
+			Options::default(),
+			Options {
+				checksum: true,
+				file: Some(FileInfo {
+					name: "days.txt".to_string(),
+					len: test_data.len() as u64,
+					modified: None,
+				}),
+				..Options::default()
+			},
+			Options {
+				keystream: true,
+				..Options::default()
+			},
+		] {
+			let mut messages = vec![];
+			let mut writer = DonWriter::with_options(&dict, 60, &options, |message| {
+				messages.push(message);
+				Ok(())
+			});
+			assert_eq!(writer.checkpoint().unwrap().offset, 0);
+			writer.write_all(&test_data[..101]).unwrap();
+			let checkpoint = writer.checkpoint().unwrap();
+			drop(writer);
+			assert_eq!(checkpoint.offset, 101);
+			assert_eq!(checkpoint.messages, messages.len());
+			let mut writer = DonWriter::resume(&dict, 60, &options, &checkpoint, |message| {
+				messages.push(message);
+				Ok(())
+			});
+			writer
+				.write_all(&test_data[checkpoint.offset as usize..])
+				.unwrap();
+			writer.finish().unwrap();
+			let (data, report) = crate::doff_with_report(&messages, &dict).unwrap();
+			assert_eq!(data, test_data);
+			assert_eq!(report.file, options.file);
+		}
+		let options = Options {
+			compress: true,
+			..Options::default()
+		};
+		let writer = DonWriter::with_options(&dict, 60, &options, |_| Ok(()));
+		assert_eq!(writer.checkpoint(), None);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn reader() {
+		let dict = DictMappings::from_seed(69, &today());
+		let input = io::repeat(b'!').take(300_001);
+		let mut messages = vec![];
+		super::don_read(input, &dict, 280, &Options::default(), |message| {
+			assert!(message.len() <= 280);
+			messages.push(message);
+			Ok(())
+		})
+		.unwrap();
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), vec![b'!'; 300_001]);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn progress() {
+		let dict = DictMappings::from_seed(69, &today());
+		let input = io::repeat(b'!').take(50_000);
+		let mut messages = vec![];
+		let mut reports = vec![];
+		super::don_read_with_progress(
+			input,
+			&dict,
+			280,
+			&Options::default(),
+			|message| {
+				messages.push(message);
+				Ok(())
+			},
+			|progress| reports.push(progress),
+		)
+		.unwrap();
+		assert!(reports
+			.windows(2)
+			.all(|v| v[0].bytes <= v[1].bytes && v[0].messages <= v[1].messages));
+		assert_eq!(
+			reports.last(),
+			Some(&Progress {
+				bytes: 50_000,
+				messages: messages.len()
+			})
+		);
+		let mut reports = vec![];
+		let mut doffed = vec![];
+		super::doff_into_with_progress(&messages, &dict, &mut doffed, |progress| {
+			reports.push(progress)
+		})
+		.unwrap();
+		assert_eq!(reports.len(), messages.len());
+		assert_eq!(
+			reports.last(),
+			Some(&Progress {
+				bytes: 50_000,
+				messages: messages.len()
+			})
+		);
+		// A writer with progress can still be handed to another thread
+		fn assert_send<T: Send>(_: &T) {}
+		let writer = DonWriter::new(&dict, 280, |_| Ok(())).with_progress(|_| {});
+		assert_send(&writer);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn cancel() {
+		let dict = DictMappings::from_seed(69, &today());
+		let cancel = AtomicBool::new(false);
+		let mut messages = vec![];
+		let e = super::don_read_cancellable(
+			io::repeat(b'!').take(10_000_000),
+			&dict,
+			280,
+			&Options::default(),
+			|message| {
+				messages.push(message);
+				if messages.len() == 10 {
+					cancel.store(true, Ordering::Relaxed);
+				}
+				Ok(())
+			},
+			&cancel,
+		)
+		.unwrap_err();
+		assert_eq!(
+			e.into_inner().unwrap().downcast_ref(),
+			Some(&CawError::Cancelled)
+		);
+		// Cancelled at the end of the write which filled the tenth message
+		assert!(messages.len() < 1000);
+		let messages = crate::don(b"Never mind", &dict, 20);
+		let e = super::doff_into_cancellable(&messages, &dict, &mut vec![], &cancel).unwrap_err();
+		assert_eq!(e.kind(), ErrorKind::Other);
+		cancel.store(false, Ordering::Relaxed);
+		let mut doffed = vec![];
+		super::doff_into_cancellable(&messages, &dict, &mut doffed, &cancel).unwrap();
+		assert_eq!(doffed, b"Never mind");
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn into_writer() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Written out a message at a time".as_bytes();
+		let mut messages = crate::don(test_data, &dict, 40);
+		messages.reverse();
+		let mut doffed = vec![];
+		super::doff_into(&messages, &dict, &mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+		// Without the end marker, what was already written isn't written again
+		let test_data = "even length data!!".as_bytes();
+		let mut messages = crate::don(test_data, &dict, 40);
+		let last = messages.pop().unwrap();
+		messages.push(last.rsplit_once(' ').unwrap().0.to_string());
+		let mut doffed = vec![];
+		super::doff_into(&messages, &dict, &mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+		let options = Options {
+			error_correction: 16,
+			..Options::default()
+		};
+		let mut messages = crate::don_with(test_data, &dict, 40, &options);
+		messages.remove(1);
+		let mut doffed = vec![];
+		super::doff_into(&messages, &dict, &mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+		messages.truncate(1);
+		let e = super::doff_into(&messages, &dict, &mut vec![]).unwrap_err();
+		assert_eq!(e.kind(), ErrorKind::InvalidData);
+		assert_eq!(
+			e.into_inner().unwrap().downcast_ref(),
+			Some(&CawError::Uncorrectable)
+		);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn lazy() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Posted one message at a time, whenever the rate limit allows. ".repeat(20);
+		let test_data = test_data.as_bytes();
+		let mut messages = super::don_iter(test_data, &dict, 50);
+		let first: Vec<String> = messages.by_ref().take(2).collect();
+		assert_eq!(first.len(), 2);
+		let messages: Vec<String> = first.into_iter().chain(messages).collect();
+		assert!(messages.len() > 20);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+		// Armor on armor is flagged, so doffing takes off both layers
+		let inner = crate::don(test_data, &dict, 50).join("\n");
+		let messages: Vec<String> = super::don_iter(inner.as_bytes(), &dict, 50).collect();
+		let (data, report) = crate::doff_with_report(&messages, &dict).unwrap();
+		assert_eq!(data, test_data);
+		assert_eq!(report.flags, [crate::Flag::Nested]);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn out_of_order() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Fragments can arrive in whatever order they like ☃".as_bytes();
+		let messages = crate::don(test_data, &dict, 40);
+		assert!(messages.len() > 2);
+		let mut reader = DoffReader::new(&dict);
+		let mut buf = [0; 8];
+		assert_eq!(
+			reader.read(&mut buf).unwrap_err().kind(),
+			ErrorKind::WouldBlock
+		);
+		for message in messages.iter().rev() {
+			reader.push(message).unwrap();
+		}
+		// Copies of messages which already arrived are ignored
+		reader.push(&messages[0]).unwrap();
+		reader.push(&messages[1]).unwrap();
+		assert_eq!(reader.duplicates(), [messages.len(), messages.len() + 1]);
+		assert!(reader.is_finished());
+		let mut doffed = vec![];
+		reader.read_to_end(&mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn checksum() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			checksum: true,
+			..Options::default()
+		};
+		let test_data = "Streams verify their checksums at the very end".as_bytes();
+		let mut messages = vec![];
+		let mut writer = DonWriter::with_options(&dict, 80, &options, |message| {
+			messages.push(message);
+			Ok(())
+		});
+		writer.write_all(test_data).unwrap();
+		writer.finish().unwrap();
+		let mut reader = DoffReader::new(&dict);
+		for message in &messages {
+			reader.push(message).unwrap();
+		}
+		let mut doffed = vec![];
+		reader.read_to_end(&mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+		// Swap the first data word, after begin and the version, checksum and padding fields,
+		// for another one
+		let mut words: Vec<&str> = messages[0].split(' ').collect();
+		let other = dict.dictionary.word(dict.words[1234]);
+		words[5] = if words[5] == other {
+			dict.dictionary.word(dict.words[4321])
+		} else {
+			other
+		};
+		messages[0] = words.join(" ");
+		let mut reader = DoffReader::new(&dict);
+		let results: Vec<_> = messages.iter().map(|v| reader.push(v)).collect();
+		assert_eq!(results.last().unwrap(), &Err(CawError::ChecksumMismatch));
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn error_correction() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			error_correction: 8,
+			..Options::default()
+		};
+		let test_data = "Nothing can be read until the parity arrives".as_bytes();
+		let mut messages = vec![];
+		let mut writer = DonWriter::with_options(&dict, 40, &options, |message| {
+			messages.push(message);
+			Ok(())
+		});
+		writer.write_all(test_data).unwrap();
+		writer.finish().unwrap();
+		assert!(messages.len() > 2);
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+		let mut reader = DoffReader::new(&dict);
+		let mut doffed = vec![];
+		for message in &messages[..messages.len() - 1] {
+			reader.push(message).unwrap();
+			reader.read_to_end(&mut doffed).unwrap_err();
+			assert!(doffed.is_empty());
+		}
+		reader.push(messages.last().unwrap()).unwrap();
+		reader.read_to_end(&mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn file_record() {
+		let dict = DictMappings::from_seed(69, &today());
+		let test_data = "Streamed to a file with its name kept. ".repeat(6);
+		let test_data = test_data.as_bytes();
+		let file = FileInfo {
+			name: "kept.txt".to_string(),
+			len: test_data.len() as u64,
+			modified: Some(1_700_000_000),
+		};
+		for options in [
+			Options {
+				file: Some(file.clone()),
+				checksum: true,
+				..Options::default()
+			},
+			Options {
+				file: Some(file.clone()),
+				compress: true,
+				..Options::default()
+			},
+		] {
+			let messages = crate::don_with(test_data, &dict, 80, &options);
+			let mut doffed = vec![];
+			crate::doff_into(&messages, &dict, &mut doffed).unwrap();
+			assert_eq!(doffed, test_data);
+			let mut reader = DoffReader::new(&dict);
+			for message in messages.iter().rev() {
+				reader.push(message).unwrap();
+			}
+			let mut doffed = vec![];
+			reader.read_to_end(&mut doffed).unwrap();
+			assert_eq!(doffed, test_data);
+			assert_eq!(reader.file(), Some(&file));
+		}
+		// Nothing is read until the whole record has arrived
+		let options = Options {
+			file: Some(file.clone()),
+			..Options::default()
+		};
+		let mut messages = vec![];
+		let mut writer = DonWriter::with_options(&dict, 30, &options, |message| {
+			messages.push(message);
+			Ok(())
+		});
+		writer.write_all(test_data).unwrap();
+		writer.finish().unwrap();
+		let mut reader = DoffReader::new(&dict);
+		reader.push(&messages[0]).unwrap();
+		let mut doffed = vec![];
+		assert!(reader.read_to_end(&mut doffed).is_err());
+		assert!(doffed.is_empty() && reader.file().is_none());
+		for message in &messages[1..] {
+			reader.push(message).unwrap();
+		}
+		reader.read_to_end(&mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+		assert_eq!(reader.file(), Some(&file));
+	}
+	#[test]
+	fn small_chunks() {
+		use crate::dictionary::WordList;
+		let test_data: Vec<u8> = (0..173).collect();
+		for len in [5000, 400] {
+			let words = WordList::new((0..len).map(|n| format!("word{}", n)).collect());
+			let dict = DictMappings::from_dictionary(words, 69, &today());
+			for options in [
+				Options {
+					authenticate: true,
+					..Options::default()
+				},
+				Options {
+					encrypt: true,
+					..Options::default()
+				},
+				Options {
+					error_correction: 8,
+					..Options::default()
+				},
+			] {
+				let messages = crate::don_with(&test_data, &dict, 100, &options);
+				let mut reader = DoffReader::new(&dict);
+				for message in &messages {
+					reader.push(message).unwrap();
+				}
+				let mut doffed = vec![];
+				reader.read_to_end(&mut doffed).unwrap();
+				assert_eq!(doffed, test_data);
+			}
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn odd_length() {
+		let dict = DictMappings::from_seed(69, &today());
+		for test_data in [&b"Streams can't know the length ahead of time"[..], b"even"] {
+			let mut messages = vec![];
+			let mut writer = DonWriter::new(&dict, 40, |message| {
+				messages.push(message);
+				Ok(())
+			});
+			writer.write_all(test_data).unwrap();
+			writer.finish().unwrap();
+			assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data);
+			let mut reader = DoffReader::new(&dict);
+			for message in &messages {
+				reader.push(message).unwrap();
+			}
+			let mut doffed = vec![];
+			reader.read_to_end(&mut doffed).unwrap();
+			assert_eq!(doffed, test_data);
+		}
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn fillers() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			fillers: vec!["lol".to_string(), "tbh".to_string()],
+			..Options::default()
+		};
+		let test_data = "Streams can be chatty too. ".repeat(10);
+		let mut messages = vec![];
+		let mut writer = DonWriter::with_options(&dict, 80, &options, |message| {
+			messages.push(message);
+			Ok(())
+		});
+		writer.write_all(test_data.as_bytes()).unwrap();
+		writer.finish().unwrap();
+		assert_eq!(crate::doff(&messages, &dict).unwrap(), test_data.as_bytes());
+		let mut reader = DoffReader::new(&dict);
+		for message in &messages {
+			reader.push(message).unwrap();
+		}
+		let mut doffed = String::new();
+		reader.read_to_string(&mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+	}
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn compression() {
+		let dict = DictMappings::from_seed(69, &today());
+		let options = Options {
+			compress: true,
+			checksum: true,
+			..Options::default()
+		};
+		let test_data = "Compressed streams wait for the end. ".repeat(10);
+		let mut messages = vec![];
+		let mut writer = DonWriter::with_options(&dict, 80, &options, |message| {
+			messages.push(message);
+			Ok(())
+		});
+		writer.write_all(test_data.as_bytes()).unwrap();
+		writer.finish().unwrap();
+		let mut reader = DoffReader::new(&dict);
+		for message in messages.iter().rev() {
+			reader.push(message).unwrap();
+		}
+		let mut doffed = String::new();
+		reader.read_to_string(&mut doffed).unwrap();
+		assert_eq!(doffed, test_data);
+	}
+}
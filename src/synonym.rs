@@ -0,0 +1,327 @@
+//! Hiding data in someone else's writing, by choosing between synonyms.
+//!
+//! Every word of the cover text which is in a set of synonyms carries a few bits, chosen by
+//! which word of the set is written. The words of each set are put in a different order for
+//! every mappings' key, so the meaning of each word changes with them. The data is written
+//! after its length as a 16-bit big-endian number, most significant bit first, and the words
+//! after the last bit are left as they were.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use sha2::{Digest, Sha256};
+
+use crate::{CawError, DictMappings, Dictionary};
+
+/// Sets of common English words which can usually stand in for each other.
+const ENGLISH: &[&[&str]] = &[
+	&["big", "large"],
+	&["small", "little"],
+	&["fast", "quick"],
+	&["start", "begin"],
+	&["end", "finish"],
+	&["help", "assist"],
+	&["buy", "purchase"],
+	&["show", "display"],
+	&["hard", "difficult"],
+	&["easy", "simple"],
+	&["maybe", "perhaps"],
+	&["often", "frequently"],
+	&["happy", "glad"],
+	&["sad", "unhappy"],
+	&["smart", "clever"],
+	&["job", "task"],
+	&["choose", "pick"],
+	&["get", "obtain"],
+	&["need", "require"],
+	&["enough", "sufficient"],
+	&["shut", "close"],
+	&["rich", "wealthy"],
+	&["answer", "reply"],
+	&["idea", "notion"],
+	&["almost", "nearly"],
+	&["mistake", "error"],
+	&["huge", "enormous", "vast", "immense"],
+	&["tiny", "minuscule"],
+	&["angry", "mad"],
+	&["scared", "afraid"],
+	&["strange", "odd", "weird", "peculiar"],
+	&["beautiful", "lovely", "pretty", "gorgeous"],
+	&["great", "excellent", "superb", "terrific"],
+	&["quiet", "silent"],
+	&["loud", "noisy"],
+	&["cheap", "inexpensive"],
+	&["road", "street"],
+	&["car", "automobile"],
+	&["tired", "weary"],
+	&["sick", "ill"],
+	&["couch", "sofa"],
+	&["shop", "store"],
+	&["rapidly", "quickly"],
+	&["usually", "generally", "typically", "normally"],
+	&["simply", "merely"],
+	&["completely", "entirely", "totally", "wholly"],
+	&["certainly", "surely"],
+	&["talk", "speak"],
+	&["wrong", "incorrect"],
+	&["right", "correct"],
+	&["cold", "chilly"],
+	&["friend", "pal"],
+	&["shout", "yell"],
+	&["rock", "stone"],
+	&["trip", "journey"],
+	&["center", "middle"],
+	&["under", "beneath"],
+	&["fix", "repair"],
+	&["use", "utilize"],
+	&["try", "attempt"],
+	&["ask", "inquire"],
+	&["famous", "renowned"],
+	&["funny", "amusing"],
+	&["brave", "courageous"],
+	&["calm", "peaceful"],
+	&["dirty", "filthy"],
+	&["dull", "boring"],
+	&["whole", "entire"],
+	&["main", "primary", "chief", "principal"],
+	&["important", "significant"],
+	&["problem", "issue"],
+	&["chance", "opportunity"],
+];
+
+/// Sets of words which can stand in for each other in a cover text.
+#[derive(Debug, Clone)]
+pub struct Synonyms {
+	sets: Vec<Vec<String>>,
+	/// The set and position of every word, in lowercase.
+	words: HashMap<String, (usize, usize)>,
+}
+
+impl Synonyms {
+	/// Sets of synonyms, compared without regard to case. A set of `n` words carries as many
+	/// bits as the largest power of two no bigger than `n`, so sets of two, four or eight
+	/// words waste nothing, and a set of one word carries nothing.
+	///
+	/// Panics if a word is in more than one set.
+	pub fn new<S, W>(sets: impl IntoIterator<Item = S>) -> Synonyms
+	where
+		S: IntoIterator<Item = W>,
+		W: AsRef<str>,
+	{
+		let sets: Vec<Vec<String>> = sets
+			.into_iter()
+			.map(|v| v.into_iter().map(|v| v.as_ref().to_lowercase()).collect())
+			.collect();
+		let mut words = HashMap::new();
+		for (set, v) in sets.iter().enumerate() {
+			for (position, word) in v.iter().enumerate() {
+				let duplicate = words.insert(word.clone(), (set, position)).is_some();
+				assert!(!duplicate, "{:?} is in more than one set", word);
+			}
+		}
+		Synonyms { sets, words }
+	}
+	/// Some sets of common English words.
+	pub fn english() -> Synonyms {
+		Synonyms::new(ENGLISH.iter().map(|v| v.iter()))
+	}
+	/// The number of bytes of data which `cover` can carry.
+	pub fn capacity(&self, cover: &str) -> usize {
+		let bits: usize = words(cover)
+			.filter_map(|(_, v)| self.bits(v))
+			.map(|(set, _)| set_bits(self.sets[set].len()) as usize)
+			.sum();
+		(bits / 8).saturating_sub(2)
+	}
+	/// The set and position of a word of the cover text, if it carries any bits.
+	fn bits(&self, word: &str) -> Option<(usize, usize)> {
+		self.words
+			.get(&word.to_lowercase())
+			.copied()
+			.filter(|(set, _)| set_bits(self.sets[*set].len()) != 0)
+	}
+	/// The order of the words of each set under `key`, where the word at `order[set][v]`
+	/// means `v`.
+	fn order(&self, key: &[u8; 32]) -> Vec<Vec<usize>> {
+		let mut rng =
+			Pcg64::from_seed(Sha256::digest([&b"caw synonyms "[..], key].concat()).into());
+		self.sets
+			.iter()
+			.map(|v| {
+				let mut order: Vec<usize> = (0..v.len()).collect();
+				order.shuffle(&mut rng);
+				order
+			})
+			.collect()
+	}
+}
+
+/// The number of bits carried by a set of `len` words.
+fn set_bits(len: usize) -> u32 {
+	len.checked_ilog2().unwrap_or(0)
+}
+
+/// The words of `text` with their positions, as runs of letters.
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+	text.split(|v: char| !v.is_alphabetic())
+		.filter(|v| !v.is_empty())
+		.map(move |v| (v.as_ptr() as usize - text.as_ptr() as usize, v))
+}
+
+/// Write `word` with the same case as `like`.
+fn match_case(word: &str, like: &str) -> String {
+	let mut chars = like.chars();
+	let first_upper = chars.next().is_some_and(char::is_uppercase);
+	if first_upper && like.chars().count() > 1 && chars.all(char::is_uppercase) {
+		return word.to_uppercase();
+	}
+	if first_upper {
+		let mut chars = word.chars();
+		return chars
+			.next()
+			.into_iter()
+			.flat_map(char::to_uppercase)
+			.chain(chars)
+			.collect();
+	}
+	word.to_string()
+}
+
+/// Hide `data` in `cover` by swapping its words for synonyms, under the key of `dict`.
+/// Returns `None` if the cover text doesn't have enough synonyms to carry the data, which
+/// `Synonyms::capacity` can check beforehand.
+///
+/// Only a few bits are carried by each word, so this is for short messages.
+pub fn don_cover<D: Dictionary>(
+	data: &[u8],
+	cover: &str,
+	dict: &DictMappings<D>,
+	synonyms: &Synonyms,
+) -> Option<String> {
+	let len = u16::try_from(data.len()).ok()?;
+	let payload: Vec<u8> = len.to_be_bytes().iter().chain(data).copied().collect();
+	let mut bits = payload
+		.iter()
+		.flat_map(|byte| (0..8).rev().map(move |v| (byte >> v) & 1));
+	let total = payload.len() * 8;
+	let order = synonyms.order(&dict.key);
+	let mut text = String::with_capacity(cover.len());
+	let mut written = 0;
+	let mut copied = 0;
+	for (at, word) in words(cover) {
+		if written >= total {
+			break;
+		}
+		let Some((set, _)) = synonyms.bits(word) else {
+			continue;
+		};
+		let count = set_bits(synonyms.sets[set].len());
+		// The last word might have room for more bits than are left, which are zeroes
+		let value = (0..count).fold(0, |value, _| {
+			(value << 1) | bits.next().unwrap_or(0) as usize
+		});
+		written += count as usize;
+		text.push_str(&cover[copied..at]);
+		text.push_str(&match_case(&synonyms.sets[set][order[set][value]], word));
+		copied = at + word.len();
+	}
+	if written < total {
+		return None;
+	}
+	text.push_str(&cover[copied..]);
+	Some(text)
+}
+
+/// Recover data hidden in `text` by `don_cover` under the key of `dict`.
+pub fn doff_cover<D: Dictionary>(
+	text: &str,
+	dict: &DictMappings<D>,
+	synonyms: &Synonyms,
+) -> Result<Vec<u8>, CawError> {
+	let order = synonyms.order(&dict.key);
+	let mut bits = vec![];
+	// The length is known once the first 16 bits are read
+	let mut total = 16;
+	for (_, word) in words(text) {
+		if bits.len() >= total {
+			break;
+		}
+		let Some((set, position)) = synonyms.bits(word) else {
+			continue;
+		};
+		let count = set_bits(synonyms.sets[set].len());
+		// Every word which carries data is one of the first words in its set's order
+		let value = order[set]
+			.iter()
+			.position(|v| *v == position)
+			.filter(|v| *v < 1 << count)
+			.ok_or(CawError::TruncatedCover)?;
+		bits.extend((0..count).rev().map(|v| (value >> v) as u8 & 1));
+		if total == 16 && bits.len() >= 16 {
+			total += 8 * bytes(&bits[..16])
+				.iter()
+				.fold(0, |len, v| (len << 8) | *v as usize);
+		}
+	}
+	if bits.len() < total {
+		return Err(CawError::TruncatedCover);
+	}
+	Ok(bytes(&bits[16..total]))
+}
+
+/// Join bits, most significant first, into bytes. There are always a whole number of them.
+fn bytes(bits: &[u8]) -> Vec<u8> {
+	bits.chunks(8)
+		.map(|v| v.iter().fold(0, |byte, bit| (byte << 1) | bit))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	#![cfg_attr(feature = "no-embedded-dict", allow(unused_imports, dead_code))]
+	use super::{doff_cover, don_cover, Synonyms};
+	use crate::{today, DictMappings};
+
+	const COVER: &str = "It was a huge problem, and the main idea was simply to start early. \
+		Perhaps it wasn't enough. We tried to fix the car, but the job was hard and the road \
+		was cold. Usually a quick trip to the shop is easy, but this time it was a strange \
+		and boring journey. Certainly the whole thing was a mistake, and an important one. \
+		My friend was tired and angry, and I was sad, but we were glad to get home. The \
+		beautiful sofa in the middle of the room was dirty, which was a great chance to ask \
+		for help. We often talk about it.";
+
+	#[test]
+	#[cfg(not(feature = "no-embedded-dict"))]
+	fn cover() {
+		let dict = DictMappings::from_seed(69, &today());
+		let synonyms = Synonyms::english();
+		let capacity = synonyms.capacity(COVER);
+		assert!(capacity >= 4);
+		let test_data = b"Hi!";
+		let hidden = don_cover(test_data, COVER, &dict, &synonyms).unwrap();
+		assert_eq!(doff_cover(&hidden, &dict, &synonyms).unwrap(), test_data);
+		// Only words are swapped, so everything else is left alone
+		let shape =
+			|text: &str| -> String { text.chars().filter(|v| !v.is_alphabetic()).collect() };
+		assert_eq!(shape(&hidden), shape(COVER));
+		assert!(hidden.starts_with("It was a "));
+		// Tomorrow the words mean something else
+		let tomorrow = DictMappings::from_seed(69, &today().succ_opt().unwrap());
+		assert_ne!(
+			doff_cover(&hidden, &tomorrow, &synonyms).ok(),
+			Some(test_data.to_vec())
+		);
+		assert!(don_cover(&vec![0; capacity + 1], COVER, &dict, &synonyms).is_none());
+		assert!(don_cover(&vec![0; capacity], COVER, &dict, &synonyms).is_some());
+		assert!(doff_cover("Nothing to see here.", &dict, &synonyms).is_err());
+	}
+
+	#[test]
+	#[should_panic]
+	fn duplicates() {
+		Synonyms::new([["big", "large"], ["Large", "huge"]]);
+	}
+}
@@ -0,0 +1,210 @@
+//! Slotting the words of armor into sentences, so messages read like someone wrote them.
+//!
+//! Each message of armor is written into a cycle of sentence skeletons, like "I saw a ___
+//! near the ___ yesterday.", one word to every slot, starting from a skeleton picked at
+//! random. A message ends straight after the slot holding its last word. Doffing walks the
+//! same cycle, so the words of the skeletons are stripped by their positions and never read
+//! as armor, even though most of them are in the dictionary.
+
+use rand::Rng;
+
+use crate::{doff, don_with, CawError, DictMappings, Dictionary, Options, Separator};
+
+/// The marker for a slot in a skeleton.
+const SLOT: &str = "___";
+
+/// Skeletons which read as ordinary chatter.
+const ENGLISH: &[&str] = &[
+	"I saw a ___ near the ___ yesterday.",
+	"Did you ever find the ___ you were looking for?",
+	"My ___ keeps talking about ___ and ___ lately.",
+	"We should get ___ before the ___ closes.",
+	"Honestly the ___ was better than the ___.",
+	"Can you bring the ___ over tonight?",
+	"Apparently ___ is the new ___ now.",
+	"Someone left a ___ on the ___ again.",
+];
+
+/// A token of a skeleton.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+	/// Text which is written as it is.
+	Literal(String),
+	/// A word of armor, with the text around it in the same token.
+	Slot { prefix: String, suffix: String },
+}
+
+/// The sentence skeletons which armor is written into.
+#[derive(Debug, Clone)]
+pub struct Templates {
+	skeletons: Vec<Vec<Token>>,
+}
+
+impl Templates {
+	/// Skeletons whose slots are written as `___`, which can have punctuation around them in
+	/// the same token, like `(___),`. Both parties must use the same skeletons in the same
+	/// order.
+	///
+	/// Panics if there are no skeletons, or one of them has no slots.
+	pub fn new(skeletons: impl IntoIterator<Item = impl AsRef<str>>) -> Templates {
+		let skeletons: Vec<Vec<Token>> = skeletons
+			.into_iter()
+			.map(|skeleton| {
+				skeleton
+					.as_ref()
+					.split_whitespace()
+					.map(|token| match token.split_once(SLOT) {
+						Some((prefix, suffix)) => Token::Slot {
+							prefix: prefix.to_string(),
+							suffix: suffix.to_string(),
+						},
+						None => Token::Literal(token.to_string()),
+					})
+					.collect()
+			})
+			.collect();
+		assert!(!skeletons.is_empty(), "there are no skeletons");
+		assert!(
+			skeletons
+				.iter()
+				.all(|v| v.iter().any(|v| matches!(v, Token::Slot { .. }))),
+			"a skeleton has no slots"
+		);
+		Templates { skeletons }
+	}
+	/// A few built-in English skeletons.
+	pub fn english() -> Templates {
+		Templates::new(ENGLISH)
+	}
+	/// The tokens of the skeletons, over and over, starting with skeleton `start`.
+	fn cycle(&self, start: usize) -> impl Iterator<Item = &Token> {
+		self.skeletons
+			.iter()
+			.cycle()
+			.skip(start)
+			.flat_map(|v| v.iter())
+	}
+	/// Write `words` into the skeletons, starting with skeleton `start`.
+	fn fill(&self, words: &[&str], start: usize) -> String {
+		let mut tokens: Vec<String> = vec![];
+		let mut words = words.iter();
+		let mut cycle = self.cycle(start);
+		while words.len() > 0 {
+			match cycle.next().unwrap() {
+				Token::Literal(literal) => tokens.push(literal.clone()),
+				Token::Slot { prefix, suffix } => {
+					tokens.push(format!("{}{}{}", prefix, words.next().unwrap(), suffix))
+				}
+			}
+		}
+		tokens.join(" ")
+	}
+	/// The words in the slots of `text`, if it was written into the skeletons starting with
+	/// skeleton `start`.
+	fn read<'t>(&self, text: &'t str, start: usize) -> Option<Vec<&'t str>> {
+		let mut words = vec![];
+		for (token, expected) in text.split_whitespace().zip(self.cycle(start)) {
+			match expected {
+				Token::Literal(literal) if token == literal => {}
+				Token::Literal(_) => return None,
+				Token::Slot { prefix, suffix } => words.push(
+					token
+						.strip_prefix(prefix.as_str())
+						.and_then(|v| v.strip_suffix(suffix.as_str()))
+						.unwrap_or(token),
+				),
+			}
+		}
+		Some(words)
+	}
+}
+
+/// Don armor, and write each message into `templates`. The `character_limit` applies to the
+/// armor, and the skeletons make the messages about twice as long. Separators, fillers and
+/// natural rendering don't mean anything here, so they're ignored, but the rest of `options`
+/// is used.
+pub fn don_templated<D: Dictionary>(
+	data: &[u8],
+	dict: &DictMappings<D>,
+	character_limit: usize,
+	options: &Options,
+	templates: &Templates,
+) -> Vec<String> {
+	let options = Options {
+		separator: Separator::Space,
+		fillers: vec![],
+		natural: false,
+		..options.clone()
+	};
+	let mut rng = rand::thread_rng();
+	don_with(data, dict, character_limit, &options)
+		.iter()
+		.map(|message| {
+			let words: Vec<&str> = message.split(' ').collect();
+			templates.fill(&words, rng.gen_range(0..templates.skeletons.len()))
+		})
+		.collect()
+}
+
+/// Doff armor written into `templates` by `don_templated`. Every skeleton is tried as the
+/// start of each message, and the first one whose words all match is used.
+pub fn doff_templated<D: Dictionary>(
+	messages: &[String],
+	dict: &DictMappings<D>,
+	templates: &Templates,
+) -> Result<Vec<u8>, CawError> {
+	let armor = messages
+		.iter()
+		.enumerate()
+		.map(|(message, text)| {
+			(0..templates.skeletons.len())
+				.find_map(|start| templates.read(text, start))
+				.map(|v| v.join(" "))
+				.ok_or_else(|| CawError::UnknownWord {
+					message,
+					word: text
+						.split_whitespace()
+						.next()
+						.unwrap_or_default()
+						.to_string(),
+				})
+		})
+		.collect::<Result<Vec<String>, CawError>>()?;
+	doff(&armor, dict)
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::{doff_templated, don_templated, Templates};
+	use crate::{today, DictMappings, Options};
+
+	#[test]
+	fn templates() {
+		let dict = DictMappings::from_seed(69, &today());
+		let templates = Templates::english();
+		let test_data = "Meet me by the old oak at dawn".as_bytes();
+		let options = Options {
+			checksum: true,
+			..Options::default()
+		};
+		let messages = don_templated(test_data, &dict, 60, &options, &templates);
+		assert!(messages.len() > 1);
+		for message in &messages {
+			assert!(message.starts_with(char::is_uppercase), "{}", message);
+		}
+		assert_eq!(
+			doff_templated(&messages, &dict, &templates).unwrap(),
+			test_data
+		);
+		// Skeleton words in the wrong places aren't armor
+		let mut broken = messages.clone();
+		broken[0] = format!("Hey {}", broken[0]);
+		assert!(doff_templated(&broken, &dict, &templates).is_err());
+		let custom = Templates::new(["(___),", "so ___ then ___!"]);
+		let messages = don_templated(test_data, &dict, 60, &options, &custom);
+		assert_eq!(
+			doff_templated(&messages, &dict, &custom).unwrap(),
+			test_data
+		);
+	}
+}
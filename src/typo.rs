@@ -0,0 +1,96 @@
+//! Recovering words which were mistyped, for lenient decoding.
+
+use crate::{DictMappings, Dictionary};
+
+/// A token which wasn't in the dictionary, and the word it was taken to be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correction {
+	/// The position of the message in the input.
+	pub message: usize,
+	/// The position of the token in the message, counting from 0.
+	pub position: usize,
+	/// The token as it was written.
+	pub typed: String,
+	/// The dictionary word it was corrected to.
+	pub corrected: String,
+	/// How many words with a meaning were one edit away from the token. The correction is
+	/// only certain when this is 1.
+	pub candidates: usize,
+}
+
+/// Whether `a` can be turned into `b` by inserting, deleting or replacing at most one
+/// character.
+fn within_one_edit(a: &[char], b: &[char]) -> bool {
+	let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+	if long.len() - short.len() > 1 {
+		return false;
+	}
+	let prefix = short.iter().zip(long).take_while(|(a, b)| a == b).count();
+	if short.len() == long.len() {
+		short[prefix..]
+			.iter()
+			.skip(1)
+			.eq(long[prefix..].iter().skip(1))
+	} else {
+		short[prefix..] == long[prefix + 1..]
+	}
+}
+
+/// Whether the word at `index` means anything under the mappings.
+fn is_mapped<D: Dictionary>(dict: &DictMappings<D>, index: u32) -> bool {
+	dict.reverse_lookup(index).is_some()
+		|| [
+			&dict.begin,
+			&dict.end,
+			&dict.fragment,
+			&dict.tags,
+			&dict.nulls,
+		]
+		.iter()
+		.any(|v| v.contains(&index))
+}
+
+/// The word with a meaning which is closest to `token`, ignoring case and any `punctuation`
+/// at either end, along with how many
+/// words were just as close. Only words within one edit are considered, and the earliest in
+/// the dictionary wins a tie.
+pub(crate) fn nearest<D: Dictionary>(
+	dict: &DictMappings<D>,
+	token: &str,
+	punctuation: &[char],
+) -> Option<(u32, usize)> {
+	let token = token.trim_matches(punctuation);
+	let token: Vec<char> = token.to_lowercase().chars().collect();
+	let byte_len = token.iter().map(|c| c.len_utf8()).sum::<usize>();
+	let mut best = None;
+	let mut candidates = 0;
+	for index in 0..dict.dictionary.len() as u32 {
+		let word = dict.dictionary.word(index);
+		// A character is at most 4 bytes, so anything further off can't be one edit away
+		if word.len() + 4 < byte_len || word.len() > byte_len + 4 {
+			continue;
+		}
+		let word: Vec<char> = word.to_lowercase().chars().collect();
+		if within_one_edit(&token, &word) && is_mapped(dict, index) {
+			best.get_or_insert(index);
+			candidates += 1;
+		}
+	}
+	best.map(|index| (index, candidates))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::within_one_edit;
+
+	#[test]
+	fn edits() {
+		let chars = |v: &str| v.chars().collect::<Vec<_>>();
+		assert!(within_one_edit(&chars("aardvark"), &chars("aardvark")));
+		assert!(within_one_edit(&chars("ardvark"), &chars("aardvark")));
+		assert!(within_one_edit(&chars("aardvarks"), &chars("aardvark")));
+		assert!(within_one_edit(&chars("aardvork"), &chars("aardvark")));
+		assert!(!within_one_edit(&chars("aradvrak"), &chars("aardvark")));
+		assert!(!within_one_edit(&chars("ardvar"), &chars("aardvark")));
+	}
+}
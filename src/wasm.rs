@@ -0,0 +1,35 @@
+//! Bindings for JavaScript, built with the `wasm` feature.
+//!
+//! Seeds are passed as decimal strings, since JavaScript numbers can't hold 128 bits.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{today, DictMappings};
+
+/// Today's mappings for a seed.
+#[wasm_bindgen]
+pub struct Mappings(DictMappings);
+
+#[wasm_bindgen]
+impl Mappings {
+	/// Build today's mappings from a seed written in decimal.
+	#[wasm_bindgen(js_name = fromSeed)]
+	pub fn from_seed(seed: &str) -> Result<Mappings, JsError> {
+		let seed: u128 = seed
+			.parse()
+			.map_err(|_| JsError::new("the seed isn't a 128-bit number"))?;
+		Ok(Mappings(DictMappings::from_seed(seed, &today())))
+	}
+}
+
+/// Don armor, returning an array of messages.
+#[wasm_bindgen]
+pub fn don(data: &[u8], mappings: &Mappings, character_limit: usize) -> Vec<String> {
+	crate::don(data, &mappings.0, character_limit)
+}
+
+/// Doff an array of messages, returning the recovered bytes.
+#[wasm_bindgen]
+pub fn doff(messages: Vec<String>, mappings: &Mappings) -> Result<Vec<u8>, JsError> {
+	Ok(crate::doff(&messages, &mappings.0)?)
+}
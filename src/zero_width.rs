@@ -0,0 +1,153 @@
+//! Carrying armor invisibly inside a cover text, as zero-width characters.
+//!
+//! The armor is donned as a single message, and each of its words is written as its index in
+//! the dictionary, in as many bits as the dictionary's last index needs, most significant
+//! first. A zero bit is a zero-width non-joiner and a one bit is a zero-width joiner. The bits
+//! are spread as evenly as possible over the ends of the cover text's words, each run coming
+//! straight before whitespace or the end of the text, so joiners inside emoji sequences are
+//! never mistaken for armor.
+
+use crate::{doff, don_with, CawError, DictMappings, Dictionary, Options, Separator};
+
+/// The character for a zero bit.
+const ZERO: char = '\u{200c}';
+/// The character for a one bit.
+const ONE: char = '\u{200d}';
+
+/// The number of bits each word is written in.
+fn index_bits<D: Dictionary>(dict: &DictMappings<D>) -> u32 {
+	u32::BITS - (dict.dictionary.len() as u32 - 1).leading_zeros()
+}
+
+/// Hide armor for `data` in `cover` as zero-width characters, which most programs don't show.
+/// Separators, fillers and natural rendering don't mean anything to this carrier, so they're
+/// ignored, but the rest of `options` is used.
+///
+/// Every word of armor takes 17 to 19 characters with English, so this is for short messages.
+/// Some platforms strip zero-width characters, and copying text out of others loses them.
+pub fn don_zero_width<D: Dictionary>(
+	data: &[u8],
+	cover: &str,
+	dict: &DictMappings<D>,
+	options: &Options,
+) -> String {
+	let options = Options {
+		separator: Separator::Space,
+		fillers: vec![],
+		natural: false,
+		..options.clone()
+	};
+	let armor = don_with(data, dict, usize::MAX, &options).concat();
+	let index_bits = index_bits(dict);
+	let bits: Vec<char> = armor
+		.split(' ')
+		.map(|v| {
+			dict.dictionary
+				.index_of(v)
+				.expect("armor is made of dictionary words")
+		})
+		.flat_map(|index| {
+			(0..index_bits)
+				.rev()
+				.map(move |v| if (index >> v) & 1 == 0 { ZERO } else { ONE })
+		})
+		.collect();
+	// The end of every word, and the end of the text
+	let ends: Vec<usize> = cover
+		.char_indices()
+		.zip(cover.chars().skip(1))
+		.filter(|((_, v), next)| !v.is_whitespace() && next.is_whitespace())
+		.map(|((at, v), _)| at + v.len_utf8())
+		.chain(std::iter::once(cover.len()))
+		.collect();
+	let mut text = String::with_capacity(cover.len() + bits.len() * 3);
+	let mut copied = 0;
+	for (position, end) in ends.iter().enumerate() {
+		text.push_str(&cover[copied..*end]);
+		let start = position * bits.len() / ends.len();
+		let stop = (position + 1) * bits.len() / ends.len();
+		text.extend(&bits[start..stop]);
+		copied = *end;
+	}
+	text
+}
+
+/// The runs of zero-width characters which carry armor in `text`, and the text without them.
+fn extract(text: &str) -> (Vec<char>, String) {
+	let mut bits = vec![];
+	let mut cover = String::with_capacity(text.len());
+	let mut run = String::new();
+	for v in text.chars() {
+		if v == ZERO || v == ONE {
+			run.push(v);
+			continue;
+		}
+		if v.is_whitespace() {
+			bits.extend(run.drain(..));
+		} else {
+			cover.push_str(&run);
+			run.clear();
+		}
+		cover.push(v);
+	}
+	bits.extend(run.drain(..));
+	(bits, cover)
+}
+
+/// Doff armor hidden in `text` by `don_zero_width`.
+pub fn doff_zero_width<D: Dictionary>(
+	text: &str,
+	dict: &DictMappings<D>,
+) -> Result<Vec<u8>, CawError> {
+	let (bits, _) = extract(text);
+	let index_bits = index_bits(dict) as usize;
+	let words = bits
+		.chunks(index_bits)
+		// Bits left over at the end aren't a whole word
+		.filter(|v| v.len() == index_bits)
+		.map(|v| {
+			let index = v
+				.iter()
+				.fold(0, |index, v| (index << 1) | (*v == ONE) as u32);
+			if (index as usize) < dict.dictionary.len() {
+				Ok(dict.dictionary.word(index))
+			} else {
+				Err(CawError::UnknownWord {
+					message: 0,
+					word: index.to_string(),
+				})
+			}
+		})
+		.collect::<Result<Vec<&str>, CawError>>()?;
+	doff(&[words.join(" ")], dict)
+}
+
+/// The cover text which armor was hidden in by `don_zero_width`, without the armor.
+pub fn strip_zero_width(text: &str) -> String {
+	extract(text).1
+}
+
+#[cfg(all(test, not(feature = "no-embedded-dict")))]
+mod tests {
+	use super::{doff_zero_width, don_zero_width, strip_zero_width};
+	use crate::{today, DictMappings, Options};
+
+	#[test]
+	fn zero_width() {
+		let dict = DictMappings::from_seed(69, &today());
+		let cover = "Nothing to see here \u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\nat all.";
+		let test_data = "Hidden in plain sight".as_bytes();
+		let options = Options {
+			checksum: true,
+			..Options::default()
+		};
+		let hidden = don_zero_width(test_data, cover, &dict, &options);
+		assert_eq!(strip_zero_width(&hidden), cover);
+		assert_eq!(doff_zero_width(&hidden, &dict).unwrap(), test_data);
+		// The emoji's joiners are left alone
+		assert!(hidden.contains("\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}"));
+		let hidden = don_zero_width(test_data, "", &dict, &options);
+		assert_eq!(doff_zero_width(&hidden, &dict).unwrap(), test_data);
+		assert!(doff_zero_width(cover, &dict).is_err());
+	}
+}